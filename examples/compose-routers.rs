@@ -0,0 +1,72 @@
+use http::Method;
+use log::info;
+use roa::core::{App, Context, Result};
+use roa::preload::*;
+use roa::router::{Router, RouterParam};
+use std::error::Error as StdError;
+use std::result::Result as StdResult;
+
+async fn get_user(mut ctx: Context<()>) -> Result {
+    let id = ctx.must_param("id").await?;
+    ctx.write_text(format!("user {}", id.as_ref())).await
+}
+
+async fn delete_user(mut ctx: Context<()>) -> Result {
+    let id = ctx.must_param("id").await?;
+    ctx.write_text(format!("user {} deleted", id.as_ref()))
+        .await
+}
+
+async fn list_posts(mut ctx: Context<()>) -> Result {
+    ctx.write_text("posts").await
+}
+
+async fn create_post(mut ctx: Context<()>) -> Result {
+    ctx.write_text("post created").await
+}
+
+async fn stats(mut ctx: Context<()>) -> Result {
+    ctx.write_text("stats").await
+}
+
+/// Routes of users, usually in their own module.
+fn users() -> Router<()> {
+    let mut router = Router::new();
+    router.get("/:id", get_user).delete("/:id", delete_user);
+    router
+}
+
+/// Routes of posts.
+fn posts() -> Router<()> {
+    let mut router = Router::new();
+    router.get("/", list_posts).post("/", create_post);
+    router
+}
+
+/// Routes of administration.
+fn admin() -> Router<()> {
+    let mut router = Router::new();
+    router.get("/stats", stats).delete("/stats", stats);
+    router
+}
+
+#[async_std::main]
+async fn main() -> StdResult<(), Box<dyn StdError>> {
+    pretty_env_logger::init();
+
+    // conflicts between sub-routers are reported by the include introducing them.
+    let router = Router::compose()
+        .include("/user", users())?
+        .include("/post", posts())?
+        // the admin routes are read-only in this deployment.
+        .include_methods("/admin", admin(), [Method::GET])?
+        .build();
+
+    App::new(())
+        .gate(router.routes("/api")?)
+        .listen("127.0.0.1:8000", |addr| {
+            info!("Server is listening on {}", addr)
+        })?
+        .await?;
+    Ok(())
+}
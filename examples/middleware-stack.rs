@@ -0,0 +1,61 @@
+use log::info;
+use roa::core::header::{HeaderValue, SERVER};
+use roa::core::{App, Context, MiddlewareStack, Next, Result};
+use roa::logger::logger;
+use roa::preload::*;
+use roa::router::Router;
+use std::error::Error as StdError;
+use std::result::Result as StdResult;
+use std::time::Instant;
+
+/// Report how long downstream takes in a response header.
+async fn timer(mut ctx: Context<()>, next: Next) -> Result {
+    let start = Instant::now();
+    next().await?;
+    let elapsed = start.elapsed().as_micros().to_string();
+    ctx.resp_mut().await.insert("x-response-time", elapsed)?;
+    Ok(())
+}
+
+/// Set the "Server" header.
+async fn server(mut ctx: Context<()>, next: Next) -> Result {
+    ctx.resp_mut()
+        .await
+        .headers
+        .insert(SERVER, HeaderValue::from_static("roa"));
+    next().await
+}
+
+async fn users(mut ctx: Context<()>) -> Result {
+    ctx.write_text("users").await
+}
+
+async fn posts(mut ctx: Context<()>) -> Result {
+    ctx.write_text("posts").await
+}
+
+#[async_std::main]
+async fn main() -> StdResult<(), Box<dyn StdError>> {
+    pretty_env_logger::init();
+
+    // a stack shared by both routers
+    let mut stack = MiddlewareStack::new();
+    stack.push(logger).push(timer).push(server);
+
+    let mut user = Router::new();
+    user.gate(stack.clone()).get("/", users);
+
+    let mut post = Router::new();
+    post.gate(stack).get("/", posts);
+
+    let mut router = Router::new();
+    router.include("/user", user).include("/post", post);
+
+    App::new(())
+        .gate(router.routes("/")?)
+        .listen("127.0.0.1:8000", |addr| {
+            info!("Server is listening on {}", addr)
+        })?
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use futures::stream::{self, Stream, StreamExt};
+use log::info;
+use roa::core::App;
+use roa::preload::*;
+use serde::Serialize;
+use std::error::Error as StdError;
+
+const TOTAL: usize = 100_000;
+const BATCH_SIZE: usize = 100;
+
+#[derive(Debug, Serialize)]
+struct User {
+    id: usize,
+    name: String,
+}
+
+/// Simulate a database query fetching a batch of rows.
+async fn fetch(offset: usize) -> Vec<User> {
+    (offset..TOTAL.min(offset + BATCH_SIZE))
+        .map(|id| User {
+            id,
+            name: format!("user-{}", id),
+        })
+        .collect()
+}
+
+/// A cursor yielding rows batch by batch, only one batch lives in memory.
+fn cursor() -> impl Stream<Item = User> + Unpin + Sync + Send {
+    Box::pin(
+        stream::unfold(0, |offset| async move {
+            let rows = fetch(offset).await;
+            if rows.is_empty() {
+                None
+            } else {
+                Some((stream::iter(rows), offset + BATCH_SIZE))
+            }
+        })
+        .flatten(),
+    )
+}
+
+#[async_std::main]
+async fn main() -> Result<(), Box<dyn StdError>> {
+    pretty_env_logger::init();
+    let mut app = App::new(());
+    app.end(|mut ctx| async move { ctx.write_json_array(cursor()).await })
+        .listen("127.0.0.1:8000", |addr| {
+            info!("Server is listening on {}", addr)
+        })?
+        .await?;
+    Ok(())
+}
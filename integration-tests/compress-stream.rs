@@ -0,0 +1,128 @@
+use async_compression::futures::bufread::{GzipDecoder, GzipEncoder};
+use async_std::task::spawn;
+use futures::io::Cursor;
+use futures::{AsyncRead, AsyncReadExt};
+use http::header::{ACCEPT_ENCODING, CONTENT_ENCODING};
+use http::StatusCode;
+use roa::compress::{Compress, Decompress};
+use roa::core::{App, Context, Result};
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::Poll;
+
+/// A reader converting ascii letters to uppercase as they are read.
+struct Uppercase<R>(R);
+
+impl<R: AsyncRead + Unpin> AsyncRead for Uppercase<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let size = futures::ready!(Pin::new(&mut self.0).poll_read(cx, buf))?;
+        buf[..size].make_ascii_uppercase();
+        Poll::Ready(Ok(size))
+    }
+}
+
+/// Bytes read through `Counted`.
+static READ: AtomicUsize = AtomicUsize::new(0);
+
+/// A reader counting bytes into `READ` as they are read.
+struct Counted<R>(R);
+
+impl<R: AsyncRead + Unpin> AsyncRead for Counted<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let size = futures::ready!(Pin::new(&mut self.0).poll_read(cx, buf))?;
+        READ.fetch_add(size, Ordering::SeqCst);
+        Poll::Ready(Ok(size))
+    }
+}
+
+/// Transform request body into response body, without reading it.
+async fn uppercase(mut ctx: Context<()>) -> Result {
+    let body = std::mem::take(&mut **ctx.req_mut().await);
+    ctx.resp_mut().await.write(Uppercase(body));
+    Ok(())
+}
+
+async fn gzip(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut compressed = Vec::new();
+    GzipEncoder::new(Cursor::new(data))
+        .read_to_end(&mut compressed)
+        .await?;
+    Ok(compressed)
+}
+
+async fn gunzip(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    GzipDecoder::new(Cursor::new(data))
+        .read_to_end(&mut decompressed)
+        .await?;
+    Ok(decompressed)
+}
+
+#[tokio::test]
+async fn round_trip_gzip() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let (addr, server) = App::new(())
+        .gate(Compress::default())
+        .gate(Decompress::default())
+        .end(uppercase)
+        .run_local()?;
+    spawn(server);
+    // larger than buffers of codecs and connection, so it must be streamed in pieces.
+    let payload = b"Hello, World!\n".repeat(64 * 1024);
+    // decompress response body manually, to make sure it's compressed.
+    let resp = reqwest::Client::builder()
+        .gzip(false)
+        .build()?
+        .post(&format!("http://{}", addr))
+        .header(CONTENT_ENCODING, "gzip")
+        .header(ACCEPT_ENCODING, "gzip")
+        .body(gzip(&payload).await?)
+        .send()
+        .await?;
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!("gzip", resp.headers()[CONTENT_ENCODING]);
+    let body = gunzip(&resp.bytes().await?).await?;
+    assert_eq!(payload.to_ascii_uppercase(), body);
+    Ok(())
+}
+
+#[tokio::test]
+async fn streaming_bomb() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let (addr, server) = App::new(())
+        .gate(Compress::default())
+        .gate(Decompress::builder().max_size(1024 * 1024).build())
+        .end(|mut ctx| async move {
+            let body = std::mem::take(&mut **ctx.req_mut().await);
+            ctx.resp_mut().await.write(Uppercase(Counted(body)));
+            Ok(())
+        })
+        .run_local()?;
+    spawn(server);
+    let bomb = gzip(&vec![b'a'; 8 * 1024 * 1024]).await?;
+    let result = reqwest::Client::new()
+        .post(&format!("http://{}", addr))
+        .header(CONTENT_ENCODING, "gzip")
+        .header(ACCEPT_ENCODING, "gzip")
+        .body(bomb)
+        .send()
+        .await;
+    // the body is read after the status and headers are sent,
+    // so 413 PAYLOAD TOO LARGE cannot be observed and the connection is aborted instead.
+    let completed = match result {
+        Ok(resp) => resp.bytes().await.is_ok(),
+        Err(_) => false,
+    };
+    assert!(!completed);
+    // the body is streamed, but never beyond the limit.
+    let read = READ.load(Ordering::SeqCst);
+    assert!(read > 0 && read <= 1024 * 1024, "{} bytes are read", read);
+    Ok(())
+}
@@ -1,20 +1,28 @@
 #[cfg(feature = "runtime")]
 mod executor;
 
+mod limits;
 mod tcp;
+use crate::group::Conditional;
 use crate::{
     join, join_all, Context, Error, Middleware, Model, Next, Request, Response, Result,
+    ResultFuture,
 };
-use http::{Request as HttpRequest, Response as HttpResponse};
+use futures::future::{FutureExt, Shared};
+use http::header::{HeaderMap, HeaderValue, SERVER};
+use http::{Method, Request as HttpRequest, Response as HttpResponse, StatusCode};
 use hyper::service::Service;
 use hyper::Body as HyperBody;
 use std::future::Future;
 use std::pin::Pin;
 use std::result::Result as StdResult;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::task::Poll;
 
-pub use tcp::{AddrIncoming, AddrStream};
+pub(crate) use limits::uri_length;
+pub use limits::Limits;
+pub use tcp::{AddrIncoming, AddrStream, SocketOpts};
 
 /// The Application of roa.
 /// ### Example
@@ -130,33 +138,370 @@ pub use tcp::{AddrIncoming, AddrStream};
 ///     Ok(())
 /// }
 /// ```
+///
+/// Use `App::with_graceful_shutdown` instead
+/// to run hooks registered by `App::on_shutdown`.
+///
+/// ### Clone
+///
+/// Cloning an `App` or a `HttpService` only clones the `Arc` of middleware and model,
+/// the model is never copied, no matter how large it is.
 pub struct App<M: Model> {
     middleware: Arc<dyn Middleware<M::State>>,
+    server_header: ServerHeader,
+    default_headers: Arc<HeaderMap>,
+    limits: Limits,
+    warn_skipped_next: bool,
+    early_hints: bool,
+    active_requests: Arc<AtomicUsize>,
+    startup_hooks: Vec<Hook>,
+    shutdown_hooks: Vec<Hook>,
+    startup: Startup,
     pub(crate) model: Arc<M>,
 }
 
+/// How the "Server" header of responses is handled, set by `App::server_header`.
+#[derive(Clone)]
+enum ServerHeader {
+    /// Keep the header set by middleware, if any.
+    Untouched,
+
+    /// Overwrite the header.
+    Set(HeaderValue),
+
+    /// Remove the header.
+    Removed,
+}
+
+/// A lifecycle hook registered by `App::on_startup` or `App::on_shutdown`.
+type Hook = Arc<dyn 'static + Sync + Send + Fn() -> ResultFuture>;
+
+/// Startup hooks in progress, shared by all servers of `App::listen_many`.
+type StartupFuture =
+    Shared<Pin<Box<dyn 'static + Future<Output = StdResult<(), String>> + Send>>>;
+
+/// Startup state of an app serving as the make service of a server.
+enum Startup {
+    Idle,
+    Running(StartupFuture),
+    Done,
+}
+
+/// Run startup hooks once, however many servers poll the returned future.
+fn start(hooks: Vec<Hook>) -> StartupFuture {
+    let startup: Pin<Box<dyn 'static + Future<Output = _> + Send>> =
+        Box::pin(async move { run_hooks(hooks).await.map_err(|err| err.to_string()) });
+    startup.shared()
+}
+
+/// Run hooks one by one, stop at the first error.
+async fn run_hooks(hooks: Vec<Hook>) -> Result {
+    for hook in hooks {
+        hook().await?;
+    }
+    Ok(())
+}
+
+/// Run all shutdown hooks, log the failed ones.
+async fn run_shutdown_hooks(hooks: Vec<Hook>) {
+    for hook in hooks {
+        if let Err(err) = hook().await {
+            log::error!("shutdown hook fails: {}", err);
+        }
+    }
+}
+
 /// An implementation of hyper HttpService.
 pub struct HttpService<M: Model> {
     middleware: Arc<dyn Middleware<M::State>>,
     stream: AddrStream,
+    server_header: ServerHeader,
+    default_headers: Arc<HeaderMap>,
+    limits: Limits,
+    warn_skipped_next: bool,
+    early_hints: bool,
+    active_requests: Arc<AtomicUsize>,
     pub(crate) model: Arc<M>,
 }
 
+/// Decrease the gauge of active requests on drop,
+/// even if the serving future is cancelled.
+struct ActiveGuard(Arc<AtomicUsize>);
+
+impl ActiveGuard {
+    fn new(counter: Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 impl<M: Model> App<M> {
     /// Construct an application from a model.
     pub fn new(model: M) -> Self {
         Self {
             middleware: Arc::new(join_all(Vec::new())),
+            server_header: ServerHeader::Untouched,
+            default_headers: Arc::new(HeaderMap::new()),
+            limits: Limits::default(),
+            warn_skipped_next: false,
+            early_hints: false,
+            active_requests: Arc::new(AtomicUsize::new(0)),
+            startup_hooks: Vec::new(),
+            shutdown_hooks: Vec::new(),
+            startup: Startup::Idle,
             model: Arc::new(model),
         }
     }
 
+    /// Number of requests being served by servers of this app (and its clones).
+    ///
+    /// A request is active from entering the middleware chain
+    /// until the response is handed to hyper, the time to send response body is not counted.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let app = App::new(());
+    ///     let (addr, server) = app.run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     assert_eq!(0, app.active_requests());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn active_requests(&self) -> usize {
+        self.active_requests.load(Ordering::SeqCst)
+    }
+
+    /// Set the "Server" header of every response, or remove it by `None`.
+    ///
+    /// By default, the "Server" header is left as middleware sets it,
+    /// and it's absent if no middleware sets it.
+    /// Once this method is called,
+    /// any value set by middleware is overwritten or removed.
+    ///
+    /// ### Panics
+    ///
+    /// Panics if value is not a valid header value.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa_core::App;
+    /// use roa_core::header::SERVER;
+    /// use async_std::task::spawn;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .server_header(Some("roa"))
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!("roa", resp.headers()[SERVER]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn server_header(&mut self, value: Option<&str>) -> &mut Self {
+        self.server_header = match value {
+            Some(value) => match HeaderValue::from_str(value) {
+                Ok(value) => ServerHeader::Set(value),
+                Err(_) => panic!("{} is not a valid header value", value),
+            },
+            None => ServerHeader::Removed,
+        };
+        self
+    }
+
+    /// Set headers added to every response, unless the response already has them.
+    ///
+    /// A header set by middleware wins over the default one of the same name,
+    /// all values of a default header are added if it's absent.
+    /// It's cheaper than a middleware for a fixed set of headers.
+    ///
+    /// A default "Server" header is overwritten or removed
+    /// if `App::server_header` is called.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa_core::App;
+    /// use roa_core::header::{HeaderMap, HeaderValue};
+    /// use async_std::task::spawn;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut headers = HeaderMap::new();
+    ///     headers.insert("x-powered-by", HeaderValue::from_static("roa"));
+    ///     let (addr, server) = App::new(())
+    ///         .default_headers(headers)
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!("roa", resp.headers()["x-powered-by"]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn default_headers(&mut self, headers: HeaderMap) -> &mut Self {
+        self.default_headers = Arc::new(headers);
+        self
+    }
+
+    /// Set limits of incoming requests, see `Limits`.
+    ///
+    /// Requests exceeding the limits are rejected before the middleware chain,
+    /// so it works even if no middleware cares about the size of requests.
+    pub fn limits(&mut self, limits: Limits) -> &mut Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Log a warning if a middleware except the last one of a group returns `Ok`
+    /// without calling next, which is usually a bug of forgetting `next().await`.
+    ///
+    /// It's disabled by default, as many middlewares short-circuit on purpose,
+    /// like a CORS preflight or a cached response. Warn at most once per request.
+    pub fn warn_skipped_next(&mut self, enabled: bool) -> &mut Self {
+        self.warn_skipped_next = enabled;
+        self
+    }
+
+    /// Allow `Context::send_early_hints` to send interim responses, disabled by default.
+    ///
+    /// Early hints are written to the connection behind hyper,
+    /// which may corrupt the response stream in cases hyper doesn't expose.
+    /// Enable it only if you have tested it with your clients and proxies.
+    pub fn early_hints(&mut self, enabled: bool) -> &mut Self {
+        self.early_hints = enabled;
+        self
+    }
+
+    /// Register a hook running before a server of this app begins accepting connections.
+    ///
+    /// Hooks run in order of registration, once for each server
+    /// (or once for all servers of `App::listen_many`),
+    /// connections are kept in the backlog of listener until all of them complete.
+    /// If any hook fails, the rest are skipped, no connection is served,
+    /// and the server fails with the error.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let warmed = Arc::new(AtomicBool::new(false));
+    ///     let cache = warmed.clone();
+    ///     let (addr, server) = App::new(())
+    ///         .on_startup(move || {
+    ///             let cache = cache.clone();
+    ///             async move {
+    ///                 // warm the cache or run migrations...
+    ///                 cache.store(true, Ordering::SeqCst);
+    ///                 Ok(())
+    ///             }
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     assert!(warmed.load(Ordering::SeqCst));
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn on_startup<F>(
+        &mut self,
+        hook: impl 'static + Sync + Send + Fn() -> F,
+    ) -> &mut Self
+    where
+        F: 'static + Send + Future<Output = Result>,
+    {
+        self.startup_hooks.push(Arc::new(move || Box::pin(hook())));
+        self
+    }
+
+    /// Register a hook running after a server of this app is shut down
+    /// by `App::with_graceful_shutdown`,
+    /// or once after the future returned by `App::listen_many` completes.
+    ///
+    /// Hooks run in order of registration, once all connections are closed.
+    /// A failed hook is logged, and the rest still run.
+    pub fn on_shutdown<F>(
+        &mut self,
+        hook: impl 'static + Sync + Send + Fn() -> F,
+    ) -> &mut Self
+    where
+        F: 'static + Send + Future<Output = Result>,
+    {
+        self.shutdown_hooks.push(Arc::new(move || Box::pin(hook())));
+        self
+    }
+
     /// Use a middleware.
     pub fn gate(&mut self, middleware: impl Middleware<M::State>) -> &mut Self {
         self.middleware = Arc::new(join(self.middleware.clone(), middleware));
         self
     }
 
+    /// Use a middleware only if the predicate resolves to true, evaluated per request;
+    /// otherwise the middleware is skipped and next is called directly.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa_core::{App, Context, Next};
+    /// use roa_core::header::{HeaderValue, CACHE_CONTROL};
+    /// use async_std::task::spawn;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .gate_if(
+    ///             |ctx| async move { ctx.uri().await.path().starts_with("/admin") },
+    ///             |mut ctx: Context<()>, next: Next| async move {
+    ///                 let no_store = HeaderValue::from_static("no-store");
+    ///                 ctx.resp_mut().await.headers.insert(CACHE_CONTROL, no_store);
+    ///                 next().await
+    ///             },
+    ///         )
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}/admin", addr)).await?;
+    ///     assert_eq!("no-store", resp.headers()[CACHE_CONTROL]);
+    ///     let resp = reqwest::get(&format!("http://{}/user", addr)).await?;
+    ///     assert!(resp.headers().get(CACHE_CONTROL).is_none());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn gate_if<F>(
+        &mut self,
+        predicate: impl 'static + Sync + Send + Fn(Context<M::State>) -> F,
+        middleware: impl Middleware<M::State>,
+    ) -> &mut Self
+    where
+        F: 'static + Send + Future<Output = bool>,
+    {
+        self.gate(Conditional::new(predicate, middleware))
+    }
+
     /// A sugar to match a lambda as a middleware.
     ///
     /// `App::gate` cannot match a lambda without parameter type indication.
@@ -242,11 +587,14 @@ use executor::Executor;
 use hyper::Server as HyperServer;
 
 #[cfg(feature = "runtime")]
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
 
 #[cfg(feature = "runtime")]
 type Server<M> = HyperServer<AddrIncoming, App<M>, Executor>;
 
+#[cfg(feature = "runtime")]
+type MultiServer = Pin<Box<dyn 'static + Future<Output = hyper::Result<()>> + Send>>;
+
 #[cfg(feature = "runtime")]
 impl<M: Model> App<M> {
     /// Listen on a socket addr, return a server and the real addr it binds.
@@ -254,7 +602,14 @@ impl<M: Model> App<M> {
         &self,
         addr: impl ToSocketAddrs,
     ) -> std::io::Result<(SocketAddr, Server<M>)> {
-        let incoming = AddrIncoming::bind(addr)?;
+        self.accept(AddrIncoming::bind(addr)?)
+    }
+
+    /// Serve on an incoming, return a server and the real addr it binds.
+    fn accept(
+        &self,
+        incoming: AddrIncoming,
+    ) -> std::io::Result<(SocketAddr, Server<M>)> {
         let local_addr = incoming.local_addr();
         let server = HyperServer::builder(incoming)
             .executor(Executor)
@@ -262,6 +617,53 @@ impl<M: Model> App<M> {
         Ok((local_addr, server))
     }
 
+    /// Serve on an already bound listener,
+    /// return a server, and pass real addr to the callback.
+    ///
+    /// It enables socket activation or handing off a listener between processes
+    /// for zero-downtime restarts.
+    ///
+    /// ### Platform specifics
+    ///
+    /// To serve on an inherited file descriptor, construct the listener by
+    /// `std::os::unix::io::FromRawFd::from_raw_fd` on unix,
+    /// or `std::os::windows::io::FromRawSocket::from_raw_socket` on windows.
+    /// Systemd passes sockets starting from fd 3 (`SD_LISTEN_FDS_START`),
+    /// the count is in the environment variable `LISTEN_FDS`.
+    ///
+    /// Descriptors are inherited by child processes only if `FD_CLOEXEC` is not set,
+    /// while sockets created by std set it by default.
+    /// The caller is responsible for the validity of the raw descriptor.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    /// use std::net::TcpListener;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let listener = TcpListener::bind("127.0.0.1:0")?;
+    ///     let addr = listener.local_addr()?;
+    ///     let server = App::new(()).serve_incoming(listener, |_addr| ())?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn serve_incoming(
+        &self,
+        listener: TcpListener,
+        callback: impl Fn(SocketAddr),
+    ) -> std::io::Result<Server<M>> {
+        let (addr, server) = self.accept(AddrIncoming::from_std(listener)?)?;
+        callback(addr);
+        Ok(server)
+    }
+
     /// Listen on a socket addr, return a server, and pass real addr to the callback.
     pub fn listen(
         &self,
@@ -273,6 +675,120 @@ impl<M: Model> App<M> {
         Ok(server)
     }
 
+    /// Listen on a socket addr with options of the listening socket, return a server,
+    /// and pass real addr to the callback.
+    ///
+    /// With `SocketOpts::reuse_port`, an operator can start a new binary on the same addr
+    /// before stopping the old one, for zero-downtime deploys.
+    /// See `SocketOpts` for platform support.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa_core::{App, SocketOpts};
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let opts = SocketOpts {
+    ///         reuse_address: true,
+    ///         reuse_port: cfg!(target_os = "linux"),
+    ///     };
+    ///     let mut addr = None;
+    ///     let server = App::new(())
+    ///         .listen_with_opts("127.0.0.1:0", opts, |real| addr = Some(real))?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr.unwrap())).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn listen_with_opts(
+        &self,
+        addr: impl ToSocketAddrs,
+        opts: SocketOpts,
+        callback: impl FnOnce(SocketAddr),
+    ) -> std::io::Result<Server<M>> {
+        let (addr, server) = self.accept(AddrIncoming::bind_with(addr, opts)?)?;
+        callback(addr);
+        Ok(server)
+    }
+
+    /// Listen on multiple socket addrs, like both IPv4 and IPv6, or multiple ports.
+    /// Return a future serving all of them by the same middleware,
+    /// and pass each real addr to the callback.
+    ///
+    /// Fails if addrs is empty or any addr cannot be bound,
+    /// the callback is never called in this case.
+    /// The future completes when any server fails.
+    ///
+    /// Hooks registered by `App::on_startup` run once before any server accepts
+    /// connections, and those by `App::on_shutdown` run once after it completes.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut addrs = Vec::new();
+    ///     let server = App::new(()).listen_many(
+    ///         vec!["127.0.0.1:0".parse()?, "127.0.0.1:0".parse()?],
+    ///         |addr| addrs.push(addr),
+    ///     )?;
+    ///     spawn(server);
+    ///     for addr in addrs {
+    ///         let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///         assert_eq!(StatusCode::OK, resp.status());
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn listen_many(
+        &self,
+        addrs: impl IntoIterator<Item = SocketAddr>,
+        mut callback: impl FnMut(SocketAddr),
+    ) -> std::io::Result<MultiServer> {
+        let startup = start(self.startup_hooks.clone());
+        let mut servers = Vec::new();
+        for addr in addrs {
+            let incoming = AddrIncoming::bind(addr).map_err(|err| {
+                std::io::Error::new(
+                    err.kind(),
+                    format!("fail to listen on {}: {}", addr, err),
+                )
+            })?;
+            let addr = incoming.local_addr();
+            let mut app = self.clone();
+            app.startup = Startup::Running(startup.clone());
+            let server = HyperServer::builder(incoming).executor(Executor).serve(app);
+            servers.push((addr, server));
+        }
+        if servers.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "no address to listen on",
+            ));
+        }
+        let servers: Vec<_> = servers
+            .into_iter()
+            .map(|(addr, server)| {
+                callback(addr);
+                server
+            })
+            .collect();
+        let shutdown_hooks = self.shutdown_hooks.clone();
+        Ok(Box::pin(async move {
+            let result = futures::future::try_join_all(servers).await;
+            run_shutdown_hooks(shutdown_hooks).await;
+            result.map(|_| ())
+        }))
+    }
+
     /// Listen on an unused port of 0.0.0.0, return a server and the real addr it binds.
     pub fn run(&self) -> std::io::Result<(SocketAddr, Server<M>)> {
         self.listen_on("0.0.0.0:0")
@@ -305,12 +821,66 @@ impl<M: Model> App<M> {
     pub fn run_local(&self) -> std::io::Result<(SocketAddr, Server<M>)> {
         self.listen_on("127.0.0.1:0")
     }
+
+    /// Prepare `server` for graceful shutdown once `signal` resolves,
+    /// like `hyper::Server::with_graceful_shutdown`,
+    /// and run hooks registered by `App::on_shutdown` after all connections are closed.
+    ///
+    /// Hooks run even if the server fails, and the result of server is returned.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa_core::App;
+    /// use futures::channel::oneshot;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let closed = Arc::new(AtomicBool::new(false));
+    ///     let pool = closed.clone();
+    ///     let mut app = App::new(());
+    ///     app.on_shutdown(move || {
+    ///         let pool = pool.clone();
+    ///         async move {
+    ///             // close database connections...
+    ///             pool.store(true, Ordering::SeqCst);
+    ///             Ok(())
+    ///         }
+    ///     });
+    ///     let (tx, rx) = oneshot::channel::<()>();
+    ///     let (_addr, server) = app.run_local()?;
+    ///     let _ = tx.send(());
+    ///     app.with_graceful_shutdown(server, async {
+    ///         rx.await.ok();
+    ///     })
+    ///     .await?;
+    ///     assert!(closed.load(Ordering::SeqCst));
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_graceful_shutdown(
+        &self,
+        server: Server<M>,
+        signal: impl 'static + Send + Future<Output = ()>,
+    ) -> impl 'static + Send + Future<Output = hyper::Result<()>> {
+        let hooks = self.shutdown_hooks.clone();
+        async move {
+            let result = server.with_graceful_shutdown(signal).await;
+            run_shutdown_hooks(hooks).await;
+            result
+        }
+    }
 }
 
 macro_rules! impl_poll_ready {
     () => {
         #[inline]
-        fn poll_ready(&mut self, _cx: &mut std::task::Context<'_>) -> Poll<StdResult<(), Self::Error>> {
+        fn poll_ready(
+            &mut self,
+            _cx: &mut std::task::Context<'_>,
+        ) -> Poll<StdResult<(), Self::Error>> {
             Poll::Ready(Ok(()))
         }
     };
@@ -323,14 +893,50 @@ impl<M: Model> Service<&AddrStream> for App<M> {
     type Response = HttpService<M>;
     type Error = std::io::Error;
     type Future = AppFuture<M>;
-    impl_poll_ready!();
+
+    /// Run startup hooks before the first connection is accepted.
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<StdResult<(), Self::Error>> {
+        loop {
+            match self.startup {
+                Startup::Done => return Poll::Ready(Ok(())),
+                Startup::Idle => {
+                    self.startup = Startup::Running(start(self.startup_hooks.clone()));
+                }
+                Startup::Running(ref mut startup) => {
+                    let result = futures::ready!(startup.poll_unpin(cx));
+                    self.startup = Startup::Done;
+                    return Poll::Ready(result.map_err(|err| {
+                        std::io::Error::new(std::io::ErrorKind::Other, err)
+                    }));
+                }
+            }
+        }
+    }
 
     #[inline]
     fn call(&mut self, stream: &AddrStream) -> Self::Future {
         let middleware = self.middleware.clone();
         let stream = stream.clone();
         let model = self.model.clone();
-        Box::pin(async move { Ok(HttpService::new(middleware, stream, model)) })
+        let server_header = self.server_header.clone();
+        let default_headers = self.default_headers.clone();
+        let limits = self.limits;
+        let warn_skipped_next = self.warn_skipped_next;
+        let early_hints = self.early_hints;
+        let active_requests = self.active_requests.clone();
+        Box::pin(async move {
+            let mut service = HttpService::new(middleware, stream, model);
+            service.server_header = server_header;
+            service.default_headers = default_headers;
+            service.limits = limits;
+            service.warn_skipped_next = warn_skipped_next;
+            service.early_hints = early_hints;
+            service.active_requests = active_requests;
+            Ok(service)
+        })
     }
 }
 
@@ -359,14 +965,48 @@ impl<M: Model> HttpService<M> {
         Self {
             middleware,
             stream,
+            server_header: ServerHeader::Untouched,
+            default_headers: Arc::new(HeaderMap::new()),
+            limits: Limits::default(),
+            warn_skipped_next: false,
+            early_hints: false,
+            active_requests: Arc::new(AtomicUsize::new(0)),
             model,
         }
     }
 
-    pub async fn serve(&self, req: Request) -> Result<Response> {
+    pub async fn serve(&self, mut req: Request) -> Result<Response> {
+        let _active = ActiveGuard::new(self.active_requests.clone());
+        self.stream.count_request();
+        let is_head = req.method == Method::HEAD;
+        let checked = self.limits.check(&mut req);
         let mut context = Context::new(req, self.model.new_state(), self.stream.clone());
-        let middleware = self.middleware.clone();
-        if let Err(err) = middleware.end(context.clone()).await {
+        context.warn_skipped_next = self.warn_skipped_next;
+        context.early_hints = self.early_hints;
+        let result = match checked {
+            Err(err) => Err(err),
+            Ok(exceeded) => {
+                let middleware = self.middleware.clone();
+                let result = middleware.end(context.clone()).await;
+                if exceeded.load(Ordering::SeqCst) {
+                    // discard the response of a request whose body is too large,
+                    // callbacks registered by `on_response_sent` are kept.
+                    context.take_response().await;
+                    Err(self.limits.body_too_large())
+                } else {
+                    result
+                }
+            }
+        };
+        if context.is_connection_aborted() {
+            return Err(Error::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "connection is aborted",
+                false,
+            ));
+        }
+        if let Err(err) = result {
+            context.resp_mut().await.clear_deferred();
             context.resp_mut().await.status = err.status_code;
             if err.expose {
                 context.resp_mut().await.write_str(&err.message);
@@ -375,8 +1015,37 @@ impl<M: Model> HttpService<M> {
                 return Err(err);
             }
         }
-        let mut response = context.resp_mut().await;
-        Ok(std::mem::take(&mut *response))
+        let mut response = std::mem::take(&mut *context.resp_mut().await);
+        if let Err(err) = response.resolve_deferred().await {
+            log::error!("fail to evaluate deferred body: {}", err);
+            let mut failed = Response::new();
+            failed.sent = std::mem::take(&mut response.sent);
+            failed.status = StatusCode::INTERNAL_SERVER_ERROR;
+            if err.expose {
+                failed.write_str(&err.message);
+            }
+            response = failed;
+        }
+        for name in self.default_headers.keys() {
+            if !response.headers.contains_key(name) {
+                for value in self.default_headers.get_all(name) {
+                    response.headers.append(name, value.clone());
+                }
+            }
+        }
+        match self.server_header {
+            ServerHeader::Untouched => (),
+            ServerHeader::Set(ref value) => {
+                response.headers.insert(SERVER, value.clone());
+            }
+            ServerHeader::Removed => {
+                response.headers.remove(SERVER);
+            }
+        }
+        if is_head {
+            response.strip_body();
+        }
+        Ok(response)
     }
 }
 
@@ -384,6 +1053,15 @@ impl<M: Model> Clone for App<M> {
     fn clone(&self) -> Self {
         Self {
             middleware: self.middleware.clone(),
+            server_header: self.server_header.clone(),
+            default_headers: self.default_headers.clone(),
+            limits: self.limits,
+            warn_skipped_next: self.warn_skipped_next,
+            early_hints: self.early_hints,
+            active_requests: self.active_requests.clone(),
+            startup_hooks: self.startup_hooks.clone(),
+            shutdown_hooks: self.shutdown_hooks.clone(),
+            startup: Startup::Idle,
             model: self.model.clone(),
         }
     }
@@ -395,17 +1073,67 @@ impl<M: Model> Clone for HttpService<M> {
             middleware: self.middleware.clone(),
             model: self.model.clone(),
             stream: self.stream.clone(),
+            server_header: self.server_header.clone(),
+            default_headers: self.default_headers.clone(),
+            limits: self.limits,
+            warn_skipped_next: self.warn_skipped_next,
+            early_hints: self.early_hints,
+            active_requests: self.active_requests.clone(),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::App;
+    use crate::{App, Model};
+    use async_std::sync::Arc;
     use async_std::task::spawn;
     use http::StatusCode;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::time::Instant;
 
+    static MODEL_CLONES: AtomicUsize = AtomicUsize::new(0);
+
+    struct LargeModel {
+        data: Vec<u8>,
+    }
+
+    impl Clone for LargeModel {
+        fn clone(&self) -> Self {
+            MODEL_CLONES.fetch_add(1, Ordering::SeqCst);
+            Self {
+                data: self.data.clone(),
+            }
+        }
+    }
+
+    impl Model for LargeModel {
+        type State = usize;
+        fn new_state(&self) -> Self::State {
+            self.data.len()
+        }
+    }
+
+    #[tokio::test]
+    async fn model_not_duplicated() -> Result<(), Box<dyn std::error::Error>> {
+        let mut app = App::new(LargeModel {
+            data: vec![0; 1 << 24],
+        });
+        app.end(|ctx| async move {
+            assert_eq!(1 << 24, *ctx.clone().state().await);
+            Ok(())
+        });
+        assert!(Arc::ptr_eq(&app.model, &app.clone().model));
+        let (addr, server) = app.run_local()?;
+        spawn(server);
+        for _ in 0..4 {
+            let resp = reqwest::get(&format!("http://{}", addr)).await?;
+            assert_eq!(StatusCode::OK, resp.status());
+        }
+        assert_eq!(0, MODEL_CLONES.load(Ordering::SeqCst));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn gate_simple() -> Result<(), Box<dyn std::error::Error>> {
         let (addr, server) = App::new(())
@@ -421,4 +1149,592 @@ mod tests {
         assert_eq!(StatusCode::OK, resp.status());
         Ok(())
     }
+
+    #[tokio::test]
+    async fn abort_connection() -> Result<(), Box<dyn std::error::Error>> {
+        use async_std::net::TcpStream;
+        use futures::{AsyncReadExt, AsyncWriteExt};
+        let (addr, server) = App::new(())
+            .gate_fn(|mut ctx, next| async move {
+                next().await?;
+                ctx.resp_mut().await.write_str("upstream");
+                Ok(())
+            })
+            .end(|mut ctx| async move {
+                ctx.resp_mut().await.write_str("Hello, World!");
+                if ctx.uri().await.path() == "/attack" {
+                    ctx.abort_connection();
+                }
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let mut stream = TcpStream::connect(addr).await?;
+        stream
+            .write_all(b"GET /attack HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        assert!(response.is_empty());
+
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("Hello, World!upstream", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn client_disconnect() -> Result<(), Box<dyn std::error::Error>> {
+        use async_std::future::timeout;
+        use async_std::net::TcpStream;
+        use futures::channel::oneshot;
+        use futures::lock::Mutex;
+        use futures::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+        use std::pin::Pin;
+        use std::task::{self, Poll};
+        use std::time::Duration;
+
+        /// An endless reader notifying when it's dropped.
+        struct Endless(Option<oneshot::Sender<()>>);
+
+        impl AsyncRead for Endless {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                _cx: &mut task::Context<'_>,
+                buf: &mut [u8],
+            ) -> Poll<std::io::Result<usize>> {
+                buf.iter_mut().for_each(|byte| *byte = b'a');
+                Poll::Ready(Ok(buf.len()))
+            }
+        }
+
+        impl Drop for Endless {
+            fn drop(&mut self) {
+                if let Some(tx) = self.0.take() {
+                    tx.send(()).ok();
+                }
+            }
+        }
+
+        let (dropped_tx, dropped_rx) = oneshot::channel();
+        let (finished_tx, finished_rx) = oneshot::channel();
+        let senders = Arc::new(Mutex::new(Some((dropped_tx, finished_tx))));
+        let (addr, server) = App::new(())
+            .gate_fn(move |mut ctx, _next| {
+                let senders = senders.clone();
+                async move {
+                    let (dropped_tx, finished_tx) = match senders.lock().await.take() {
+                        Some(senders) => senders,
+                        None => return Ok(()),
+                    };
+                    let finished_tx = std::sync::Mutex::new(Some(finished_tx));
+                    ctx.resp_mut()
+                        .await
+                        .write(Endless(Some(dropped_tx)))
+                        .on_finish(move |body| {
+                            if let Some(tx) = finished_tx.lock().unwrap().take() {
+                                tx.send((body.is_finished(), body.consumed())).ok();
+                            }
+                        });
+                    Ok(())
+                }
+            })
+            .run_local()?;
+        spawn(server);
+
+        let mut stream = TcpStream::connect(addr).await?;
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await?;
+        let mut buf = vec![0; 64 * 1024];
+        let mut received = 0;
+        while received < buf.len() {
+            received += stream.read(&mut buf[received..]).await?;
+        }
+        drop(stream);
+
+        // the endless body is cancelled
+        timeout(Duration::from_secs(1), dropped_rx).await??;
+        let (finished, consumed) =
+            timeout(Duration::from_secs(1), finished_rx).await??;
+        assert!(!finished);
+        assert!(consumed >= received - 1024);
+
+        // the server is still serving
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        // a pending handler is cancelled
+        let (started_tx, started_rx) = oneshot::channel();
+        let (dropped_tx, dropped_rx) = oneshot::channel();
+        let senders = Arc::new(Mutex::new(Some((started_tx, dropped_tx))));
+        let (addr, server) = App::new(())
+            .gate_fn(move |_ctx, _next| {
+                let senders = senders.clone();
+                async move {
+                    let (started_tx, dropped_tx) = senders.lock().await.take().unwrap();
+                    let _guard = Endless(Some(dropped_tx));
+                    started_tx.send(()).ok();
+                    futures::future::pending::<()>().await;
+                    Ok(())
+                }
+            })
+            .run_local()?;
+        spawn(server);
+        let mut stream = TcpStream::connect(addr).await?;
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await?;
+        timeout(Duration::from_secs(1), started_rx).await??;
+        drop(stream);
+        timeout(Duration::from_secs(1), dropped_rx).await??;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn active_requests() -> Result<(), Box<dyn std::error::Error>> {
+        use futures::channel::oneshot;
+        use futures::lock::Mutex;
+        let (tx, rx) = oneshot::channel::<()>();
+        let rx = Arc::new(Mutex::new(Some(rx)));
+        let mut app = App::new(());
+        app.gate_fn(move |_ctx, next| {
+            let rx = rx.clone();
+            async move {
+                if let Some(rx) = rx.lock().await.take() {
+                    rx.await.ok();
+                }
+                next().await
+            }
+        });
+        let (addr, server) = app.run_local()?;
+        spawn(server);
+        assert_eq!(0, app.active_requests());
+        let url = format!("http://{}", addr);
+        let pending = tokio::spawn(async move { reqwest::get(&url).await });
+        while app.active_requests() == 0 {
+            async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(1, app.clone().active_requests());
+        tx.send(()).ok();
+        assert_eq!(StatusCode::OK, pending.await??.status());
+        assert_eq!(0, app.active_requests());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn listen_many() -> Result<(), Box<dyn std::error::Error>> {
+        let app = App::new(());
+        let mut addrs = Vec::new();
+        let server = app.listen_many(
+            vec!["127.0.0.1:0".parse()?, "127.0.0.1:0".parse()?],
+            |addr| addrs.push(addr),
+        )?;
+        spawn(server);
+        assert_eq!(2, addrs.len());
+        assert_ne!(addrs[0], addrs[1]);
+        for addr in addrs.iter() {
+            let resp = reqwest::get(&format!("http://{}", addr)).await?;
+            assert_eq!(StatusCode::OK, resp.status());
+        }
+
+        // any address in use
+        let mut called = false;
+        let err = app
+            .listen_many(vec!["127.0.0.1:0".parse()?, addrs[0]], |_| called = true)
+            .err()
+            .unwrap();
+        assert!(!called);
+        assert_eq!(std::io::ErrorKind::AddrInUse, err.kind());
+        assert!(err
+            .to_string()
+            .starts_with(&format!("fail to listen on {}", addrs[0])));
+
+        let err = app.listen_many(vec![], |_| ()).err().unwrap();
+        assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn serve_raw_fd() -> Result<(), Box<dyn std::error::Error>> {
+        use std::net::TcpListener;
+        use std::os::unix::io::{FromRawFd, IntoRawFd};
+        let fd = TcpListener::bind("127.0.0.1:0")?.into_raw_fd();
+        let listener = unsafe { TcpListener::from_raw_fd(fd) };
+        let local_addr = std::cell::Cell::new(None);
+        let server =
+            App::new(()).serve_incoming(listener, |addr| local_addr.set(Some(addr)))?;
+        spawn(server);
+        let resp =
+            reqwest::get(&format!("http://{}", local_addr.get().unwrap())).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn gate_if() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Context, Next};
+        use http::header::{HeaderValue, CACHE_CONTROL};
+        let counter = Arc::new(AtomicUsize::new(0));
+        let count = counter.clone();
+        let (addr, server) = App::new(())
+            .gate_if(
+                move |ctx| {
+                    count.fetch_add(1, Ordering::SeqCst);
+                    async move { ctx.uri().await.path().starts_with("/admin") }
+                },
+                |mut ctx: Context<()>, next: Next| async move {
+                    ctx.resp_mut()
+                        .await
+                        .headers
+                        .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+                    next().await
+                },
+            )
+            .end(|mut ctx| async move {
+                ctx.resp_mut().await.write_str("downstream");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        for path in ["/admin", "/admin/user"].iter() {
+            let resp = reqwest::get(&format!("http://{}{}", addr, path)).await?;
+            assert_eq!("no-store", resp.headers()[CACHE_CONTROL]);
+            assert_eq!("downstream", resp.text().await?);
+        }
+        for path in ["/", "/user"].iter() {
+            let resp = reqwest::get(&format!("http://{}{}", addr, path)).await?;
+            assert!(resp.headers().get(CACHE_CONTROL).is_none());
+            assert_eq!("downstream", resp.text().await?);
+        }
+        assert_eq!(4, counter.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn server_header() -> Result<(), Box<dyn std::error::Error>> {
+        use http::header::{HeaderValue, SERVER};
+        let mut app = App::new(());
+        app.gate_fn(|mut ctx, next| async move {
+            ctx.resp_mut()
+                .await
+                .headers
+                .insert(SERVER, HeaderValue::from_static("hyper/0.13"));
+            next().await
+        });
+        let (addr, server) = app.run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("hyper/0.13", resp.headers()[SERVER]);
+
+        let (addr, server) = app.server_header(None).run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert!(resp.headers().get(SERVER).is_none());
+
+        let (addr, server) = app.server_header(Some("roa")).run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("roa", resp.headers()[SERVER]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn default_headers() -> Result<(), Box<dyn std::error::Error>> {
+        use http::header::{HeaderMap, HeaderValue, CACHE_CONTROL, SERVER};
+        let mut headers = HeaderMap::new();
+        headers.insert("x-powered-by", HeaderValue::from_static("roa"));
+        headers.insert(SERVER, HeaderValue::from_static("roa"));
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        headers.append(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        let (addr, server) = App::new(())
+            .default_headers(headers)
+            .gate_fn(|mut ctx, next| async move {
+                if ctx.uri().await.path() == "/override" {
+                    ctx.resp_mut()
+                        .await
+                        .headers
+                        .insert(CACHE_CONTROL, HeaderValue::from_static("max-age=60"));
+                }
+                next().await
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("roa", resp.headers()["x-powered-by"]);
+        assert_eq!("roa", resp.headers()[SERVER]);
+        let cache_control: Vec<_> =
+            resp.headers().get_all(CACHE_CONTROL).iter().collect();
+        assert_eq!(vec!["no-cache", "no-store"], cache_control);
+
+        let resp = reqwest::get(&format!("http://{}/override", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("roa", resp.headers()["x-powered-by"]);
+        let cache_control: Vec<_> =
+            resp.headers().get_all(CACHE_CONTROL).iter().collect();
+        assert_eq!(vec!["max-age=60"], cache_control);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn head() -> Result<(), Box<dyn std::error::Error>> {
+        use http::header::CONTENT_LENGTH;
+        let (addr, server) = App::new(())
+            .end(|mut ctx| async move {
+                ctx.resp_mut().await.write_str("Hello, World!");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let resp = client.head(&format!("http://{}", addr)).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("13", resp.headers()[CONTENT_LENGTH]);
+        assert_eq!("", resp.text().await?);
+
+        let resp = client.get(&format!("http://{}", addr)).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("Hello, World!", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn limits() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::Limits;
+        use async_std::net::TcpStream;
+        use futures::{AsyncReadExt, AsyncWriteExt};
+        let sent = Arc::new(AtomicUsize::new(0));
+        let sent_counter = sent.clone();
+        let (addr, server) = App::new(())
+            .limits(Limits {
+                max_body_size: Some(8),
+                max_uri_length: Some(16),
+                max_header_size: Some(128),
+            })
+            .gate_fn(move |mut ctx, next| {
+                let sent = sent_counter.clone();
+                async move {
+                    ctx.on_response_sent(move |_body| {
+                        sent.fetch_add(1, Ordering::SeqCst);
+                    })
+                    .await;
+                    next().await
+                }
+            })
+            .end(|mut ctx| async move {
+                let mut data = String::new();
+                // the error is ignored, but the response is still replaced by 413.
+                let _ = ctx.req_mut().await.read_to_string(&mut data).await;
+                ctx.resp_mut().await.write_str(data);
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+
+        let resp = client.post(&url).body("12345678").send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("12345678", resp.text().await?);
+
+        let resp = client.post(&url).body("123456789").send().await?;
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, resp.status());
+        assert_eq!("request body is larger than 8 bytes", resp.text().await?);
+
+        let resp = client
+            .get(&format!("{}/a/very/long/path", url))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::URI_TOO_LONG, resp.status());
+        assert_eq!("uri is longer than 16 bytes", resp.text().await?);
+
+        let resp = client
+            .get(&url)
+            .header("x-large", "x".repeat(128))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE, resp.status());
+        assert_eq!(
+            "request headers are larger than 128 bytes",
+            resp.text().await?
+        );
+
+        // a chunked body has no content-length, it's limited when read.
+        let mut stream = TcpStream::connect(addr).await?;
+        stream
+            .write_all(
+                b"POST / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+                  Transfer-Encoding: chunked\r\n\r\n5\r\n12345\r\n5\r\n67890\r\n0\r\n\r\n",
+            )
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        assert!(
+            response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"),
+            "unexpected response: {}",
+            response
+        );
+        assert!(response.contains("request body is larger than 8 bytes"));
+        // callbacks survive replacing the response by 413,
+        // requests rejected before the middleware chain register nothing.
+        assert_eq!(2, sent.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn lifecycle_hooks() -> Result<(), Box<dyn std::error::Error>> {
+        use futures::channel::oneshot;
+        use std::time::Duration;
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let startup = events.clone();
+        let shutdown = events.clone();
+        let endpoint = events.clone();
+        let mut app = App::new(());
+        app.on_startup(move || {
+            let events = startup.clone();
+            async move {
+                async_std::task::sleep(Duration::from_millis(100)).await;
+                events.lock().unwrap().push("startup");
+                Ok(())
+            }
+        })
+        .on_shutdown(move || {
+            let events = shutdown.clone();
+            async move {
+                events.lock().unwrap().push("shutdown");
+                Err(crate::Error::new(StatusCode::IM_A_TEAPOT, "", false))
+            }
+        })
+        .on_shutdown(|| async { Ok(()) })
+        .gate_fn(move |_ctx, next| {
+            let events = endpoint.clone();
+            async move {
+                events.lock().unwrap().push("request");
+                next().await
+            }
+        });
+        let (tx, rx) = oneshot::channel::<()>();
+        let (addr, server) = app.run_local()?;
+        let server = spawn(app.with_graceful_shutdown(server, async {
+            rx.await.ok();
+        }));
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(vec!["startup", "request"], *events.lock().unwrap());
+        let _ = tx.send(());
+        server.await?;
+        assert_eq!(
+            vec!["startup", "request", "shutdown"],
+            *events.lock().unwrap()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn startup_fails() -> Result<(), Box<dyn std::error::Error>> {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let hook_counter = counter.clone();
+        let (_addr, server) = App::new(())
+            .on_startup(|| async {
+                Err(crate::Error::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "migration fails",
+                    false,
+                ))
+            })
+            .on_startup(move || {
+                hook_counter.fetch_add(1, Ordering::SeqCst);
+                async { Ok(()) }
+            })
+            .run_local()?;
+        let err = server.await.unwrap_err();
+        assert!(format!("{:?}", err).contains("migration fails"));
+        assert_eq!(0, counter.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn listen_many_hooks() -> Result<(), Box<dyn std::error::Error>> {
+        let startups = Arc::new(AtomicUsize::new(0));
+        let shutdowns = Arc::new(AtomicUsize::new(0));
+        let mut app = App::new(());
+        let (startup, shutdown) = (startups.clone(), shutdowns.clone());
+        app.on_startup(move || {
+            startup.fetch_add(1, Ordering::SeqCst);
+            async { Ok(()) }
+        })
+        .on_shutdown(move || {
+            shutdown.fetch_add(1, Ordering::SeqCst);
+            async { Ok(()) }
+        });
+        let mut addrs = Vec::new();
+        let server = app.listen_many(
+            vec!["127.0.0.1:0".parse()?, "127.0.0.1:0".parse()?],
+            |addr| addrs.push(addr),
+        )?;
+        spawn(server);
+        for addr in addrs.iter() {
+            let resp = reqwest::get(&format!("http://{}", addr)).await?;
+            assert_eq!(StatusCode::OK, resp.status());
+        }
+        assert_eq!(1, startups.load(Ordering::SeqCst));
+
+        // a failed startup fails all servers, then shutdown hooks run once
+        app.on_startup(|| async {
+            Err(crate::Error::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "migration fails",
+                false,
+            ))
+        });
+        let server = app.listen_many(
+            vec!["127.0.0.1:0".parse()?, "127.0.0.1:0".parse()?],
+            |_addr| (),
+        )?;
+        let err = server.await.unwrap_err();
+        assert!(format!("{:?}", err).contains("migration fails"));
+        assert_eq!(2, startups.load(Ordering::SeqCst));
+        assert_eq!(1, shutdowns.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn reuse_port() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::SocketOpts;
+        let opts = SocketOpts {
+            reuse_address: true,
+            reuse_port: true,
+        };
+        let mut app = App::new(());
+        app.end(|mut ctx| async move {
+            ctx.resp_mut().await.write_str("Hello, World");
+            Ok(())
+        });
+        let mut old_addr = None;
+        let old =
+            app.listen_with_opts("127.0.0.1:0", opts, |addr| old_addr = Some(addr))?;
+        let addr = old_addr.unwrap();
+        // binding the same port fails without SO_REUSEPORT
+        assert!(app
+            .listen_with_opts(addr, SocketOpts::default(), |_addr| ())
+            .is_err());
+        let mut new_addr = None;
+        let new = app.listen_with_opts(addr, opts, |addr| new_addr = Some(addr))?;
+        assert_eq!(Some(addr), new_addr);
+        spawn(new);
+        // stop the old server, then the new one takes over all connections.
+        drop(old);
+        for _ in 0..4 {
+            let resp = reqwest::get(&format!("http://{}", addr)).await?;
+            assert_eq!(StatusCode::OK, resp.status());
+            assert_eq!("Hello, World", resp.text().await?);
+        }
+        Ok(())
+    }
 }
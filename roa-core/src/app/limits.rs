@@ -0,0 +1,183 @@
+use crate::{Error, Request, Result};
+use async_std::io::Read;
+use http::header::CONTENT_LENGTH;
+use http::{StatusCode, Uri};
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Limits of incoming requests, enforced by `App` before the middleware chain.
+///
+/// Each limit is disabled if it's `None`, and all of them are disabled by default.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa_core::{App, Limits};
+/// use async_std::task::spawn;
+/// use http::StatusCode;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let (addr, server) = App::new(())
+///         .limits(Limits {
+///             max_body_size: Some(1024 * 1024),
+///             max_uri_length: Some(16),
+///             max_header_size: Some(8 * 1024),
+///         })
+///         .run_local()?;
+///     spawn(server);
+///     let resp = reqwest::get(&format!("http://{}/a/very/long/path", addr)).await?;
+///     assert_eq!(StatusCode::URI_TOO_LONG, resp.status());
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Limits {
+    /// Max size of request body in bytes.
+    ///
+    /// A request with a larger "content-length" is rejected by 413 PAYLOAD TOO LARGE
+    /// before the middleware chain. Otherwise, reading more bytes from the body fails,
+    /// and the response is replaced by 413 PAYLOAD TOO LARGE after the middleware chain.
+    pub max_body_size: Option<u64>,
+
+    /// Max length of request uri in bytes,
+    /// a request with a longer uri is rejected by 414 URI TOO LONG.
+    pub max_uri_length: Option<usize>,
+
+    /// Max total size of request headers in bytes, counting names and values,
+    /// a request with larger headers is rejected by 431 REQUEST HEADER FIELDS TOO LARGE.
+    pub max_header_size: Option<usize>,
+}
+
+impl Limits {
+    /// Check uri and headers of `req`, and limit its body.
+    ///
+    /// Return a flag which is set once the body exceeds the limit.
+    pub(crate) fn check(&self, req: &mut Request) -> Result<Arc<AtomicBool>> {
+        if let Some(max) = self.max_uri_length {
+            if uri_length(&req.uri) > max {
+                return Err(Error::new(
+                    StatusCode::URI_TOO_LONG,
+                    format!("uri is longer than {} bytes", max),
+                    true,
+                ));
+            }
+        }
+        if let Some(max) = self.max_header_size {
+            let size: usize = req
+                .headers
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len())
+                .sum();
+            if size > max {
+                return Err(Error::new(
+                    StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+                    format!("request headers are larger than {} bytes", max),
+                    true,
+                ));
+            }
+        }
+        let exceeded = Arc::new(AtomicBool::new(false));
+        if let Some(max) = self.max_body_size {
+            let content_length = req
+                .headers
+                .get(CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok());
+            if matches!(content_length, Some(length) if length > max) {
+                return Err(self.body_too_large());
+            }
+            let body = std::mem::take(&mut **req);
+            req.write(Limited {
+                body,
+                remaining: max,
+                exceeded: exceeded.clone(),
+            });
+        }
+        Ok(exceeded)
+    }
+
+    /// The error of a too large body.
+    pub(crate) fn body_too_large(&self) -> Error {
+        Error::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "request body is larger than {} bytes",
+                self.max_body_size.unwrap_or_default()
+            ),
+            true,
+        )
+    }
+}
+
+/// Length of `uri` in its text form, without formatting it.
+pub(crate) fn uri_length(uri: &Uri) -> usize {
+    uri.scheme_str()
+        .map(|scheme| scheme.len() + "://".len())
+        .unwrap_or(0)
+        + uri
+            .authority()
+            .map(|authority| authority.as_str().len())
+            .unwrap_or(0)
+        + uri.path().len()
+        + uri
+            .query()
+            .map(|query| query.len() + "?".len())
+            .unwrap_or(0)
+}
+
+/// A reader failing once more than `remaining` bytes are read.
+struct Limited<R> {
+    body: R,
+    remaining: u64,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl<R: Read + Unpin> Read for Limited<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let size = futures::ready!(Pin::new(&mut self.body).poll_read(cx, buf))?;
+        match self.remaining.checked_sub(size as u64) {
+            Some(remaining) => {
+                self.remaining = remaining;
+                Poll::Ready(Ok(size))
+            }
+            None => {
+                self.exceeded.store(true, Ordering::SeqCst);
+                Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "request body is too large",
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::uri_length;
+    use http::Uri;
+
+    #[test]
+    fn uri_length_matches_text() {
+        for uri in [
+            "/",
+            "/path?query=1",
+            "http://example.com",
+            "https://user@example.com:8080/path?query",
+            "example.com:443",
+            "*",
+        ]
+        .iter()
+        {
+            let uri: Uri = uri.parse().unwrap();
+            assert_eq!(uri.to_string().len(), uri_length(&uri), "{}", uri);
+        }
+    }
+}
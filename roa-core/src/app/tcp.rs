@@ -1,9 +1,11 @@
 pub use self::addr_stream::AddrStream;
-use async_std::net::{SocketAddr, TcpListener};
+use async_std::net::{SocketAddr, TcpListener, TcpStream};
+use async_std::sync::Arc;
 use futures::FutureExt as _;
 use futures_timer::Delay;
 use hyper::server::accept::Accept;
 use log::{debug, error, trace};
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
 use std::fmt;
 use std::future::Future;
 use std::io;
@@ -12,15 +14,76 @@ use std::pin::Pin;
 use std::task::{self, Poll};
 use std::time::Duration;
 
+type AcceptFuture =
+    Pin<Box<dyn 'static + Future<Output = io::Result<(TcpStream, SocketAddr)>> + Send>>;
+
+/// Options of the listening socket, used by `AddrIncoming::bind_with`.
+///
+/// All options are disabled by default.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct SocketOpts {
+    /// Set `SO_REUSEADDR`, allowing to bind an address in `TIME_WAIT` state.
+    ///
+    /// On windows, it allows another socket to steal the address,
+    /// which is usually undesired.
+    pub reuse_address: bool,
+
+    /// Set `SO_REUSEPORT`, allowing multiple sockets, even of different processes,
+    /// to listen on the same address, which enables graceful restarts:
+    /// start the new process before stopping the old one.
+    ///
+    /// Connections are load balanced between sockets on linux (3.9+),
+    /// while the other BSDs may deliver them to the last bound socket.
+    /// Only supported on unix except solaris and illumos,
+    /// binding fails with `ErrorKind::Unsupported` on other platforms.
+    pub reuse_port: bool,
+}
+
+/// The backlog of listeners bound by `AddrIncoming::bind_with`, the same as std.
+const BACKLOG: i32 = 128;
+
+/// Create a socket for `addr`, set options, then bind and listen on it.
+fn bind_socket(addr: SocketAddr, opts: SocketOpts) -> io::Result<StdListener> {
+    let domain = if addr.is_ipv4() {
+        Domain::ipv4()
+    } else {
+        Domain::ipv6()
+    };
+    let socket = Socket::new(domain, Type::stream(), Some(Protocol::tcp()))?;
+    if opts.reuse_address {
+        socket.set_reuse_address(true)?;
+    }
+    if opts.reuse_port {
+        set_reuse_port(&socket)?;
+    }
+    socket.bind(&SockAddr::from(addr))?;
+    socket.listen(BACKLOG)?;
+    Ok(socket.into_tcp_listener())
+}
+
+#[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+fn set_reuse_port(socket: &Socket) -> io::Result<()> {
+    socket.set_reuse_port(true)
+}
+
+#[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos")))))]
+fn set_reuse_port(_socket: &Socket) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "SO_REUSEPORT is not supported on this platform",
+    ))
+}
+
 /// A stream of connections from binding to an address.
 /// As an implementation of hyper::server::accept::Accept.
 #[must_use = "streams do nothing unless polled"]
 pub struct AddrIncoming {
     addr: SocketAddr,
-    listener: TcpListener,
+    listener: Arc<TcpListener>,
     sleep_on_errors: bool,
     tcp_nodelay: bool,
     timeout: Option<Delay>,
+    accept: Option<AcceptFuture>,
 }
 
 impl AddrIncoming {
@@ -29,14 +92,17 @@ impl AddrIncoming {
         AddrIncoming::from_std(listener)
     }
 
-    pub(super) fn from_std(listener: StdListener) -> io::Result<Self> {
+    /// Creates a new `AddrIncoming` from an already bound listener.
+    /// The listener is set to nonblocking mode.
+    pub fn from_std(listener: StdListener) -> io::Result<Self> {
         let addr = listener.local_addr()?;
         Ok(AddrIncoming {
-            listener: listener.into(),
+            listener: Arc::new(listener.into()),
             addr,
             sleep_on_errors: true,
             tcp_nodelay: false,
             timeout: None,
+            accept: None,
         })
     }
 
@@ -45,6 +111,27 @@ impl AddrIncoming {
         AddrIncoming::new(addr)
     }
 
+    /// Creates a new `AddrIncoming` binding to provided socket address,
+    /// with options set on the listening socket before binding.
+    ///
+    /// Each resolved address is tried in order until one is bound,
+    /// the last error is returned if all of them fail.
+    pub fn bind_with(addr: impl ToSocketAddrs, opts: SocketOpts) -> io::Result<Self> {
+        let mut last_err = None;
+        for addr in addr.to_socket_addrs()? {
+            match bind_socket(addr, opts) {
+                Ok(listener) => return AddrIncoming::from_std(listener),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any addresses",
+            )
+        }))
+    }
+
     /// Get the local address bound to this listener.
     pub fn local_addr(&self) -> SocketAddr {
         self.addr
@@ -90,19 +177,24 @@ impl AddrIncoming {
         }
         self.timeout = None;
 
-        let accept = self.listener.accept();
-        futures::pin_mut!(accept);
-
         loop {
-            match accept.poll_unpin(cx) {
-                Poll::Ready(Ok((socket, addr))) => {
+            // Keep the pending accept future across polls,
+            // dropping it would deregister the waker.
+            let listener = self.listener.clone();
+            let accept = self
+                .accept
+                .get_or_insert_with(|| Box::pin(async move { listener.accept().await }));
+            let result = futures::ready!(accept.poll_unpin(cx));
+            self.accept = None;
+            match result {
+                Ok((socket, addr)) => {
                     if let Err(e) = socket.set_nodelay(self.tcp_nodelay) {
                         trace!("error trying to set TCP nodelay: {}", e);
                     }
-                    return Poll::Ready(Ok(AddrStream::new(socket, addr)));
+                    let local_addr = socket.local_addr().unwrap_or(self.addr);
+                    return Poll::Ready(Ok(AddrStream::new(socket, local_addr, addr)));
                 }
-                Poll::Pending => return Poll::Pending,
-                Poll::Ready(Err(e)) => {
+                Err(e) => {
                     // Connection errors can be ignored directly, continue by
                     // accepting the next request.
                     if is_connection_error(&e) {
@@ -157,12 +249,12 @@ impl Accept for AddrIncoming {
 /// and EMFILE. Otherwise, could enter into tight loop.
 #[cfg_attr(tarpaulin, skip)]
 fn is_connection_error(e: &io::Error) -> bool {
-    match e.kind() {
+    matches!(
+        e.kind(),
         io::ErrorKind::ConnectionRefused
-        | io::ErrorKind::ConnectionAborted
-        | io::ErrorKind::ConnectionReset => true,
-        _ => false,
-    }
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    )
 }
 
 #[cfg_attr(tarpaulin, skip)]
@@ -182,6 +274,7 @@ mod addr_stream {
     use std::io;
     use std::net::SocketAddr;
     use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::task::{self, Poll};
     use tokio::io::{AsyncRead, AsyncWrite};
 
@@ -189,23 +282,65 @@ mod addr_stream {
     #[derive(Debug, Clone)]
     pub struct AddrStream {
         inner: Arc<TcpStream>,
+        pub(super) local_addr: SocketAddr,
         pub(super) remote_addr: SocketAddr,
+        pub(super) is_tls: bool,
+        requests: Arc<AtomicUsize>,
     }
 
     impl AddrStream {
-        pub(super) fn new(tcp: TcpStream, addr: SocketAddr) -> AddrStream {
+        pub(super) fn new(
+            tcp: TcpStream,
+            local_addr: SocketAddr,
+            remote_addr: SocketAddr,
+        ) -> AddrStream {
             AddrStream {
                 inner: Arc::new(tcp),
-                remote_addr: addr,
+                local_addr,
+                remote_addr,
+                is_tls: false,
+                requests: Arc::new(AtomicUsize::new(0)),
             }
         }
 
+        /// Returns the local address of this connection.
+        #[inline]
+        pub fn local_addr(&self) -> SocketAddr {
+            self.local_addr
+        }
+
         /// Returns the remote (peer) address of this connection.
         #[inline]
         pub fn remote_addr(&self) -> SocketAddr {
             self.remote_addr
         }
 
+        /// Returns true if this connection is over TLS.
+        ///
+        /// Always false until a TLS listener is supported.
+        #[inline]
+        pub fn is_tls(&self) -> bool {
+            self.is_tls
+        }
+
+        /// Returns true if the peer connects from a loopback address.
+        #[inline]
+        pub fn is_loopback(&self) -> bool {
+            self.remote_addr.ip().is_loopback()
+        }
+
+        /// Count a new request served on this connection.
+        #[inline]
+        pub(crate) fn count_request(&self) {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+        }
+
+        /// Number of requests served on this connection, including the current one.
+        #[inline]
+        pub(crate) fn requests(&self) -> usize {
+            self.requests.load(Ordering::SeqCst)
+        }
+
         /// Consumes the AddrStream and returns the underlying IO object
         #[inline]
         pub fn stream(&self) -> Arc<TcpStream> {
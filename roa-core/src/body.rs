@@ -7,6 +7,13 @@ use std::pin::Pin;
 pub type Callback = dyn 'static + Sync + Send + Unpin + Fn(&Body);
 
 /// The Body of Request and Response.
+///
+/// Every `write*` method appends a segment, it never replaces the existing ones.
+/// A body shared by cloned contexts is guarded by the lock of response,
+/// so a segment is never interleaved with another one,
+/// but the order of segments written concurrently is unspecified.
+/// Use `Body::is_written` if a writer expects to be the only one.
+///
 /// ### Example
 /// ```rust
 /// use roa_core::Body;
@@ -30,10 +37,42 @@ pub type Callback = dyn 'static + Sync + Send + Unpin + Fn(&Body);
 pub struct Body {
     counter: usize,
     segments: Vec<Segment>,
+    /// Total length of segments, `None` if any of them has unknown length.
+    length: Option<usize>,
     consumed: usize,
     finish: Vec<Box<Callback>>,
 }
 
+/// The kind of a body, returned by `Body::kind`.
+///
+/// ### Example
+/// ```rust
+/// use roa_core::{Body, BodyKind};
+/// use async_std::fs::File;
+///
+/// #[async_std::main]
+/// async fn main() -> std::io::Result<()> {
+///     let mut body = Body::default();
+///     assert_eq!(BodyKind::Empty, body.kind());
+///     body.write_str("Hello, ").write_bytes(b"World".as_ref());
+///     assert_eq!(BodyKind::Bytes(12), body.kind());
+///     body.write(File::open("../assets/author.txt").await?);
+///     assert_eq!(BodyKind::Stream, body.kind());
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BodyKind {
+    /// Nothing left to read.
+    Empty,
+
+    /// Buffered bytes with known length left to read.
+    Bytes(usize),
+
+    /// Contains readers of unknown length, written by `write` or `write_buf`.
+    Stream,
+}
+
 pub type Segment = Box<dyn BufRead + Sync + Send + Unpin + 'static>;
 
 impl Body {
@@ -41,6 +80,7 @@ impl Body {
         Self {
             counter: 0,
             segments: Vec::new(),
+            length: Some(0),
             consumed: 0,
             finish: Vec::new(),
         }
@@ -52,6 +92,7 @@ impl Body {
         &mut self,
         buf_reader: impl BufRead + Sync + Send + Unpin + 'static,
     ) -> &mut Self {
+        self.length = None;
         self.segments.push(Box::new(buf_reader));
         self
     }
@@ -68,7 +109,40 @@ impl Body {
     /// Write `Vec<u8>`.
     #[inline]
     pub fn write_bytes(&mut self, bytes: impl Into<Vec<u8>>) -> &mut Self {
-        self.write_buf(Cursor::new(bytes.into()))
+        let bytes = bytes.into();
+        self.length = self.length.map(|length| length + bytes.len());
+        self.segments.push(Box::new(Cursor::new(bytes)));
+        self
+    }
+
+    /// Write shared bytes like `Bytes` or `Arc<[u8]>`, they are read without copying.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::{Body, BodyKind, Bytes};
+    /// use futures::io::AsyncReadExt;
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> std::io::Result<()> {
+    ///     let content = Bytes::from_static(b"Hello, World");
+    ///     let mut body = Body::default();
+    ///     body.write_shared(content.clone());
+    ///     assert_eq!(BodyKind::Bytes(12), body.kind());
+    ///     let mut data = String::new();
+    ///     body.read_to_string(&mut data).await?;
+    ///     assert_eq!("Hello, World", data);
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn write_shared(
+        &mut self,
+        bytes: impl AsRef<[u8]> + Sync + Send + Unpin + 'static,
+    ) -> &mut Self {
+        let size = bytes.as_ref().len();
+        self.length = self.length.map(|length| length + size);
+        self.segments.push(Box::new(Cursor::new(bytes)));
+        self
     }
 
     /// Write `String`.
@@ -77,6 +151,30 @@ impl Body {
         self.write_bytes(data.to_string())
     }
 
+    /// Write an owned `String`, its buffer is moved into body without copy.
+    ///
+    /// "Content-Type" is never set by this method.
+    #[inline]
+    pub fn write_string(&mut self, data: String) -> &mut Self {
+        self.write_bytes(data.into_bytes())
+    }
+
+    /// Check if anything has been written into body.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::Body;
+    ///
+    /// let mut body = Body::default();
+    /// assert!(!body.is_written());
+    /// body.write_str("Hello, World!");
+    /// assert!(body.is_written());
+    /// ```
+    #[inline]
+    pub fn is_written(&self) -> bool {
+        !self.segments.is_empty()
+    }
+
     /// Into a stream.
     #[inline]
     pub fn stream(self) -> BodyStream<Self> {
@@ -122,6 +220,41 @@ impl Body {
     pub fn consumed(&self) -> usize {
         self.consumed
     }
+
+    /// Check if all segments have been read to the end.
+    ///
+    /// It's useful in `Body::on_finish` to tell a response sent completely
+    /// from one dropped midway, usually because the client disconnected.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::Body;
+    /// use futures::io::AsyncReadExt;
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> std::io::Result<()> {
+    ///     let mut body = Body::default();
+    ///     body.write_buf(b"Hello, World".as_ref());
+    ///     assert!(!body.is_finished());
+    ///     body.read_to_end(&mut Vec::new()).await?;
+    ///     assert!(body.is_finished());
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.counter >= self.segments.len()
+    }
+
+    /// Get the kind of this body, depending on what is left to read.
+    #[inline]
+    pub fn kind(&self) -> BodyKind {
+        match self.length {
+            None => BodyKind::Stream,
+            Some(length) if length == self.consumed => BodyKind::Empty,
+            Some(length) => BodyKind::Bytes(length - self.consumed),
+        }
+    }
 }
 
 impl Default for Body {
@@ -185,14 +318,27 @@ impl Read for Body {
     }
 }
 
+/// A stream of body sent to the client.
+///
+/// The client disconnecting midway is not an error of the body,
+/// hyper just drops the stream, and all segments are dropped with it,
+/// which cancels readers still producing data.
+/// So it's logged at debug level,
+/// while errors of reading segments are logged at error level.
 pub struct BodyStream<R: BufRead> {
     body: R,
+    sent: usize,
+    ended: bool,
 }
 
 impl<R: BufRead> BodyStream<R> {
     #[inline]
     pub fn new(body: R) -> Self {
-        Self { body }
+        Self {
+            body,
+            sent: 0,
+            ended: false,
+        }
     }
 }
 
@@ -204,18 +350,40 @@ impl<R: BufRead + Unpin> Stream for BodyStream<R> {
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Option<Self::Item>> {
-        let buf: &[u8] = futures::ready!(Pin::new(&mut self.body).poll_fill_buf(cx))?;
+        let buf: &[u8] = match futures::ready!(Pin::new(&mut self.body).poll_fill_buf(cx))
+        {
+            Ok(buf) => buf,
+            Err(err) => {
+                log::error!("fail to read response body: {}", err);
+                self.ended = true;
+                return Poll::Ready(Some(Err(err)));
+            }
+        };
         let buf_len = buf.len();
         if buf_len == 0 {
+            self.ended = true;
             Poll::Ready(None)
         } else {
             let data = buf.to_vec();
             Pin::new(&mut self.body).consume(buf_len);
+            self.sent += buf_len;
             Poll::Ready(Some(Ok(data)))
         }
     }
 }
 
+impl<R: BufRead> Drop for BodyStream<R> {
+    fn drop(&mut self) {
+        // a body never polled is skipped by hyper, like the body of a 304 response
+        if !self.ended && self.sent > 0 {
+            log::debug!(
+                "response body is dropped after {} bytes, client may be disconnected",
+                self.sent
+            );
+        }
+    }
+}
+
 impl<R: BufRead + Unpin + Send + Sync + 'static> From<BodyStream<R>> for hyper::Body {
     #[inline]
     fn from(stream: BodyStream<R>) -> Self {
@@ -225,7 +393,7 @@ impl<R: BufRead + Unpin + Send + Sync + 'static> From<BodyStream<R>> for hyper::
 
 #[cfg(test)]
 mod tests {
-    use super::Body;
+    use super::{Body, BodyKind};
     use async_std::fs::File;
     use async_std::io::ReadExt;
 
@@ -249,6 +417,18 @@ mod tests {
         Ok(())
     }
 
+    #[async_std::test]
+    async fn body_string() -> std::io::Result<()> {
+        let mut body = Body::new();
+        let mut data = String::new();
+        let html = "<html></html>".to_string();
+        body.write_string(html);
+        assert_eq!(BodyKind::Bytes(13), body.kind());
+        body.read_to_string(&mut data).await?;
+        assert_eq!("<html></html>", data);
+        Ok(())
+    }
+
     #[async_std::test]
     async fn body_multiple() -> std::io::Result<()> {
         let mut body = Body::new();
@@ -287,6 +467,29 @@ mod tests {
         Ok(())
     }
 
+    #[async_std::test]
+    async fn body_kind() -> std::io::Result<()> {
+        let mut body = Body::new();
+        assert_eq!(BodyKind::Empty, body.kind());
+        body.write_bytes(b"".as_ref());
+        assert_eq!(BodyKind::Empty, body.kind());
+        body.write_str("Hello, ").write_bytes(b"World".as_ref());
+        assert_eq!(BodyKind::Bytes(12), body.kind());
+        let mut data = [0; 5];
+        body.read_exact(&mut data).await?;
+        assert_eq!(BodyKind::Bytes(7), body.kind());
+        let mut data = String::new();
+        body.read_to_string(&mut data).await?;
+        assert_eq!("Hello, World"[5..], data);
+        assert_eq!(BodyKind::Empty, body.kind());
+
+        let mut body = Body::new();
+        body.write_str("Hello, ")
+            .write(File::open("../assets/author.txt").await?);
+        assert_eq!(BodyKind::Stream, body.kind());
+        Ok(())
+    }
+
     #[async_std::test]
     async fn body_on_finish() -> std::io::Result<()> {
         let mut body = Body::new();
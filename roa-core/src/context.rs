@@ -1,20 +1,31 @@
-use crate::{AddrStream, Error, Request, Response};
+use crate::body::BodyStream;
+use crate::{AddrStream, Body, Error, Next, Request, Response};
 use async_std::net::{SocketAddr, TcpStream};
 use async_std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
-use http::header::{AsHeaderName, ToStrError};
+use bytes::Bytes;
+use futures::channel::mpsc::{channel, Receiver};
+use futures::{AsyncWriteExt, SinkExt, StreamExt};
+use http::header::{AsHeaderName, ToStrError, CONNECTION, EXPECT};
 use http::StatusCode;
-use http::{HeaderValue, Method, Uri, Version};
+use http::{HeaderMap, HeaderValue, Method, Uri, Version};
 use std::any::TypeId;
 use std::collections::HashMap;
 use std::convert::AsRef;
 use std::fmt::Display;
 use std::ops::Deref;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 /// A structure to share request, response and other data between middlewares.
 ///
 /// Type of the first parameter in a middleware.
 ///
+/// Cloning a context is O(1), all clones share the same request, response, state and storage.
+///
+/// Request, response, state and storage are guarded by independent `RwLock`s,
+/// so reading the request never blocks writing the response and readers never block each other.
+/// Guards should not be held across `next().await` unless you mean to lock out downstream.
+///
 /// ### Example
 ///
 /// ```rust
@@ -45,9 +56,18 @@ pub struct Context<S> {
     response: Arc<RwLock<Response>>,
     state: Arc<RwLock<S>>,
     storage: Arc<RwLock<HashMap<TypeId, Bucket>>>,
+    scopes: Arc<RwLock<Vec<Vec<Shadowed>>>>,
     stream: AddrStream,
+    original_method: Arc<Method>,
+    original_uri: Arc<Uri>,
+    aborted: Arc<AtomicBool>,
+    pub(crate) warn_skipped_next: bool,
+    pub(crate) early_hints: bool,
 }
 
+/// A key stored within a storage scope, with its value before the scope.
+type Shadowed = (TypeId, String, Option<String>);
+
 /// A wrapper of `HashMap<String, String>`, method `get` return a `Variable`.
 ///
 /// ### Example
@@ -56,8 +76,8 @@ pub struct Context<S> {
 /// let mut bucket = Bucket::new();
 /// assert!(bucket.get("id").is_none());
 /// assert!(bucket.insert("id", "1").is_none());
-/// assert_eq!(1, bucket.get("id").unwrap().parse().unwrap());
-/// assert_eq!(1, bucket.insert("id", "2").unwrap().parse().unwrap());
+/// assert_eq!(1, bucket.get("id").unwrap().parse::<i32>().unwrap());
+/// assert_eq!(1, bucket.insert("id", "2").unwrap().parse::<i32>().unwrap());
 /// ```
 #[derive(Debug, Clone)]
 pub struct Bucket(HashMap<String, String>);
@@ -68,7 +88,7 @@ pub struct Bucket(HashMap<String, String>);
 /// ```rust
 /// use roa_core::Variable;
 /// use http::StatusCode;
-/// assert_eq!(1, Variable::new("id", "1".to_string()).parse().unwrap());
+/// assert_eq!(1, Variable::new("id", "1".to_string()).parse::<i32>().unwrap());
 /// let result = Variable::new("id", "x".to_string()).parse::<usize>();
 /// assert!(result.is_err());
 /// let status = result.unwrap_err();
@@ -81,6 +101,24 @@ pub struct Variable<'a> {
     value: String,
 }
 
+/// Metadata of the connection a request comes from, without access to the raw socket.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ConnectionInfo {
+    /// Local socket addr.
+    pub local_addr: SocketAddr,
+
+    /// Remote (peer) socket addr.
+    pub remote_addr: SocketAddr,
+
+    /// If this connection is over TLS.
+    pub is_tls: bool,
+
+    /// Protocol negotiated by TLS ALPN, like `b"h2"`.
+    ///
+    /// Always None until a TLS listener is supported.
+    pub alpn_protocol: Option<Vec<u8>>,
+}
+
 impl Deref for Variable<'_> {
     type Target = str;
 
@@ -93,7 +131,7 @@ impl Deref for Variable<'_> {
 impl AsRef<str> for Variable<'_> {
     #[inline]
     fn as_ref(&self) -> &str {
-        &self
+        self
     }
 }
 
@@ -160,7 +198,7 @@ impl Bucket {
     /// use roa_core::{Bucket, Variable};
     /// let mut bucket = Bucket::new();
     /// assert!(bucket.insert("id", "1").is_none());
-    /// assert_eq!(1, bucket.insert("id", "2").unwrap().parse().unwrap());
+    /// assert_eq!(1, bucket.insert("id", "2").unwrap().parse::<i32>().unwrap());
     /// ```
     #[inline]
     pub fn insert<'a>(
@@ -183,7 +221,7 @@ impl Bucket {
     /// let mut bucket = Bucket::new();
     /// assert!(bucket.get("id").is_none());
     /// bucket.insert("id", "1");
-    /// assert_eq!(1, bucket.get("id").unwrap().parse().unwrap());
+    /// assert_eq!(1, bucket.get("id").unwrap().parse::<i32>().unwrap());
     /// ```
     #[inline]
     pub fn get<'a>(&self, name: &'a str) -> Option<Variable<'a>> {
@@ -192,6 +230,21 @@ impl Bucket {
             value: value.to_string(),
         })
     }
+
+    /// Consume the bucket and return all key-value pairs.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::Bucket;
+    /// let mut bucket = Bucket::new();
+    /// bucket.insert("id", "1");
+    /// let map = bucket.into_inner();
+    /// assert_eq!("1", map["id"]);
+    /// ```
+    #[inline]
+    pub fn into_inner(self) -> HashMap<String, String> {
+        self.0
+    }
 }
 
 impl Default for Bucket {
@@ -204,10 +257,16 @@ impl<S> Context<S> {
     /// Construct a context from a request, an app and a addr_stream.  
     pub(crate) fn new(request: Request, state: S, stream: AddrStream) -> Self {
         Self {
+            original_method: Arc::new(request.method.clone()),
+            original_uri: Arc::new(request.uri.clone()),
+            aborted: Arc::new(AtomicBool::new(false)),
+            warn_skipped_next: false,
+            early_hints: false,
             request: Arc::new(RwLock::new(request)),
             response: Arc::new(RwLock::new(Response::new())),
             state: Arc::new(RwLock::new(state)),
             storage: Arc::new(RwLock::new(HashMap::new())),
+            scopes: Arc::new(RwLock::new(Vec::new())),
             stream,
         }
     }
@@ -268,6 +327,9 @@ impl<S> Context<S> {
 
     /// Get an immutable reference of state.
     ///
+    /// State is guarded by a `RwLock` shared by all clones of this context,
+    /// so readers never block each other; only `state_mut` takes exclusive access.
+    ///
     /// ### Example
     /// ```rust
     /// use roa_core::{App, Model};
@@ -361,8 +423,75 @@ impl<S> Context<S> {
         self.request.write().await
     }
 
+    /// Take the request body and forward it chunk by chunk to a bounded channel,
+    /// so another task can consume it with backpressure.
+    ///
+    /// The body is read by a spawned task, which waits when `buffer` chunks are pending
+    /// (plus one for the sender). An error is forwarded as the last item.
+    /// If the receiver is dropped, the task stops at the next chunk and drops the body,
+    /// the remaining bytes are never read.
+    ///
+    /// The body is moved out, so reading it from `Context::req` gets nothing afterward.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use futures::StreamExt;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|mut ctx| async move {
+    ///             let mut chunks = ctx.body_into_channel(8).await;
+    ///             let consumer = spawn(async move {
+    ///                 let mut size = 0;
+    ///                 while let Some(chunk) = chunks.next().await {
+    ///                     size += chunk?.len();
+    ///                 }
+    ///                 Ok::<_, roa_core::Error>(size)
+    ///             });
+    ///             let size = consumer.await?;
+    ///             ctx.resp_mut().await.write_str(size.to_string());
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::Client::new()
+    ///         .post(&format!("http://{}", addr))
+    ///         .body("Hello, World!")
+    ///         .send()
+    ///         .await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     assert_eq!("13", resp.text().await?);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn body_into_channel(
+        &mut self,
+        buffer: usize,
+    ) -> Receiver<crate::Result<Bytes>> {
+        let body = std::mem::take(&mut **self.req_mut().await);
+        let (mut sender, receiver) = channel(buffer);
+        async_std::task::spawn(async move {
+            let mut chunks = BodyStream::new(body);
+            while let Some(chunk) = chunks.next().await {
+                let chunk = chunk.map(Bytes::from).map_err(Error::from);
+                let failed = chunk.is_err();
+                if sender.send(chunk).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+        receiver
+    }
+
     /// Get a mutable reference of response.
     ///
+    /// Writes of cloned contexts are serialized by the lock of response,
+    /// see `Body` for the contract of concurrent writes.
+    ///
     /// ### Example
     /// ```rust
     /// use roa_core::App;
@@ -389,6 +518,124 @@ impl<S> Context<S> {
         self.response.write().await
     }
 
+    /// Register a callback invoked after the response is sent.
+    ///
+    /// Unlike the upstream code of a middleware, which runs before hyper writes any byte,
+    /// the callback is invoked after hyper has written the whole body,
+    /// or dropped it as the connection is closed; check `Body::consumed` to distinguish them.
+    /// It's attached to the final body, so it survives body replacement by other middlewares.
+    ///
+    /// The callback is never invoked if an error is thrown to hyper.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    /// use std::time::Instant;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .gate_fn(|mut ctx, next| async move {
+    ///             let start = Instant::now();
+    ///             ctx.on_response_sent(move |body| {
+    ///                 println!("{} bytes sent in {:?}", body.consumed(), start.elapsed());
+    ///             })
+    ///             .await;
+    ///             next().await
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn on_response_sent(
+        &mut self,
+        callback: impl 'static + Sync + Send + Unpin + Fn(&Body),
+    ) {
+        self.resp_mut().await.sent.push(Box::new(callback));
+    }
+
+    /// Replace the whole response under one lock, return the old one.
+    ///
+    /// Callbacks registered by `Context::on_response_sent` are kept,
+    /// the returned response never carries them.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::{App, Response};
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|mut ctx| async move {
+    ///             let mut response = Response::from("Hello, World!".to_string());
+    ///             response.status = StatusCode::CREATED;
+    ///             ctx.set_response(response).await;
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::CREATED, resp.status());
+    ///     assert_eq!("Hello, World!", resp.text().await?);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn set_response(&mut self, mut response: Response) -> Response {
+        let mut current = self.resp_mut().await;
+        let mut sent = std::mem::take(&mut current.sent);
+        sent.append(&mut response.sent);
+        response.sent = sent;
+        std::mem::replace(&mut *current, response)
+    }
+
+    /// Take the whole response under one lock, leaving a default one.
+    ///
+    /// Callbacks registered by `Context::on_response_sent` are kept,
+    /// the returned response never carries them.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .gate_fn(|mut ctx, next| async move {
+    ///             next().await?;
+    ///             let mut response = ctx.take_response().await;
+    ///             if response.status == StatusCode::NOT_FOUND {
+    ///                 response.status = StatusCode::GONE;
+    ///             }
+    ///             ctx.set_response(response).await;
+    ///             Ok(())
+    ///         })
+    ///         .end(|mut ctx| async move {
+    ///             ctx.resp_mut().await.status = StatusCode::NOT_FOUND;
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::GONE, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn take_response(&mut self) -> Response {
+        let mut current = self.resp_mut().await;
+        let mut response = Response::new();
+        response.sent = std::mem::take(&mut current.sent);
+        std::mem::replace(&mut *current, response)
+    }
+
     /// Get a mutable reference of state.
     ///
     /// ### Example
@@ -447,12 +694,60 @@ impl<S> Context<S> {
         self.state.write().await
     }
 
-    /// Get a mutable reference of storage.
+    /// Call a closure with an immutable reference of state.
+    ///
+    /// The read lock is released once the closure returns,
+    /// so it can never be held across an await point by accident.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::{App, Model};
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// struct AppModel;
+    ///
+    /// struct AppState {
+    ///     id: u64,
+    /// }
+    ///
+    /// impl Model for AppModel {
+    ///     type State = AppState;
+    ///     fn new_state(&self) -> Self::State {
+    ///         AppState { id: 0 }
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(AppModel)
+    ///         .gate_fn(|mut ctx, next| async move {
+    ///             ctx.with_state_mut(|state| state.id = 1).await;
+    ///             next().await
+    ///         })
+    ///         .end(|ctx| async move {
+    ///             assert_eq!(1, ctx.with_state(|state| state.id).await);
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn with_state<T>(&self, f: impl FnOnce(&S) -> T) -> T {
+        f(&*self.state().await)
+    }
+
+    /// Call a closure with a mutable reference of state.
+    ///
+    /// The write lock is released once the closure returns,
+    /// so it can never be held across an await point by accident.
     #[inline]
-    pub(crate) async fn storage_mut(
-        &mut self,
-    ) -> RwLockWriteGuard<'_, HashMap<TypeId, Bucket>> {
-        self.storage.write().await
+    pub async fn with_state_mut<T>(&mut self, f: impl FnOnce(&mut S) -> T) -> T {
+        f(&mut *self.state_mut().await)
     }
 
     /// Clone URI.
@@ -481,6 +776,120 @@ impl<S> Context<S> {
         self.req().await.uri.clone()
     }
 
+    /// Get the method as received, without locking request.
+    ///
+    /// It's cached when the context is constructed, so it never reflects
+    /// changes made by `Context::req_mut`; use `Context::method` if it may be rewritten.
+    /// Reading headers or body still requires `Context::req`.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::{StatusCode, Method};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|ctx| async move {
+    ///             assert_eq!(Method::GET, ctx.original_method());
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn original_method(&self) -> &Method {
+        &self.original_method
+    }
+
+    /// Get the URI as received, without locking request.
+    ///
+    /// It's cached when the context is constructed, so it never reflects
+    /// changes made by `Context::req_mut` (like stripping a path prefix);
+    /// use `Context::uri` if it may be rewritten.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|ctx| async move {
+    ///             assert_eq!("/path", ctx.original_uri().path());
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}/path", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn original_uri(&self) -> &Uri {
+        &self.original_uri
+    }
+
+    /// Clone path of URI.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|ctx| async move {
+    ///             assert_eq!("/path", ctx.path().await);
+    ///             assert_eq!(Some("id=0".to_string()), ctx.query_str().await);
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}/path?id=0", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn path(&self) -> String {
+        self.req().await.uri.path().to_string()
+    }
+
+    /// Clone query string of URI, return `None` if there is no query.
+    pub async fn query_str(&self) -> Option<String> {
+        self.req().await.uri.query().map(ToString::to_string)
+    }
+
+    /// Clone scheme of URI.
+    ///
+    /// It's `None` for most requests, whose target is only a path and query.
+    /// Use `Context::scheme` to get the scheme of this connection.
+    pub async fn scheme_str(&self) -> Option<String> {
+        self.req().await.uri.scheme_str().map(ToString::to_string)
+    }
+
+    /// Clone authority of URI.
+    ///
+    /// Like `Context::scheme_str`, it's `None` unless the request target is an absolute URI,
+    /// the "host" header is preferred in most cases.
+    pub async fn authority(&self) -> Option<String> {
+        self.req()
+            .await
+            .uri
+            .authority()
+            .map(|authority| authority.to_string())
+    }
+
     /// Clone request::method.
     ///
     /// ### Example
@@ -606,39 +1015,169 @@ impl<S> Context<S> {
         self.resp().await.status
     }
 
-    /// Clone request::version.
+    /// Clone trailers of request, see `Request::trailers`.
+    ///
+    /// Trailers are only available once the body is read to the end, and may be absent.
     ///
     /// ### Example
     /// ```rust
     /// use roa_core::App;
     /// use async_std::task::spawn;
-    /// use http::{StatusCode, Version};
+    /// use futures::AsyncReadExt;
+    /// use http::StatusCode;
     ///
     /// #[tokio::main]
     /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
     ///     let (addr, server) = App::new(())
-    ///         .end(|ctx| async move {
-    ///             assert_eq!(Version::HTTP_11, ctx.version().await);
+    ///         .end(|mut ctx| async move {
+    ///             let mut data = Vec::new();
+    ///             ctx.req_mut().await.read_to_end(&mut data).await?;
+    ///             // no trailer is sent by the client.
+    ///             assert!(ctx.trailers().await.is_none());
     ///             Ok(())
     ///         })
     ///         .run_local()?;
     ///     spawn(server);
-    ///     let resp = reqwest::get(&format!("http://{}/path", addr)).await?;
+    ///     let resp = reqwest::Client::new()
+    ///         .post(&format!("http://{}", addr))
+    ///         .body("Hello, World!")
+    ///         .send()
+    ///         .await?;
     ///     assert_eq!(StatusCode::OK, resp.status());
     ///     Ok(())
     /// }
     /// ```
-    pub async fn version(&self) -> Version {
-        self.req().await.version
+    pub async fn trailers(&self) -> Option<HeaderMap> {
+        self.req().await.trailers()
     }
 
-    /// Store key-value pair. Each type has its namespace.
+    /// Get the size of the request in bytes, counting request line, headers and body,
+    /// useful for ingress accounting.
+    ///
+    /// The body is counted by "content-length" if present, otherwise by bytes read so far,
+    /// so the size of a streaming body is only known after it's read to the end.
+    /// See `Request::size` for details.
     ///
     /// ### Example
     /// ```rust
     /// use roa_core::App;
     /// use async_std::task::spawn;
-    /// use http::{StatusCode, Method};
+    /// use futures::AsyncReadExt;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|mut ctx| async move {
+    ///             let mut data = Vec::new();
+    ///             ctx.req_mut().await.read_to_end(&mut data).await?;
+    ///             let size = ctx.request_size().await;
+    ///             ctx.resp_mut().await.write_str(size.to_string());
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::Client::new()
+    ///         .post(&format!("http://{}", addr))
+    ///         .body("Hello, World!")
+    ///         .send()
+    ///         .await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     assert!(resp.text().await?.parse::<usize>()? > "Hello, World!".len());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn request_size(&self) -> usize {
+        self.req().await.size()
+    }
+
+    /// Clone request::version.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::{StatusCode, Version};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|ctx| async move {
+    ///             assert_eq!(Version::HTTP_11, ctx.version().await);
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}/path", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn version(&self) -> Version {
+        self.req().await.version
+    }
+
+    /// Whether the connection will be kept alive after this request,
+    /// judging by version and "Connection" header of the request.
+    ///
+    /// - HTTP/1.1 connections are persistent by default,
+    ///   unless "Connection: close" is sent.
+    /// - HTTP/1.0 connections are closed by default,
+    ///   unless "Connection: keep-alive" is sent.
+    /// - HTTP/2 connections are always persistent, HTTP/0.9 connections never are.
+    ///
+    /// It only reads the request, a connection may still be closed by the server,
+    /// for example, when middleware sets "Connection: close" on the response.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|ctx| async move {
+    ///             assert!(!ctx.will_keep_alive().await);
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::Client::new()
+    ///         .get(&format!("http://{}", addr))
+    ///         .header("connection", "close")
+    ///         .send()
+    ///         .await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn will_keep_alive(&self) -> bool {
+        let req = self.req().await;
+        let has_token = |token: &str| {
+            req.headers
+                .get_all(CONNECTION)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .any(|item| item.trim().eq_ignore_ascii_case(token))
+        };
+        match req.version {
+            Version::HTTP_09 => false,
+            Version::HTTP_10 => has_token("keep-alive") && !has_token("close"),
+            Version::HTTP_11 => !has_token("close"),
+            _ => true,
+        }
+    }
+
+    /// Store key-value pair. Each type has its namespace.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::{StatusCode, Method};
     ///
     /// struct Symbol;
     /// struct AnotherSymbol;
@@ -668,17 +1207,70 @@ impl<S> Context<S> {
         name: &'a str,
         value: String,
     ) -> Option<Variable<'a>> {
-        let mut storage = self.storage_mut().await;
+        let mut storage = self.storage.write().await;
         let id = TypeId::of::<T>();
-        match storage.get_mut(&id) {
-            Some(bucket) => bucket.insert(name, value),
-            None => {
-                let mut bucket = Bucket::default();
-                bucket.insert(name, value);
-                storage.insert(id, bucket);
-                None
+        let old = storage.entry(id).or_default().insert(name, value);
+        // record the value before the latest scope, only the first time
+        if let Some(scope) = self.scopes.write().await.last_mut() {
+            if !scope
+                .iter()
+                .any(|(key, key_name, _)| *key == id && key_name == name)
+            {
+                let shadowed = old.as_ref().map(|old| old.value.clone());
+                scope.push((id, name.to_string(), shadowed));
             }
         }
+        old
+    }
+
+    /// Store key-value pair through all storage scopes,
+    /// it's kept after scopes are popped.
+    ///
+    /// It's for values that belong to the whole request rather than a scope,
+    /// like pending cookies to be set on the response.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// struct Symbol;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .gate_fn(|ctx, next| async move {
+    ///             next().await?;
+    ///             assert_eq!("1", ctx.load::<Symbol>("id").await.unwrap().as_ref());
+    ///             Ok(())
+    ///         })
+    ///         .gate_fn(|mut ctx, next| async move { ctx.scoped(next).await })
+    ///         .end(|mut ctx| async move {
+    ///             ctx.store_unscoped::<Symbol>("id", "1".to_owned()).await;
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    #[allow(clippy::needless_lifetimes)]
+    pub async fn store_unscoped<'a, T: 'static>(
+        &mut self,
+        name: &'a str,
+        value: String,
+    ) -> Option<Variable<'a>> {
+        let mut storage = self.storage.write().await;
+        let id = TypeId::of::<T>();
+        let old = storage.entry(id).or_default().insert(name, value);
+        for scope in self.scopes.write().await.iter_mut() {
+            scope.retain(|(key, key_name, _)| *key != id || key_name != name);
+        }
+        old
     }
 
     /// Search for value by key.
@@ -747,16 +1339,363 @@ impl<S> Context<S> {
         storage.get(&id).and_then(|bucket| bucket.get(name))
     }
 
+    /// Clone the whole bucket of a namespace, return `None` if nothing stored in it.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// struct Symbol;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .gate_fn(|mut ctx, next| async move {
+    ///             ctx.store::<Symbol>("id", "1".to_owned()).await;
+    ///             ctx.store::<Symbol>("name", "Hexilee".to_owned()).await;
+    ///             next().await
+    ///         })
+    ///         .end(|ctx| async move {
+    ///             let bucket = ctx.bucket::<Symbol>().await.unwrap();
+    ///             assert_eq!(1, bucket.get("id").unwrap().parse::<i32>()?);
+    ///             assert_eq!("Hexilee", bucket.get("name").unwrap().as_ref());
+    ///             assert!(ctx.bucket::<()>().await.is_none());
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}/path", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn bucket<T: 'static>(&self) -> Option<Bucket> {
+        let storage = self.storage().await;
+        storage.get(&TypeId::of::<T>()).cloned()
+    }
+
+    /// Push a storage scope of all namespaces.
+    ///
+    /// Within the scope, `store` and `load` work as usual:
+    /// values stored before are visible and can be shadowed.
+    /// Only keys stored within the scope are recorded, the storage is never copied.
+    /// Scopes can be nested, each `pop_scope` pops the latest one.
+    ///
+    /// The storage is shared by all clones of the context,
+    /// so a scope covers every middleware running between push and pop.
+    /// Prefer `Context::scoped` unless something must be stored into the scope
+    /// before calling `next`, and make sure `pop_scope` is called even if `next` fails.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// struct Symbol;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .gate_fn(|mut ctx, next| async move {
+    ///             ctx.store::<Symbol>("id", "1".to_owned()).await;
+    ///             ctx.push_scope().await;
+    ///             ctx.store::<Symbol>("id", "2".to_owned()).await;
+    ///             ctx.store::<Symbol>("name", "Hexilee".to_owned()).await;
+    ///             let result = next().await;
+    ///             ctx.pop_scope().await;
+    ///             assert_eq!("1", ctx.load::<Symbol>("id").await.unwrap().as_ref());
+    ///             assert!(ctx.load::<Symbol>("name").await.is_none());
+    ///             result
+    ///         })
+    ///         .end(|ctx| async move {
+    ///             assert_eq!("2", ctx.load::<Symbol>("id").await.unwrap().as_ref());
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn push_scope(&mut self) {
+        self.scopes.write().await.push(Vec::new());
+    }
+
+    /// Pop the latest storage scope, restoring keys stored within it.
+    ///
+    /// Values stored within the scope are discarded and shadowed values come back,
+    /// other keys, including ones stored by `Context::store_unscoped`, are untouched.
+    /// Do nothing if there is no scope.
+    pub async fn pop_scope(&mut self) {
+        let scope = self.scopes.write().await.pop();
+        if let Some(scope) = scope {
+            let mut storage = self.storage.write().await;
+            for (id, name, shadowed) in scope {
+                let bucket = storage.entry(id).or_default();
+                match shadowed {
+                    Some(value) => {
+                        bucket.0.insert(name, value);
+                    }
+                    None => {
+                        bucket.0.remove(&name);
+                        if bucket.0.is_empty() {
+                            storage.remove(&id);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Call `next` in a new storage scope, the scope is popped on the upstream phase
+    /// whether `next` succeeds or not.
+    /// Values stored by downstream middleware don't leak to upstream
+    /// and sibling middleware.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// struct Symbol;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .gate_fn(|ctx, next| async move {
+    ///             next().await?;
+    ///             assert!(ctx.load::<Symbol>("user").await.is_none());
+    ///             Ok(())
+    ///         })
+    ///         .gate_fn(|mut ctx, next| async move { ctx.scoped(next).await })
+    ///         .end(|mut ctx| async move {
+    ///             ctx.store::<Symbol>("user", "Hexilee".to_owned()).await;
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn scoped(&mut self, next: Next) -> crate::Result {
+        self.push_scope().await;
+        let result = next().await;
+        self.pop_scope().await;
+        result
+    }
+
     /// Get remote socket addr.
     pub fn remote_addr(&self) -> SocketAddr {
         self.stream.remote_addr()
     }
 
+    /// Get local socket addr of this connection.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.stream.local_addr()
+    }
+
+    /// Get metadata of this connection, the raw socket is not reachable from it.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|ctx| async move {
+    ///             let info = ctx.connection_info();
+    ///             assert!(info.remote_addr.ip().is_loopback());
+    ///             assert!(!info.is_tls);
+    ///             assert_eq!(None, info.alpn_protocol);
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn connection_info(&self) -> ConnectionInfo {
+        ConnectionInfo {
+            local_addr: self.stream.local_addr(),
+            remote_addr: self.stream.remote_addr(),
+            is_tls: self.stream.is_tls(),
+            alpn_protocol: None,
+        }
+    }
+
+    /// Get metadata of this connection.
+    pub fn addr_stream(&self) -> &AddrStream {
+        &self.stream
+    }
+
+    /// Get scheme of this connection, "https" if it is over TLS, otherwise "http".
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|ctx| async move {
+    ///             assert_eq!("http", ctx.scheme());
+    ///             assert!(ctx.addr_stream().is_loopback());
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn scheme(&self) -> &'static str {
+        if self.stream.is_tls() {
+            "https"
+        } else {
+            "http"
+        }
+    }
+
     /// Get reference of raw async_std::net::TcpStream.
     /// This method is dangerous, it's reserved for special scene like websocket.
     pub fn raw_stream(&self) -> Arc<TcpStream> {
         self.stream.stream()
     }
+
+    /// Send a `103 Early Hints` interim response with `headers`,
+    /// usually "link" headers to preload resources, before the final response.
+    ///
+    /// It's disabled by default, return `Ok(false)` without sending anything
+    /// unless `App::early_hints` is enabled.
+    ///
+    /// Return `Ok(false)` as well if the request is not over plain HTTP/1.1:
+    /// interim responses must not be sent to HTTP/1.0 clients,
+    /// and hyper provides no way to send them over HTTP/2 or TLS.
+    ///
+    /// The interim response is written to the connection behind hyper,
+    /// so it's sent only if hyper has nothing buffered on the connection,
+    /// that is, the request is the first one on its connection and expects no
+    /// "100 Continue". Otherwise `Ok(false)` is returned as well.
+    /// It must be sent before the middleware chain returns.
+    ///
+    /// ### Browser support
+    ///
+    /// Browsers supporting early hints (like Chrome since 103) only honor them
+    /// over HTTP/2 or later, and which "rel" of links are honored varies.
+    /// So they are ignored if browsers talk to roa directly;
+    /// they become useful behind a proxy or CDN forwarding 103 to browsers over HTTP/2.
+    /// Some old HTTP/1.1 clients mistake the interim response for the final one,
+    /// so consider sending them only to known clients.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::App;
+    /// use roa_core::header::{HeaderMap, HeaderValue, LINK};
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .early_hints(true)
+    ///         .gate_fn(|ctx, next| async move {
+    ///             let mut hints = HeaderMap::new();
+    ///             let style = "</style.css>; rel=preload; as=style";
+    ///             hints.insert(LINK, HeaderValue::from_static(style));
+    ///             ctx.send_early_hints(&hints).await?;
+    ///             next().await
+    ///         })
+    ///         .end(|mut ctx| async move {
+    ///             // render the page slowly...
+    ///             ctx.resp_mut().await.write_str("<html></html>");
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn send_early_hints(&self, headers: &HeaderMap) -> crate::Result<bool> {
+        if !self.early_hints {
+            return Ok(false);
+        }
+        if self.stream.is_tls() || self.version().await != Version::HTTP_11 {
+            return Ok(false);
+        }
+        // hyper writes nothing before the final response of the first request
+        // on a connection, except "100 Continue".
+        if self.stream.requests() != 1 || self.req().await.headers.contains_key(EXPECT) {
+            return Ok(false);
+        }
+        let mut head = b"HTTP/1.1 103 Early Hints\r\n".to_vec();
+        for (name, value) in headers {
+            head.extend_from_slice(name.as_str().as_bytes());
+            head.extend_from_slice(b": ");
+            head.extend_from_slice(value.as_bytes());
+            head.extend_from_slice(b"\r\n");
+        }
+        head.extend_from_slice(b"\r\n");
+        (&*self.stream.stream()).write_all(&head).await?;
+        Ok(true)
+    }
+
+    /// Close the connection without sending any byte of response, as a last-resort defense
+    /// (for example, an attack is detected).
+    ///
+    /// It takes effect after the whole middleware chain returns, whatever it returns,
+    /// so return right after calling it. No byte of response is sent, even if upstream
+    /// middlewares write a response or recover from an error, and callbacks registered
+    /// by `Context::on_response_sent` are never invoked.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::App;
+    /// use async_std::task::spawn;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|mut ctx| async move {
+    ///             // a scanner is detected
+    ///             if ctx.uri().await.path() == "/wp-login.php" {
+    ///                 ctx.abort_connection();
+    ///             }
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     assert!(reqwest::get(&format!("http://{}/wp-login.php", addr)).await.is_err());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn abort_connection(&mut self) {
+        self.aborted.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether `Context::abort_connection` is called.
+    pub fn is_connection_aborted(&self) -> bool {
+        self.aborted.load(Ordering::SeqCst)
+    }
 }
 
 impl<S> Clone for Context<S> {
@@ -766,16 +1705,130 @@ impl<S> Clone for Context<S> {
             response: self.response.clone(),
             state: self.state.clone(),
             storage: self.storage.clone(),
+            scopes: self.scopes.clone(),
             stream: self.stream.clone(),
+            original_method: self.original_method.clone(),
+            original_uri: self.original_uri.clone(),
+            aborted: self.aborted.clone(),
+            warn_skipped_next: self.warn_skipped_next,
+            early_hints: self.early_hints,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{App, Context, Model};
+    use crate::{App, Body, BodyKind, Context, Model, Response};
+    use async_std::future::timeout;
     use async_std::task::spawn;
-    use http::{StatusCode, Version};
+    use http::{HeaderValue, Method, StatusCode, Uri, Version};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn swap_response() -> Result<(), Box<dyn std::error::Error>> {
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+        let (addr, server) = App::new(())
+            .gate_fn(move |mut ctx, next| {
+                let tx = tx.clone();
+                async move {
+                    ctx.on_response_sent(move |body| {
+                        tx.send(body.consumed()).unwrap();
+                    })
+                    .await;
+                    next().await?;
+                    let mut response = ctx.take_response().await;
+                    assert!(response.sent.is_empty());
+                    assert_eq!(StatusCode::OK, ctx.status().await);
+                    response.status = StatusCode::ACCEPTED;
+                    response
+                        .headers
+                        .insert("x-swapped", HeaderValue::from_static("true"));
+                    let old = ctx.set_response(response).await;
+                    assert!(old.sent.is_empty());
+                    Ok(())
+                }
+            })
+            .end(|mut ctx| async move {
+                let mut response = Response::from("Hello, World!".to_string());
+                response.status = StatusCode::CREATED;
+                ctx.set_response(response).await;
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::ACCEPTED, resp.status());
+        assert_eq!("true", resp.headers()["x-swapped"]);
+        assert_eq!("Hello, World!", resp.text().await?);
+        let consumed = rx.recv_timeout(Duration::from_secs(1))?;
+        assert_eq!(13, consumed);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn original_method_and_uri() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate_fn(|mut ctx, next| async move {
+                let mut request = ctx.req_mut().await;
+                request.method = Method::POST;
+                request.uri = Uri::from_static("/rewritten");
+                drop(request);
+                next().await
+            })
+            .end(|ctx| async move {
+                assert_eq!(Method::GET, ctx.original_method());
+                assert_eq!("/path?id=0", ctx.original_uri().to_string());
+                assert_eq!(Method::POST, ctx.method().await);
+                assert_eq!("/rewritten", ctx.uri().await.to_string());
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/path?id=0", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn body_into_channel() -> Result<(), Box<dyn std::error::Error>> {
+        use futures::StreamExt;
+        let (addr, server) = App::new(())
+            .end(|mut ctx| async move {
+                let mut chunks = ctx.body_into_channel(1).await;
+                if ctx.uri().await.path() == "/drop" {
+                    drop(chunks);
+                    ctx.resp_mut().await.write_str("dropped");
+                    return Ok(());
+                }
+                let mut data = Vec::new();
+                while let Some(chunk) = chunks.next().await {
+                    data.extend_from_slice(&chunk?);
+                }
+                assert_eq!(BodyKind::Empty, ctx.req().await.kind());
+                ctx.resp_mut().await.write_str(data.len().to_string());
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let body = vec![b'x'; 1024 * 1024];
+        let resp = client
+            .post(&format!("http://{}", addr))
+            .body(body.clone())
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!((1024 * 1024).to_string(), resp.text().await?);
+
+        let resp = client
+            .post(&format!("http://{}/drop", addr))
+            .body(body)
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("dropped", resp.text().await?);
+        Ok(())
+    }
 
     #[tokio::test]
     async fn status_and_version() -> Result<(), Box<dyn std::error::Error>> {
@@ -791,6 +1844,24 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn connection_metadata() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(|ctx| async move {
+                assert_eq!("http", ctx.scheme());
+                assert!(!ctx.addr_stream().is_tls());
+                assert!(ctx.addr_stream().is_loopback());
+                assert_eq!(ctx.local_addr(), ctx.addr_stream().local_addr());
+                assert_eq!(ctx.remote_addr(), ctx.addr_stream().remote_addr());
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+
     struct AppModel;
     struct AppState {
         data: usize,
@@ -802,6 +1873,171 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn concurrent_state_read() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(AppModel {})
+            .gate_fn(|ctx, next| async move {
+                // hold a read guard while downstream reads state
+                let _guard = ctx.state().await;
+                next().await
+            })
+            .end(|ctx: Context<AppState>| async move {
+                let readers = (0..8).map(|_| {
+                    let ctx = ctx.clone();
+                    spawn(async move { ctx.state().await.data })
+                });
+                let result =
+                    timeout(Duration::from_secs(1), futures::future::join_all(readers))
+                        .await;
+                assert_eq!(vec![0; 8], result.expect("state readers contend"));
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn concurrent_write() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(|ctx| async move {
+                let writers = ["a", "b"].iter().map(|data| {
+                    let mut ctx = ctx.clone();
+                    spawn(async move {
+                        let mut resp = ctx.resp_mut().await;
+                        if !resp.is_written() {
+                            resp.write_str(data.repeat(1024));
+                        }
+                    })
+                });
+                futures::future::join_all(writers).await;
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let data = resp.text().await?;
+        assert!(data == "a".repeat(1024) || data == "b".repeat(1024));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn on_response_sent() -> Result<(), Box<dyn std::error::Error>> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        let sent = Arc::new(AtomicUsize::new(0));
+        let counter = sent.clone();
+        let (addr, server) = App::new(())
+            .gate_fn(move |mut ctx, next| {
+                let counter = counter.clone();
+                async move {
+                    let sent = counter.clone();
+                    ctx.on_response_sent(move |body| {
+                        sent.store(body.consumed(), Ordering::SeqCst)
+                    })
+                    .await;
+                    next().await?;
+                    assert_eq!(0, counter.load(Ordering::SeqCst));
+                    Ok(())
+                }
+            })
+            .end(|mut ctx| async move {
+                // replace body
+                **ctx.resp_mut().await = Body::default();
+                ctx.resp_mut().await.write_str("Hello, World!");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("Hello, World!", resp.text().await?);
+        timeout(Duration::from_secs(1), async {
+            while sent.load(Ordering::SeqCst) != 13 {
+                async_std::task::yield_now().await;
+            }
+        })
+        .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn uri_components() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(|mut ctx| async move {
+                assert_eq!("/user", ctx.path().await);
+                assert_eq!(Some("id=0".to_string()), ctx.query_str().await);
+                assert_eq!(None, ctx.scheme_str().await);
+                assert_eq!(None, ctx.authority().await);
+
+                ctx.req_mut().await.uri = Uri::from_static("https://example.com:8080/");
+                assert_eq!("/", ctx.path().await);
+                assert_eq!(None, ctx.query_str().await);
+                assert_eq!(Some("https".to_string()), ctx.scheme_str().await);
+                assert_eq!(Some("example.com:8080".to_string()), ctx.authority().await);
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/user?id=0", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn connection_info() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(|mut ctx| async move {
+                let info = ctx.connection_info();
+                assert_eq!(ctx.remote_addr(), info.remote_addr);
+                assert!(!info.is_tls);
+                ctx.resp_mut().await.write_str(info.local_addr.to_string());
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(addr.to_string(), resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn independent_locks() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate_fn(|ctx, next| async move {
+                // hold a request read guard while downstream works
+                let _req = ctx.req().await;
+                next().await
+            })
+            .end(|mut ctx| async move {
+                let reader = ctx.clone();
+                let result = timeout(Duration::from_secs(1), async move {
+                    let host = reader.header_value(http::header::HOST).await;
+                    let mut resp = ctx.resp_mut().await;
+                    resp.headers
+                        .insert("x-foo", HeaderValue::from_static("bar"));
+                    resp.write_str("Hello, World");
+                    host
+                })
+                .await;
+                assert!(result
+                    .expect("request and response locks contend")
+                    .is_some());
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("bar", resp.headers()["x-foo"].to_str()?);
+        assert_eq!("Hello, World", resp.text().await?);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn state_mut() -> Result<(), Box<dyn std::error::Error>> {
         let (addr, server) = App::new(AppModel {})
@@ -818,4 +2054,220 @@ mod tests {
         reqwest::get(&format!("http://{}", addr)).await?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn with_state() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(AppModel {})
+            .gate_fn(|mut ctx, next| async move {
+                // the write lock is released before downstream runs
+                ctx.with_state_mut(|state| state.data += 1).await;
+                next().await?;
+                ctx.with_state_mut(|state| state.data += 1).await;
+                assert_eq!(4, ctx.with_state(|state| state.data).await);
+                Ok(())
+            })
+            .end(|ctx: Context<AppState>| async move {
+                let writers = (0..2).map(|_| {
+                    let mut ctx = ctx.clone();
+                    spawn(
+                        async move { ctx.with_state_mut(|state| state.data += 1).await },
+                    )
+                });
+                timeout(Duration::from_secs(1), futures::future::join_all(writers))
+                    .await
+                    .expect("state lock is leaked");
+                assert_eq!(3, ctx.with_state(|state| state.data).await);
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn scoped_storage() -> Result<(), Box<dyn std::error::Error>> {
+        struct Symbol;
+        let (addr, server) = App::new(())
+            .gate_fn(|mut ctx, next| async move {
+                ctx.store::<Symbol>("id", "0".to_string()).await;
+                let result = next().await;
+                // the scope is popped even if downstream fails.
+                assert_eq!(StatusCode::IM_A_TEAPOT, result.unwrap_err().status_code);
+                assert_eq!("0", ctx.load::<Symbol>("id").await.unwrap().as_ref());
+                assert!(ctx.load::<Symbol>("outer").await.is_none());
+                assert!(ctx.load::<Symbol>("inner").await.is_none());
+                // popping without scope does nothing.
+                ctx.pop_scope().await;
+                assert_eq!("0", ctx.load::<Symbol>("id").await.unwrap().as_ref());
+                Ok(())
+            })
+            .gate_fn(|mut ctx, next| async move { ctx.scoped(next).await })
+            .gate_fn(|mut ctx, next| async move {
+                ctx.store::<Symbol>("outer", "1".to_string()).await;
+                ctx.push_scope().await;
+                ctx.store::<Symbol>("id", "2".to_string()).await;
+                ctx.store::<Symbol>("inner", "2".to_string()).await;
+                let result = next().await;
+                ctx.pop_scope().await;
+                assert_eq!("0", ctx.load::<Symbol>("id").await.unwrap().as_ref());
+                assert_eq!("1", ctx.load::<Symbol>("outer").await.unwrap().as_ref());
+                assert!(ctx.load::<Symbol>("inner").await.is_none());
+                result
+            })
+            .end(|ctx| async move {
+                assert_eq!("2", ctx.load::<Symbol>("id").await.unwrap().as_ref());
+                assert_eq!("1", ctx.load::<Symbol>("outer").await.unwrap().as_ref());
+                assert_eq!("2", ctx.load::<Symbol>("inner").await.unwrap().as_ref());
+                Err(crate::Error::new(StatusCode::IM_A_TEAPOT, "", false))
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn early_hints() -> Result<(), Box<dyn std::error::Error>> {
+        use futures::{AsyncReadExt, AsyncWriteExt};
+        use http::header::{HeaderMap, LINK};
+        let mut app = App::new(());
+        app.end(|mut ctx| async move {
+            let mut hints = HeaderMap::new();
+            hints.append(LINK, HeaderValue::from_static("</a.css>; rel=preload"));
+            hints.append(LINK, HeaderValue::from_static("</b.js>; rel=preload"));
+            let sent = ctx.send_early_hints(&hints).await?;
+            ctx.resp_mut().await.write_str(sent.to_string());
+            Ok(())
+        });
+
+        // disabled by default
+        let (addr, server) = app.run_local()?;
+        spawn(server);
+        let mut stream = async_std::net::TcpStream::connect(addr).await?;
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("\r\nfalse\r\n"));
+
+        let (addr, server) = app.early_hints(true).run_local()?;
+        spawn(server);
+
+        let mut stream = async_std::net::TcpStream::connect(addr).await?;
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        assert!(
+            response.starts_with(
+                "HTTP/1.1 103 Early Hints\r\n\
+                 link: </a.css>; rel=preload\r\n\
+                 link: </b.js>; rel=preload\r\n\r\n\
+                 HTTP/1.1 200 OK\r\n"
+            ),
+            "unexpected response: {}",
+            response
+        );
+        assert!(response.contains("\r\ntrue\r\n"));
+
+        let mut stream = async_std::net::TcpStream::connect(addr).await?;
+        stream
+            .write_all(b"GET / HTTP/1.0\r\nHost: localhost\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        assert!(response.starts_with("HTTP/1.0 200 OK\r\n"));
+        assert!(
+            response.ends_with("false"),
+            "unexpected response: {}",
+            response
+        );
+
+        // the previous response may be buffered by hyper.
+        let mut stream = async_std::net::TcpStream::connect(addr).await?;
+        stream
+            .write_all(
+                b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n\
+                  GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            )
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        assert_eq!(1, response.matches("103 Early Hints").count());
+        assert_eq!(2, response.matches("200 OK").count());
+        assert!(response.contains("\r\ntrue\r\n"));
+        assert!(response.contains("\r\nfalse\r\n"));
+
+        // hyper may buffer "100 Continue".
+        let mut stream = async_std::net::TcpStream::connect(addr).await?;
+        stream
+            .write_all(
+                b"POST / HTTP/1.1\r\nHost: localhost\r\nExpect: 100-continue\r\n\
+                  Content-Length: 0\r\nConnection: close\r\n\r\n",
+            )
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        assert!(!response.contains("103 Early Hints"));
+        assert!(response.contains("\r\nfalse\r\n"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn will_keep_alive() -> Result<(), Box<dyn std::error::Error>> {
+        use futures::{AsyncReadExt, AsyncWriteExt};
+        let (addr, server) = App::new(())
+            .end(|mut ctx| async move {
+                let keep_alive = ctx.will_keep_alive().await;
+                ctx.resp_mut().await.write_str(keep_alive.to_string());
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!("true", resp.text().await?);
+
+        let cases = [
+            ("GET / HTTP/1.1\r\nHost: localhost\r\n", true),
+            ("GET / HTTP/1.1\r\nConnection: Upgrade, Close\r\n", false),
+            ("GET / HTTP/1.0\r\n", false),
+            ("GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n", true),
+        ];
+        for (request, expected) in cases.iter() {
+            let mut stream = async_std::net::TcpStream::connect(addr).await?;
+            stream
+                .write_all(format!("{}\r\n", request).as_bytes())
+                .await?;
+            // a persistent connection is not closed by server,
+            // read until the end of body instead of eof.
+            let mut response = Vec::new();
+            let mut buf = [0; 1024];
+            while ![&b"\r\n0\r\n\r\n"[..], b"\r\n\r\ntrue", b"\r\n\r\nfalse"]
+                .iter()
+                .any(|end| response.ends_with(end))
+            {
+                let size = stream.read(&mut buf).await?;
+                if size == 0 {
+                    break;
+                }
+                response.extend_from_slice(&buf[..size]);
+            }
+            let response = String::from_utf8(response)?;
+            assert!(
+                response.contains(&format!("\r\n{}\r\n", expected))
+                    || response.ends_with(&format!("\r\n\r\n{}", expected)),
+                "unexpected response of request {}: {}",
+                request,
+                response
+            );
+        }
+        Ok(())
+    }
 }
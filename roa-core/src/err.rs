@@ -58,6 +58,12 @@ macro_rules! throw {
 }
 
 /// The `Error` of roa.
+///
+/// An error of 5xx (`ErrorKind::ServerError`) returned by the top middleware
+/// is also returned to hyper after its status and message are set,
+/// so the connection is aborted and the client may see no response at all.
+/// Middleware that mean to respond a 5xx should set the status and write the body
+/// directly, then return `Ok`.
 #[derive(Debug, Clone)]
 pub struct Error {
     /// StatusCode will be responded to client if Error is thrown by the top middleware.
@@ -186,6 +192,94 @@ impl Error {
     }
 }
 
+/// A typed error which can be converted into `Error` automatically,
+/// the status code and exposure are determined by the error itself,
+/// and the message is its `Display`.
+///
+/// With it, handlers can propagate custom errors by `?` or `map_err(Error::from)`,
+/// instead of `throw!` at each call site. `throw!` is still available for ad-hoc errors.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa_core::{App, Context, Error, IntoError, Result, throw};
+/// use async_std::task::spawn;
+/// use http::StatusCode;
+/// use std::fmt::{self, Display, Formatter};
+///
+/// #[derive(Debug)]
+/// enum UserError {
+///     NotFound(u64),
+///     Banned,
+/// }
+///
+/// impl Display for UserError {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+///         match self {
+///             UserError::NotFound(id) => write!(f, "user {} not found", id),
+///             UserError::Banned => f.write_str("user is banned"),
+///         }
+///     }
+/// }
+///
+/// impl IntoError for UserError {
+///     fn status_code(&self) -> StatusCode {
+///         match self {
+///             UserError::NotFound(_) => StatusCode::NOT_FOUND,
+///             UserError::Banned => StatusCode::FORBIDDEN,
+///         }
+///     }
+/// }
+///
+/// fn find_user(id: u64) -> std::result::Result<String, UserError> {
+///     match id {
+///         0 => Ok("Hexilee".to_string()),
+///         1 => Err(UserError::Banned),
+///         _ => Err(UserError::NotFound(id)),
+///     }
+/// }
+///
+/// async fn get_user(mut ctx: Context<()>) -> Result {
+///     let id = match ctx.uri().await.path() {
+///         "/0" => 0,
+///         "/1" => 1,
+///         "/2" => 2,
+///         _ => throw!(StatusCode::BAD_REQUEST, "invalid id"),
+///     };
+///     let name = find_user(id)?;
+///     ctx.resp_mut().await.write_str(name);
+///     Ok(())
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///     let (addr, server) = App::new(()).end(get_user).run_local()?;
+///     spawn(server);
+///     let resp = reqwest::get(&format!("http://{}/1", addr)).await?;
+///     assert_eq!(StatusCode::FORBIDDEN, resp.status());
+///     assert_eq!("user is banned", resp.text().await?);
+///     let resp = reqwest::get(&format!("http://{}/2", addr)).await?;
+///     assert_eq!(StatusCode::NOT_FOUND, resp.status());
+///     assert_eq!("user 2 not found", resp.text().await?);
+///     Ok(())
+/// }
+/// ```
+pub trait IntoError: Display {
+    /// Status code of this error.
+    fn status_code(&self) -> StatusCode;
+
+    /// If message exposed, by default only messages of client errors (4xx) are exposed.
+    fn expose(&self) -> bool {
+        self.status_code().is_client_error()
+    }
+}
+
+impl<E: IntoError> From<E> for Error {
+    fn from(err: E) -> Self {
+        Self::new(err.status_code(), &err, err.expose())
+    }
+}
+
 macro_rules! internal_server_error {
     ($error:ty) => {
         impl From<$error> for Error {
@@ -206,3 +300,46 @@ impl Display for Error {
 }
 
 impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, IntoError};
+    use http::StatusCode;
+    use std::fmt::{self, Display, Formatter};
+
+    enum TypedError {
+        Conflict,
+        Unavailable,
+    }
+
+    impl Display for TypedError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            match self {
+                TypedError::Conflict => f.write_str("conflict"),
+                TypedError::Unavailable => f.write_str("unavailable"),
+            }
+        }
+    }
+
+    impl IntoError for TypedError {
+        fn status_code(&self) -> StatusCode {
+            match self {
+                TypedError::Conflict => StatusCode::CONFLICT,
+                TypedError::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            }
+        }
+    }
+
+    #[test]
+    fn from_typed_error() {
+        let err = Error::from(TypedError::Conflict);
+        assert_eq!(StatusCode::CONFLICT, err.status_code);
+        assert_eq!("conflict", err.message);
+        assert!(err.expose);
+
+        let err: Error = TypedError::Unavailable.into();
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, err.status_code);
+        assert_eq!("unavailable", err.message);
+        assert!(!err.expose);
+    }
+}
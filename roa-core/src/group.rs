@@ -1,4 +1,6 @@
 use crate::{async_trait, Context, Middleware, Next, Result, State};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 /// A middleware composing and executing other middlewares in a stack-like manner.
@@ -10,18 +12,165 @@ impl<S> Join<S> {
     }
 }
 
-#[async_trait]
-impl<S: State> Middleware<S> for Join<S> {
-    async fn handle(self: Arc<Self>, ctx: Context<S>, mut next: Next) -> Result {
-        for middleware in self.0.iter().rev() {
+impl<S: State> Join<S> {
+    /// Compose middlewares and next into a new next.
+    fn compose(&self, ctx: Context<S>, mut next: Next) -> Next {
+        for (index, middleware) in self.0.iter().enumerate().rev() {
             let ctx = ctx.clone();
             let middleware = middleware.clone();
-            next = Box::new(move || middleware.handle(ctx, next))
+            // the last one may be an endpoint, which never calls next.
+            if ctx.warn_skipped_next && index + 1 < self.0.len() {
+                next = Box::new(move || Box::pin(handle_checked(middleware, ctx, next)))
+            } else {
+                next = Box::new(move || middleware.handle(ctx, next))
+            }
         }
-        next().await
+        next
+    }
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for Join<S> {
+    async fn handle(self: Arc<Self>, ctx: Context<S>, next: Next) -> Result {
+        self.compose(ctx, next)().await
     }
 }
 
+/// A reusable stack of middlewares, which can be used by many apps or routers.
+///
+/// Middlewares are composed like `join_all` and executed in order of pushing,
+/// cloning a stack only clones the `Arc` of middlewares.
+///
+/// ### Example
+/// ```rust
+/// use roa_core::{App, Context, MiddlewareStack, Next};
+/// use async_std::task::spawn;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut stack = MiddlewareStack::new();
+///     stack.push(|mut ctx: Context<()>, next: Next| async move {
+///         next().await?;
+///         ctx.resp_mut().await.write_str("Hello, World");
+///         Ok(())
+///     });
+///     for _ in 0..2 {
+///         let (addr, server) = App::new(()).gate(stack.clone()).run_local()?;
+///         spawn(server);
+///         let resp = reqwest::get(&format!("http://{}", addr)).await?;
+///         assert_eq!("Hello, World", resp.text().await?);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct MiddlewareStack<S>(Join<S>);
+
+impl<S> MiddlewareStack<S> {
+    /// Construct an empty stack.
+    pub fn new() -> Self {
+        Self(Join::new(Vec::new()))
+    }
+}
+
+impl<S: State> MiddlewareStack<S> {
+    /// Push a middleware onto the stack.
+    pub fn push(&mut self, middleware: impl Middleware<S>) -> &mut Self {
+        (self.0).0.push(Arc::new(middleware));
+        self
+    }
+}
+
+impl<S> Default for MiddlewareStack<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Clone for MiddlewareStack<S> {
+    fn clone(&self) -> Self {
+        Self(Join::new((self.0).0.clone()))
+    }
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for MiddlewareStack<S> {
+    async fn handle(self: Arc<Self>, ctx: Context<S>, next: Next) -> Result {
+        self.0.compose(ctx, next)().await
+    }
+}
+
+/// A middleware running another one only if the predicate resolves to true.
+pub(crate) struct Conditional<S, P> {
+    predicate: P,
+    middleware: Arc<dyn Middleware<S>>,
+}
+
+impl<S: State, P> Conditional<S, P> {
+    pub(crate) fn new(predicate: P, middleware: impl Middleware<S>) -> Self {
+        Self {
+            predicate,
+            middleware: Arc::new(middleware),
+        }
+    }
+}
+
+#[async_trait]
+impl<S, P, F> Middleware<S> for Conditional<S, P>
+where
+    S: State,
+    P: 'static + Sync + Send + Fn(Context<S>) -> F,
+    F: 'static + Send + Future<Output = bool>,
+{
+    async fn handle(self: Arc<Self>, ctx: Context<S>, next: Next) -> Result {
+        if (self.predicate)(ctx.clone()).await {
+            self.middleware.clone().handle(ctx, next).await
+        } else {
+            next().await
+        }
+    }
+}
+
+/// Wrap next to track whether it is called.
+fn track(next: Next) -> (Next, Arc<AtomicBool>) {
+    let called = Arc::new(AtomicBool::new(false));
+    let flag = called.clone();
+    let tracked: Next = Box::new(move || {
+        flag.store(true, Ordering::SeqCst);
+        next()
+    });
+    (tracked, called)
+}
+
+/// A unique symbol to mark a request whose skipped next has been warned.
+struct SkippedSymbol;
+
+/// Handle context and warn if a middleware returns `Ok` without calling next,
+/// which silently skips all downstream middlewares.
+///
+/// Only used if enabled by `App::warn_skipped_next`,
+/// warn at most once per request as groups are usually nested.
+async fn handle_checked<S: State>(
+    middleware: Arc<dyn Middleware<S>>,
+    ctx: Context<S>,
+    next: Next,
+) -> Result {
+    let mut context = ctx.clone();
+    let (next, called) = track(next);
+    let result = middleware.handle(ctx, next).await;
+    if result.is_ok()
+        && !called.load(Ordering::SeqCst)
+        && context.load::<SkippedSymbol>("skipped").await.is_none()
+    {
+        context
+            .store::<SkippedSymbol>("skipped", String::new())
+            .await;
+        log::warn!(
+            "a middleware returned Ok without calling next, downstream middlewares are skipped"
+        );
+    }
+    result
+}
+
 /// Join two middleware.
 ///
 /// ```rust
@@ -45,7 +194,12 @@ pub fn join<S: State>(
 
 /// Join all middlewares in a vector.
 ///
-/// All middlewares would be composed and executed in a stack-like manner.
+/// All middlewares would be composed and executed in a stack-like manner:
+/// downstream code runs in order of the vector, upstream code runs in reverse order.
+/// Nested groups keep this order, `join_all(vec![join_all(vec![a, b]), c])` runs like `join_all(vec![a, b, c])`.
+///
+/// If `App::warn_skipped_next` is enabled, a warning is logged if a middleware
+/// except the last one returns `Ok` without calling next.
 ///
 /// ### Example
 /// ```rust
@@ -90,12 +244,80 @@ pub fn join_all<S: State>(
 
 #[cfg(test)]
 mod tests {
-    use crate::{join_all, App, Middleware, Next};
+    use crate::{join, join_all, App, Middleware, MiddlewareStack, Next};
     use async_std::task::spawn;
     use futures::lock::Mutex;
     use http::StatusCode;
+    use log::{Log, Metadata, Record};
+    use std::sync::atomic::Ordering;
     use std::sync::Arc;
 
+    /// A logger recording messages of warnings.
+    struct Recorder(std::sync::Mutex<Vec<String>>);
+
+    impl Log for Recorder {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= log::Level::Warn
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                self.0.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static RECORDER: Recorder = Recorder(std::sync::Mutex::new(Vec::new()));
+
+    /// Count recorded warnings of skipped next.
+    fn skipped_warnings() -> usize {
+        RECORDER
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|message| message.contains("without calling next"))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn middleware_stack() -> Result<(), Box<dyn std::error::Error>> {
+        let vector = Arc::new(Mutex::new(Vec::new()));
+        let mut stack = MiddlewareStack::new();
+        for i in 0..10 {
+            let vec = vector.clone();
+            stack.push(move |_ctx, next: Next| {
+                let vec = vec.clone();
+                async move {
+                    vec.lock().await.push(i);
+                    next().await?;
+                    vec.lock().await.push(i);
+                    Ok(())
+                }
+            });
+        }
+        let mut apps = [App::new(()), App::new(())];
+        for app in apps.iter_mut() {
+            app.gate(stack.clone());
+        }
+        for app in apps.iter() {
+            let (addr, server) = app.run_local()?;
+            spawn(server);
+            let resp = reqwest::get(&format!("http://{}", addr)).await?;
+            assert_eq!(StatusCode::OK, resp.status());
+        }
+        let vector = vector.lock().await;
+        assert_eq!(40, vector.len());
+        for i in 0..10 {
+            assert_eq!(i, vector[i]);
+            assert_eq!(i, vector[19 - i]);
+            assert_eq!(i, vector[20 + i]);
+        }
+        Ok(())
+    }
+
     #[tokio::test]
     async fn middleware_order() -> Result<(), Box<dyn std::error::Error>> {
         let vector = Arc::new(Mutex::new(Vec::new()));
@@ -122,4 +344,79 @@ mod tests {
         }
         Ok(())
     }
+
+    #[tokio::test]
+    async fn nested_order() -> Result<(), Box<dyn std::error::Error>> {
+        let vector = Arc::new(Mutex::new(Vec::new()));
+        let recorder = |name: &'static str| -> Arc<dyn Middleware<()>> {
+            let vec = vector.clone();
+            Arc::new(move |_ctx, next: Next| {
+                let vec = vec.clone();
+                async move {
+                    vec.lock().await.push(format!("{} down", name));
+                    next().await?;
+                    vec.lock().await.push(format!("{} up", name));
+                    Ok(())
+                }
+            })
+        };
+        let inner = join_all(vec![recorder("a"), recorder("b")]);
+        let outer = join(Arc::new(inner), join_all(vec![recorder("c")]));
+        let (addr, server) = App::new(())
+            .gate(outer)
+            .gate(join(recorder("d"), join_all(Vec::new())))
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(
+            vec!["a down", "b down", "c down", "d down", "d up", "c up", "b up", "a up"],
+            *vector.lock().await
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn track_next() -> Result<(), Box<dyn std::error::Error>> {
+        let (next, called) = super::track(Box::new(crate::last));
+        assert!(!called.load(Ordering::SeqCst));
+        next().await?;
+        assert!(called.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn skip_next() -> Result<(), Box<dyn std::error::Error>> {
+        log::set_logger(&RECORDER)?;
+        log::set_max_level(log::LevelFilter::Warn);
+        let vector = Arc::new(Mutex::new(Vec::new()));
+        let vec = vector.clone();
+        let mut app = App::new(());
+        app
+            // forget to call next
+            .gate_fn(|_ctx, _next| async move { Ok(()) })
+            .gate_fn(move |_ctx, next| {
+                let vec = vec.clone();
+                async move {
+                    vec.lock().await.push(0);
+                    next().await
+                }
+            });
+
+        // disabled by default
+        let (addr, server) = app.run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(0, skipped_warnings());
+
+        let (addr, server) = app.warn_skipped_next(true).run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert!(vector.lock().await.is_empty());
+        // warn once, though the skipping gate is nested in many groups
+        assert_eq!(1, skipped_warnings());
+        Ok(())
+    }
 }
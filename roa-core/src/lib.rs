@@ -180,22 +180,22 @@ mod response;
 pub(crate) use app::AddrStream;
 
 #[doc(inline)]
-pub use app::{AddrIncoming, App};
+pub use app::{AddrIncoming, App, Limits, SocketOpts};
 
 #[doc(inline)]
-pub use body::{Body, Callback as BodyCallback};
+pub use body::{Body, BodyKind, Callback as BodyCallback};
 
 #[doc(inline)]
-pub use context::{Bucket, Context, Variable};
+pub use context::{Bucket, ConnectionInfo, Context, Variable};
 
 #[doc(inline)]
-pub use err::{Error, ErrorKind, Result, ResultFuture};
+pub use err::{Error, ErrorKind, IntoError, Result, ResultFuture};
 
 #[doc(inline)]
 pub use middleware::Middleware;
 
 #[doc(inline)]
-pub use group::{join, join_all};
+pub use group::{join, join_all, MiddlewareStack};
 
 #[doc(inline)]
 pub use model::{Model, State};
@@ -214,6 +214,8 @@ pub use http::{header, StatusCode};
 
 pub use async_trait::async_trait;
 
+pub use bytes::Bytes;
+
 pub use hyper::rt::Executor;
 
 pub use hyper::Server;
@@ -1,9 +1,17 @@
+use crate::app::uri_length;
 use crate::Body;
-use futures::stream::TryStreamExt;
+use bytes::Bytes;
+use futures::stream::{Stream, TryStreamExt};
+use http::header::CONTENT_LENGTH;
 use http::{HeaderValue, Method, Uri, Version};
+use hyper::body::HttpBody;
 use hyper::HeaderMap;
 use std::io;
 use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
 
 /// Http request type of roa.
 pub struct Request {
@@ -20,6 +28,10 @@ pub struct Request {
     pub headers: HeaderMap<HeaderValue>,
 
     body: Body,
+
+    trailers: Arc<Mutex<Option<HeaderMap>>>,
+
+    body_size: Arc<AtomicUsize>,
 }
 
 impl Request {
@@ -30,8 +42,70 @@ impl Request {
             version: Version::default(),
             headers: HeaderMap::default(),
             body: Body::new(),
+            trailers: Arc::new(Mutex::new(None)),
+            body_size: Arc::new(AtomicUsize::new(0)),
         }
     }
+
+    /// Clone trailers of the request.
+    ///
+    /// Trailers are received after the body,
+    /// so they are only available once the body is read to the end.
+    /// Return `None` if the body is not fully read yet, or the client sends no trailer.
+    ///
+    /// Trailers of HTTP/1.1 chunked bodies are discarded by hyper,
+    /// only those of HTTP/2 requests can be read.
+    pub fn trailers(&self) -> Option<HeaderMap> {
+        self.trailers
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Get the size of the request in bytes, counting request line, headers and body.
+    ///
+    /// The request line and headers are measured by current fields of the request,
+    /// in their HTTP/1.1 text form regardless of the protocol.
+    /// The body is measured by "content-length" if present,
+    /// otherwise by bytes read so far,
+    /// so the size of a streaming body is only known after it's read to the end.
+    pub fn size(&self) -> usize {
+        let content_length = self
+            .headers
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+        let body_size =
+            content_length.unwrap_or_else(|| self.body_size.load(Ordering::SeqCst));
+        self.head_size() + body_size
+    }
+
+    /// Size of request line and headers in HTTP/1.1 text form.
+    fn head_size(&self) -> usize {
+        // "{method} {uri} {version}\r\n"
+        let line = self.method.as_str().len()
+            + uri_length(&self.uri)
+            + version_str(self.version).len()
+            + 4;
+        // "{name}: {value}\r\n" for each header, then "\r\n"
+        let headers: usize = self
+            .headers
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len() + 4)
+            .sum();
+        line + headers + 2
+    }
+}
+
+/// Text form of `version` in request line.
+fn version_str(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_2 => "HTTP/2.0",
+        Version::HTTP_3 => "HTTP/3.0",
+        _ => "HTTP/1.1",
+    }
 }
 
 impl Default for Request {
@@ -64,6 +138,8 @@ impl From<http::Request<Body>> for Request {
             version: parts.version,
             headers: parts.headers,
             body,
+            trailers: Arc::new(Mutex::new(None)),
+            body_size: Arc::new(AtomicUsize::new(0)),
         }
     }
 }
@@ -72,20 +148,90 @@ impl From<http::Request<hyper::Body>> for Request {
     fn from(req: http::Request<hyper::Body>) -> Self {
         let (parts, body) = req.into_parts();
         let mut new_req: Self = http::Request::from_parts(parts, Body::new()).into();
-        new_req.write(
-            body.map_err(|err| io::Error::new(io::ErrorKind::Other, err))
-                .into_async_read(),
-        );
+        let data = DataStream {
+            body,
+            trailers: new_req.trailers.clone(),
+            size: new_req.body_size.clone(),
+            data_end: false,
+            done: false,
+        };
+        new_req.write(data.into_async_read());
         new_req
     }
 }
 
+/// A stream of data frames of a http body,
+/// which counts received bytes and stores trailers once all data frames are polled.
+struct DataStream<B> {
+    body: B,
+    trailers: Arc<Mutex<Option<HeaderMap>>>,
+    size: Arc<AtomicUsize>,
+    data_end: bool,
+    done: bool,
+}
+
+impl<B> Stream for DataStream<B>
+where
+    B: HttpBody<Data = Bytes> + Unpin,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        if !self.data_end {
+            match futures::ready!(Pin::new(&mut self.body).poll_data(cx)) {
+                Some(data) => {
+                    if let Ok(ref data) = data {
+                        self.size.fetch_add(data.len(), Ordering::SeqCst);
+                    }
+                    return Poll::Ready(Some(
+                        data.map_err(|err| io::Error::new(io::ErrorKind::Other, err)),
+                    ));
+                }
+                None => self.data_end = true,
+            }
+        }
+        let trailers = futures::ready!(Pin::new(&mut self.body).poll_trailers(cx));
+        self.done = true;
+        match trailers {
+            Ok(trailers) => {
+                *self.trailers.lock().unwrap_or_else(|err| err.into_inner()) = trailers;
+                Poll::Ready(None)
+            }
+            Err(err) => {
+                Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::Other, err))))
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::App;
     use async_std::task::spawn;
     use futures::AsyncReadExt;
-    use http::StatusCode;
+    use http::{StatusCode, Version};
+
+    #[test]
+    fn version_str() {
+        for version in [
+            Version::HTTP_09,
+            Version::HTTP_10,
+            Version::HTTP_11,
+            Version::HTTP_2,
+            Version::HTTP_3,
+        ]
+        .iter()
+        {
+            assert_eq!(format!("{:?}", version), super::version_str(*version));
+        }
+    }
 
     #[tokio::test]
     async fn body_read() -> Result<(), Box<dyn std::error::Error>> {
@@ -107,4 +253,102 @@ mod tests {
         assert_eq!(StatusCode::OK, resp.status());
         Ok(())
     }
+
+    #[tokio::test]
+    async fn size() -> Result<(), Box<dyn std::error::Error>> {
+        use async_std::net::TcpStream;
+        use futures::AsyncWriteExt;
+        let mut app = App::new(());
+        app.gate_fn(|mut ctx, _next| async move {
+            let before = ctx.request_size().await;
+            let mut data = Vec::new();
+            ctx.req_mut().await.read_to_end(&mut data).await?;
+            let after = ctx.request_size().await;
+            ctx.resp_mut()
+                .await
+                .write_str(format!("{} {}", before, after));
+            Ok(())
+        });
+        let (addr, server) = app.run_local()?;
+        spawn(server);
+
+        let head = "POST /upload?id=1 HTTP/1.1\r\n\
+                    host: localhost\r\n\
+                    connection: close\r\n\
+                    content-length: 13\r\n\r\n";
+        let mut stream = TcpStream::connect(addr).await?;
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(b"Hello, World!").await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        let total = head.len() + 13;
+        assert!(response.contains(&format!("{} {}", total, total)));
+
+        // streaming body is only counted after read
+        let head = "POST / HTTP/1.1\r\n\
+                    host: localhost\r\n\
+                    connection: close\r\n\
+                    transfer-encoding: chunked\r\n\r\n";
+        let mut stream = TcpStream::connect(addr).await?;
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(b"5\r\nHello\r\n0\r\n\r\n").await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        assert!(response.contains(&format!("{} {}", head.len(), head.len() + 5)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn trailers() -> Result<(), Box<dyn std::error::Error>> {
+        use bytes::Bytes;
+        use http::{HeaderMap, HeaderValue};
+        use hyper::body::HttpBody;
+        use std::pin::Pin;
+        use std::task::{Context, Poll};
+
+        /// A body with a data frame and trailers.
+        struct TrailerBody(Option<Bytes>);
+
+        impl HttpBody for TrailerBody {
+            type Data = Bytes;
+            type Error = hyper::Error;
+
+            fn poll_data(
+                mut self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+            ) -> Poll<Option<Result<Bytes, hyper::Error>>> {
+                Poll::Ready(self.0.take().map(Ok))
+            }
+
+            fn poll_trailers(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+            ) -> Poll<Result<Option<HeaderMap>, hyper::Error>> {
+                let mut trailers = HeaderMap::new();
+                trailers.insert("x-checksum", HeaderValue::from_static("3"));
+                Poll::Ready(Ok(Some(trailers)))
+            }
+        }
+
+        let (addr, server) = App::new(())
+            .end(|mut ctx| async move {
+                assert!(ctx.trailers().await.is_none());
+                let mut data = String::new();
+                ctx.req_mut().await.read_to_string(&mut data).await?;
+                let trailers = ctx.trailers().await.unwrap();
+                assert_eq!(data.len().to_string(), trailers["x-checksum"]);
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = hyper::Client::builder()
+            .http2_only(true)
+            .build_http::<TrailerBody>();
+        let req = http::Request::post(format!("http://{}", addr))
+            .body(TrailerBody(Some(Bytes::from_static(b"abc"))))?;
+        let resp = client.request(req).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        Ok(())
+    }
 }
@@ -1,7 +1,14 @@
-use crate::Body;
+use crate::{Body, BodyCallback, BodyKind, ResultFuture};
+use async_std::io::BufRead;
+use async_std::task::{Context, Poll};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
 use http::{HeaderValue, StatusCode, Version};
 use hyper::HeaderMap;
+use std::future::Future;
+use std::io;
 use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
 
 /// Http response type of roa.
 pub struct Response {
@@ -14,6 +21,93 @@ pub struct Response {
     /// Raw header map.
     pub headers: HeaderMap<HeaderValue>,
     body: Body,
+
+    /// Callbacks attached to the final body, registered by `Context::on_response_sent`.
+    pub(crate) sent: Vec<Box<BodyCallback>>,
+
+    /// Slots of deferred segments, written by `Response::write_deferred`.
+    deferred: Vec<Arc<Mutex<Deferred>>>,
+}
+
+/// State of a deferred body, shared by its segment and the response.
+enum Deferred {
+    Pending(ResultFuture<Body>),
+    Ready(Body),
+    Failed(String),
+    Taken,
+}
+
+/// A segment reading the body produced by a deferred future,
+/// the future is polled on the first read if it's not evaluated yet.
+struct DeferredSegment {
+    slot: Arc<Mutex<Deferred>>,
+    body: Option<Body>,
+}
+
+fn lock(slot: &Mutex<Deferred>) -> MutexGuard<'_, Deferred> {
+    slot.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+impl BufRead for DeferredSegment {
+    fn poll_fill_buf(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.body.is_none() {
+            let mut slot = lock(&this.slot);
+            let body = match std::mem::replace(&mut *slot, Deferred::Taken) {
+                Deferred::Pending(mut future) => match future.as_mut().poll(cx) {
+                    Poll::Pending => {
+                        *slot = Deferred::Pending(future);
+                        return Poll::Pending;
+                    }
+                    Poll::Ready(Ok(body)) => body,
+                    Poll::Ready(Err(err)) => {
+                        *slot = Deferred::Failed(err.message.clone());
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::Other,
+                            err.message,
+                        )));
+                    }
+                },
+                Deferred::Ready(body) => body,
+                Deferred::Failed(message) => {
+                    *slot = Deferred::Failed(message.clone());
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        message,
+                    )));
+                }
+                Deferred::Taken => Body::default(),
+            };
+            this.body = Some(body);
+        }
+        match this.body {
+            Some(ref mut body) => Pin::new(body).poll_fill_buf(cx),
+            None => Poll::Ready(Ok(&[])),
+        }
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        if let Some(ref mut body) = self.get_mut().body {
+            Pin::new(body).consume(amt)
+        }
+    }
+}
+
+impl async_std::io::Read for DeferredSegment {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let data = futures::ready!(self.as_mut().poll_fill_buf(cx))?;
+        let size = std::cmp::min(data.len(), buf.len());
+        buf[..size].copy_from_slice(&data[..size]);
+        self.consume(size);
+        Poll::Ready(Ok(size))
+    }
 }
 
 impl Response {
@@ -23,17 +117,132 @@ impl Response {
             version: Version::default(),
             headers: HeaderMap::default(),
             body: Body::new(),
+            sent: Vec::new(),
+            deferred: Vec::new(),
+        }
+    }
+
+    /// Write a segment produced by `future`, which is evaluated lazily.
+    ///
+    /// ### Ordering
+    ///
+    /// The segment takes its place in the body like any other `write*`,
+    /// so middleware wrapping the body (like `Compress` of roa) wrap it as well.
+    ///
+    /// The future is evaluated after the upstream phase of every middleware,
+    /// that is, after all code following `next().await` has completed,
+    /// and right before the response is sent.
+    /// Unless an upstream middleware reads the body before that,
+    /// then it's evaluated on the first read.
+    ///
+    /// The future is dropped without evaluation if the middleware chain returns an error.
+    ///
+    /// ### Errors
+    ///
+    /// If the future fails before the response is sent, the response is replaced
+    /// by 500 INTERNAL SERVER ERROR, with the message of error as body if it's exposed.
+    /// If it fails when it's read by upstream middleware, the read fails.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::{App, Body, BodyKind};
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|mut ctx| async move {
+    ///             let mut resp = ctx.resp_mut().await;
+    ///             resp.write_str("Hello, ");
+    ///             resp.write_deferred(async {
+    ///                 let mut body = Body::default();
+    ///                 body.write_str("World");
+    ///                 Ok(body)
+    ///             });
+    ///             // the length of a deferred segment is unknown.
+    ///             assert_eq!(BodyKind::Stream, resp.kind());
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     assert_eq!("Hello, World", resp.text().await?);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn write_deferred(
+        &mut self,
+        future: impl 'static + Send + Future<Output = crate::Result<Body>>,
+    ) -> &mut Self {
+        let slot = Arc::new(Mutex::new(Deferred::Pending(Box::pin(future))));
+        self.deferred.push(slot.clone());
+        self.body.write_buf(DeferredSegment { slot, body: None });
+        self
+    }
+
+    /// Drop pending deferred futures without evaluation, their segments become empty.
+    pub(crate) fn clear_deferred(&mut self) {
+        for slot in std::mem::take(&mut self.deferred) {
+            let mut slot = lock(&slot);
+            if let Deferred::Pending(_) = *slot {
+                *slot = Deferred::Ready(Body::default());
+            }
         }
     }
 
+    /// Evaluate pending deferred futures in order, stop at the first error.
+    pub(crate) async fn resolve_deferred(&mut self) -> crate::Result {
+        for slot in std::mem::take(&mut self.deferred) {
+            let state = std::mem::replace(&mut *lock(&slot), Deferred::Taken);
+            let pending = match state {
+                Deferred::Pending(future) => future,
+                state => {
+                    *lock(&slot) = state;
+                    continue;
+                }
+            };
+            match pending.await {
+                Ok(body) => *lock(&slot) = Deferred::Ready(body),
+                Err(err) => {
+                    *lock(&slot) = Deferred::Failed(err.message.clone());
+                    return Err(err);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop the body as a response to HEAD request,
+    /// set "Content-Length" by the length of body if it's known and not set.
+    pub(crate) fn strip_body(&mut self) {
+        let length = match self.body.kind() {
+            BodyKind::Empty => Some(0),
+            BodyKind::Bytes(length) => Some(length),
+            BodyKind::Stream => None,
+        };
+        if let Some(length) = length {
+            if !self.headers.contains_key(CONTENT_LENGTH) {
+                self.headers.insert(CONTENT_LENGTH, length.into());
+            }
+        }
+        self.body = Body::new();
+    }
+
     fn into_resp(self) -> http::Response<hyper::Body> {
         let (mut parts, _) = http::Response::new(()).into_parts();
         let Response {
             status,
             version,
             headers,
-            body,
+            mut body,
+            sent,
+            ..
         } = self;
+        for callback in sent {
+            body.on_finish(callback);
+        }
         parts.status = status;
         parts.version = version;
         parts.headers = headers;
@@ -67,3 +276,185 @@ impl Default for Response {
         Self::new()
     }
 }
+
+#[cfg(feature = "json")]
+impl Response {
+    /// Construct a response with 200 OK and `value` serialized as json body,
+    /// "Content-Type" is set to "application/json; charset=utf-8".
+    ///
+    /// It's the counterpart of `PowerBody::write_json` without a `Context`,
+    /// to build a response to replace the one of context, or in tests.
+    ///
+    /// Return 500 INTERNAL SERVER ERROR if `value` cannot be serialized.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa_core::{BodyKind, Response};
+    /// use http::header::CONTENT_TYPE;
+    ///
+    /// let response = Response::json(&vec![1, 2, 3]).unwrap();
+    /// assert_eq!("application/json; charset=utf-8", response.headers[CONTENT_TYPE]);
+    /// assert_eq!(BodyKind::Bytes(7), response.kind());
+    /// ```
+    pub fn json<B: serde::Serialize + ?Sized>(value: &B) -> crate::Result<Self> {
+        let bytes = serde_json::to_vec(value).map_err(|err| {
+            log::error!("object cannot be serialized to json: {}", err);
+            crate::Error::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("{}\nobject cannot be serialized to json", err),
+                false,
+            )
+        })?;
+        let mut response = Self::from(bytes);
+        response.headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/json; charset=utf-8"),
+        );
+        Ok(response)
+    }
+}
+
+impl From<Vec<u8>> for Response {
+    /// Construct a response with 200 OK and bytes as body,
+    /// "Content-Type" is left to the caller.
+    fn from(bytes: Vec<u8>) -> Self {
+        let mut response = Self::new();
+        response.write_bytes(bytes);
+        response
+    }
+}
+
+impl From<String> for Response {
+    /// Construct a response with 200 OK and data as body,
+    /// "Content-Type" is left to the caller.
+    fn from(data: String) -> Self {
+        let mut response = Self::new();
+        response.write_string(data);
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Response;
+    use crate::{App, BodyKind};
+    use async_std::task::spawn;
+    use http::header::CONTENT_TYPE;
+    use http::StatusCode;
+
+    #[test]
+    fn from_bytes() {
+        let response = Response::from(b"Hello, World".to_vec());
+        assert_eq!(StatusCode::OK, response.status);
+        assert!(response.headers.is_empty());
+        assert_eq!(BodyKind::Bytes(12), response.kind());
+
+        let response: Response = String::from("Hello, World").into();
+        assert!(response.headers.is_empty());
+        assert_eq!(BodyKind::Bytes(12), response.kind());
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn json() -> Result<(), Box<dyn std::error::Error>> {
+        use std::collections::HashMap;
+        let (addr, server) = App::new(())
+            .end(|mut ctx| async move {
+                let mut user = HashMap::new();
+                user.insert("name", "Hexilee");
+                *ctx.resp_mut().await = Response::json(&user)?;
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(
+            "application/json; charset=utf-8",
+            resp.headers()[CONTENT_TYPE]
+        );
+        assert_eq!(r#"{"name":"Hexilee"}"#, resp.text().await?);
+
+        // keys of json object must be strings
+        let mut invalid = HashMap::new();
+        invalid.insert((0, 0), 0);
+        let err = Response::json(&invalid).err().unwrap();
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, err.status_code);
+        assert!(!err.expose);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_deferred() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{Body, Error};
+        use futures::AsyncReadExt;
+        let (addr, server) = App::new(())
+            .gate_fn(|mut ctx, next| async move {
+                next().await?;
+                assert_eq!(BodyKind::Stream, ctx.resp().await.kind());
+                if ctx.uri().await.path() == "/read" {
+                    // upstream reading body evaluates deferred segments in place
+                    let mut resp = ctx.resp_mut().await;
+                    let mut data = String::new();
+                    std::mem::take(&mut **resp)
+                        .read_to_string(&mut data)
+                        .await?;
+                    resp.write_str(data.to_uppercase());
+                }
+                Ok(())
+            })
+            .end(|mut ctx| async move {
+                let path = ctx.uri().await.path().to_string();
+                let mut resp = ctx.resp_mut().await;
+                resp.write_str("Hello, ");
+                resp.write_deferred(async {
+                    let mut body = Body::default();
+                    body.write_str("World");
+                    Ok(body)
+                });
+                if path == "/fail" {
+                    resp.write_deferred(async {
+                        Err(Error::new(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "body is unavailable",
+                            true,
+                        ))
+                    });
+                }
+                resp.write_str("!");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("Hello, World!", resp.text().await?);
+
+        let resp = reqwest::get(&format!("http://{}/read", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("HELLO, WORLD!", resp.text().await?);
+
+        let resp = reqwest::get(&format!("http://{}/fail", addr)).await?;
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, resp.status());
+        assert_eq!("body is unavailable", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn replace_response() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(|mut ctx| async move {
+                let mut response = Response::from("Hello, World".to_string());
+                response.status = StatusCode::ACCEPTED;
+                *ctx.resp_mut().await = response;
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::ACCEPTED, resp.status());
+        assert!(resp.headers().get(CONTENT_TYPE).is_none());
+        assert_eq!("Hello, World", resp.text().await?);
+        Ok(())
+    }
+}
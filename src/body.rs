@@ -1,5 +1,6 @@
 //! The body module of roa.
-//! This module provides a context extension `PowerBody`.
+//! This module provides a context extension `PowerBody`
+//! and endpoints `serve_file`, `serve_dir` and `serve_embedded`.
 //!
 //! ### Read/write body in a simpler way.
 //!
@@ -80,29 +81,64 @@
 //!     // render html template, based on [askama](https://github.com/djc/askama).
 //!     // set "Content-Type"
 //!     ctx.render(&user).await?;
+//!
+//!     // serialize object in the best format by "Accept",
+//!     // set "Content-Type"
+//!     ctx.respond_negotiated(&user).await?;
 //!     Ok(())
 //! }
 //! ```
 
+mod accepts;
 mod decode;
+mod dir;
+mod embed;
+mod file;
 mod json;
 mod mime_ext;
+mod negotiate;
+#[cfg(feature = "schema")]
+mod schema;
 mod urlencoded;
+#[cfg(feature = "validate")]
+mod validate;
+#[cfg(feature = "xml")]
+mod xml;
+
+pub use accepts::{accepts, Accepts};
+pub use dir::{serve_dir, ServeDir};
+pub use embed::{serve_embedded, Assets, ServeEmbedded};
+pub use file::{serve_file, ServeFile};
+pub use negotiate::{enforce_accept, EnforceAccept, Format};
+
+#[cfg(feature = "schema")]
+pub use jsonschema::JSONSchema;
 
 use crate::core::{async_trait, throw, Context, Error, Result, State, StatusCode};
 use crate::header::FriendlyHeaders;
 use askama::Template;
 use async_std::fs::File;
 use async_std::path::Path;
-use futures::{AsyncBufRead as BufRead, AsyncReadExt};
+use futures::{AsyncBufRead as BufRead, AsyncReadExt, Stream};
 use mime::Mime;
 use mime_ext::MimeExt;
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+#[cfg(feature = "validate")]
+use validator::Validate;
 
 const APPLICATION_JSON_UTF_8: &str = "application/json; charset=utf-8";
 
+#[cfg(feature = "xml")]
+const APPLICATION_XML_UTF_8: &str = "application/xml; charset=utf-8";
+
+#[cfg(not(feature = "xml"))]
+const UNSUPPORTED_TYPE: &str = "Content-Type can only be JSON or URLENCODED";
+
+#[cfg(feature = "xml")]
+const UNSUPPORTED_TYPE: &str = "Content-Type can only be JSON, URLENCODED or XML";
+
 /// A context extension to read/write body more simply.
 #[async_trait]
 pub trait PowerBody {
@@ -112,6 +148,32 @@ pub trait PowerBody {
     /// try to get mime content type of response.
     async fn response_type(&self) -> Option<Result<Mime>>;
 
+    /// check whether the mime content type of request is `mime`, ignoring parameters.
+    ///
+    /// Return false if "Content-Type" is absent or invalid.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::core::{Context, Result};
+    /// use roa::body::PowerBody;
+    /// use serde_json::Value;
+    ///
+    /// async fn post(mut ctx: Context<()>) -> Result {
+    ///     if ctx.content_type_is(&mime::TEXT_PLAIN).await {
+    ///         let text = ctx.read_text().await?;
+    ///         ctx.write_text(text).await
+    ///     } else {
+    ///         let value: Value = ctx.read_json().await?;
+    ///         ctx.write_json(&value).await
+    ///     }
+    /// }
+    /// ```
+    async fn content_type_is(&self, mime: &Mime) -> bool;
+
+    /// check whether the mime content type of request is "application/json".
+    async fn is_json(&self) -> bool;
+
     /// read request body as Vec<u8>.
     async fn body_buf(&mut self) -> Result<Vec<u8>>;
 
@@ -124,18 +186,275 @@ pub trait PowerBody {
     /// read request body as "application/x-www-form-urlencoded".
     async fn read_form<B: DeserializeOwned>(&mut self) -> Result<B>;
 
+    /// read request body as "application/xml".
+    #[cfg(feature = "xml")]
+    async fn read_xml<B: DeserializeOwned>(&mut self) -> Result<B>;
+
+    /// read request body as text, decoded by the charset of Content-Type,
+    /// utf-8 if it's not declared.
+    ///
+    /// Throw 400 BAD REQUEST if body cannot be decoded,
+    /// or 415 UNSUPPORTED MEDIA TYPE if the charset is unknown.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::body::PowerBody;
+    /// use roa::core::App;
+    /// use roa::core::header::CONTENT_TYPE;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|mut ctx| async move {
+    ///             let text = ctx.read_text().await?;
+    ///             ctx.write_text(text).await
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::Client::new()
+    ///         .post(&format!("http://{}", addr))
+    ///         .header(CONTENT_TYPE, "text/plain; charset=iso-8859-1")
+    ///         .body(vec![0x63, 0x61, 0x66, 0xe9])
+    ///         .send()
+    ///         .await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     assert_eq!("café", resp.text().await?);
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn read_text(&mut self) -> Result<String>;
+
+    /// read request body as "application/json" and validate it.
+    ///
+    /// Throw 422 UNPROCESSABLE ENTITY with a json body listing field errors if validation fails,
+    /// in form of:
+    ///
+    /// ```json
+    /// {
+    ///     "message": "validation failed",
+    ///     "errors": {
+    ///         "name": [{"code": "length", "message": null, "params": {"min": 1, "value": ""}}]
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::body::PowerBody;
+    /// use roa::core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    /// use serde::{Deserialize, Serialize};
+    /// use validator::Validate;
+    /// use validator_derive::Validate;
+    ///
+    /// #[derive(Serialize, Deserialize, Validate)]
+    /// struct User {
+    ///     #[validate(length(min = 1))]
+    ///     name: String,
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|mut ctx| async move {
+    ///             let user: User = ctx.read_json_validated().await?;
+    ///             ctx.write_json(&user).await
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let client = reqwest::Client::new();
+    ///     let resp = client
+    ///         .post(&format!("http://{}", addr))
+    ///         .json(&User { name: "".to_string() })
+    ///         .send()
+    ///         .await?;
+    ///     assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "validate")]
+    async fn read_json_validated<B: DeserializeOwned + Validate>(&mut self)
+        -> Result<B>;
+
+    /// read request body as "application/x-www-form-urlencoded" and validate it.
+    ///
+    /// Throw 422 UNPROCESSABLE ENTITY like `read_json_validated` if validation fails.
+    #[cfg(feature = "validate")]
+    async fn read_form_validated<B: DeserializeOwned + Validate>(&mut self)
+        -> Result<B>;
+
+    /// read request body as "application/json", validate it against a JSON Schema,
+    /// then deserialize it.
+    ///
+    /// Throw 422 UNPROCESSABLE ENTITY with a json body listing schema errors
+    /// if validation fails, in form of:
+    ///
+    /// ```json
+    /// {
+    ///     "message": "schema validation failed",
+    ///     "errors": [{"path": "/name", "message": "\"\" is shorter than 1 character"}]
+    /// }
+    /// ```
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::body::{JSONSchema, PowerBody};
+    /// use roa::core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    /// use serde::{Deserialize, Serialize};
+    /// use serde_json::json;
+    /// use std::sync::Arc;
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// struct User {
+    ///     name: String,
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let schema = JSONSchema::compile(&json!({
+    ///         "type": "object",
+    ///         "properties": {"name": {"type": "string", "minLength": 1}},
+    ///         "required": ["name"]
+    ///     }))
+    ///     .expect("invalid schema");
+    ///     let schema = Arc::new(schema);
+    ///     let (addr, server) = App::new(())
+    ///         .gate_fn(move |mut ctx, _next| {
+    ///             let schema = schema.clone();
+    ///             async move {
+    ///                 let user: User = ctx.read_json_schema(&schema).await?;
+    ///                 ctx.write_json(&user).await
+    ///             }
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::Client::new()
+    ///         .post(&format!("http://{}", addr))
+    ///         .json(&json!({"name": ""}))
+    ///         .send()
+    ///         .await?;
+    ///     assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "schema")]
+    async fn read_json_schema<B: DeserializeOwned>(
+        &mut self,
+        schema: &JSONSchema,
+    ) -> Result<B>;
+
     // read request body as "multipart/form-data"
     // async fn read_multipart(&self) -> Result<B, Status>;
 
     /// write object to response body as "application/json; charset=utf-8"
+    ///
+    /// If the object cannot be serialized, nothing is written,
+    /// the serde error is logged and thrown as a 500 INTERNAL SERVER ERROR never exposed to client.
     async fn write_json<B: Serialize + Sync>(&mut self, data: &B) -> Result;
 
+    /// write a dynamic json value to response body as "application/json; charset=utf-8",
+    /// the same as `write_json` but not generic,
+    /// so it's compiled once instead of once per type in apps writing much ad-hoc json.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::body::PowerBody;
+    /// use roa::core::{Context, Result};
+    /// use serde_json::json;
+    ///
+    /// async fn get(mut ctx: Context<()>) -> Result {
+    ///     ctx.write_value(json!({ "id": 0, "name": "Hexilee" })).await
+    /// }
+    /// ```
+    async fn write_value(&mut self, value: serde_json::Value) -> Result;
+
+    /// write items of a stream to response body as a json array, "application/json; charset=utf-8".
+    ///
+    /// Items are serialized lazily when the body is sent, so the whole array is never buffered.
+    /// As the status has been sent, a failure of serialization can only be logged
+    /// and the connection is aborted.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::body::PowerBody;
+    /// use roa::core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|mut ctx| async move {
+    ///             ctx.write_json_array(futures::stream::iter(0..3)).await
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     assert_eq!("[0,1,2]", resp.text().await?);
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn write_json_array<St>(&mut self, stream: St) -> Result
+    where
+        St: 'static + Stream + Unpin + Sync + Send,
+        St::Item: Serialize;
+
+    /// write object to response body as "application/xml; charset=utf-8"
+    #[cfg(feature = "xml")]
+    async fn write_xml<B: Serialize + Sync>(&mut self, data: &B) -> Result;
+
     /// write object to response body as "text/html; charset=utf-8"
     async fn render<B: Template + Sync>(&mut self, data: &B) -> Result;
 
     /// write object to response body as "text/plain; charset=utf-8"
     async fn write_text<S: ToString + Send>(&mut self, string: S) -> Result;
 
+    /// write object to response body as "text/html; charset=utf-8"
+    async fn write_html<S: ToString + Send>(&mut self, string: S) -> Result;
+
+    /// write bytes to response body and set "Content-Type" as `content_type`.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::body::PowerBody;
+    /// use roa::core::App;
+    /// use roa::core::header::CONTENT_TYPE;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|mut ctx| async move {
+    ///             ctx.write_with_type("body { margin: 0 }", mime::TEXT_CSS_UTF_8).await
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     assert_eq!("text/css; charset=utf-8", resp.headers()[CONTENT_TYPE]);
+    ///     assert_eq!("body { margin: 0 }", resp.text().await?);
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn write_with_type<B: Into<Vec<u8>> + Send>(
+        &mut self,
+        bytes: B,
+        content_type: Mime,
+    ) -> Result;
+
     /// write object to response body as "application/octet-stream"
     async fn write_octet<B: 'static + BufRead + Unpin + Sync + Send>(
         &mut self,
@@ -144,6 +463,108 @@ pub trait PowerBody {
 
     /// write object to response body as extension name of file
     async fn write_file<P: AsRef<Path> + Send>(&mut self, path: P) -> Result;
+
+    /// stream a file to response body, set "Content-Type" by extension name of file,
+    /// "application/octet-stream" if it's unknown.
+    ///
+    /// Unlike `write_file`, "Content-Disposition" is not set,
+    /// and 404 NOT FOUND is thrown if the file is missing or not a regular file.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::body::PowerBody;
+    /// use roa::core::App;
+    /// use roa::core::header::CONTENT_TYPE;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|mut ctx| async move {
+    ///             ctx.send_file("assets/welcome.html").await
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     assert_eq!("text/html", resp.headers()[CONTENT_TYPE]);
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn send_file<P: AsRef<Path> + Send>(&mut self, path: P) -> Result;
+
+    /// write object to response body in the best format of `Format::ALL` by "Accept",
+    /// throw 406 NOT ACCEPTABLE if none of them is accepted.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::body::PowerBody;
+    /// use roa::core::App;
+    /// use roa::core::header::{ACCEPT, CONTENT_TYPE};
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct User {
+    ///     id: u64,
+    ///     name: String,
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end(|mut ctx| async move {
+    ///             let user = User { id: 0, name: "Hexilee".to_string() };
+    ///             ctx.respond_negotiated(&user).await
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let client = reqwest::Client::new();
+    ///     let resp = client
+    ///         .get(&format!("http://{}", addr))
+    ///         .header(ACCEPT, "application/json")
+    ///         .send()
+    ///         .await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     assert_eq!("application/json; charset=utf-8", resp.headers()[CONTENT_TYPE]);
+    ///     assert_eq!(r#"{"id":0,"name":"Hexilee"}"#, resp.text().await?);
+    ///
+    ///     let resp = client
+    ///         .get(&format!("http://{}", addr))
+    ///         .header(ACCEPT, "text/html")
+    ///         .send()
+    ///         .await?;
+    ///     assert_eq!(StatusCode::NOT_ACCEPTABLE, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn respond_negotiated<B: Serialize + Sync>(&mut self, data: &B) -> Result;
+
+    /// write object to response body in the best format of `formats` by "Accept",
+    /// throw 406 NOT ACCEPTABLE if none of them is accepted.
+    ///
+    /// The first format is used if "Accept" is absent,
+    /// formats with the same quality are preferred in order.
+    async fn respond_negotiated_with<B: Serialize + Sync>(
+        &mut self,
+        data: &B,
+        formats: &[Format],
+    ) -> Result;
+}
+
+/// Compare mime types, ignoring parameters.
+fn same_type(mime_type: &Mime, expected: &Mime) -> bool {
+    mime_type.pure_type() == expected.pure_type()
+}
+
+/// "application/xml" or "text/xml".
+#[cfg(feature = "xml")]
+fn is_xml(pure_type: &Mime) -> bool {
+    *pure_type == mime::TEXT_XML || pure_type.essence_str() == "application/xml"
 }
 
 fn parse_mime(value: &str) -> Result<Mime> {
@@ -156,6 +577,21 @@ fn parse_mime(value: &str) -> Result<Mime> {
     })
 }
 
+/// Set "Content-Type" to json if validation fails.
+#[cfg(any(feature = "validate", feature = "schema"))]
+async fn validation_failed<S: State>(ctx: &mut Context<S>, err: Error) -> Error {
+    if err.status_code == StatusCode::UNPROCESSABLE_ENTITY {
+        if let Err(err) = ctx
+            .resp_mut()
+            .await
+            .insert(http::header::CONTENT_TYPE, APPLICATION_JSON_UTF_8)
+        {
+            return err;
+        }
+    }
+    err
+}
+
 #[async_trait]
 impl<S: State> PowerBody for Context<S> {
     async fn request_type(&self) -> Option<Result<Mime>> {
@@ -172,6 +608,17 @@ impl<S: State> PowerBody for Context<S> {
             .map(|result| result.and_then(parse_mime))
     }
 
+    async fn content_type_is(&self, mime: &Mime) -> bool {
+        match self.request_type().await {
+            Some(Ok(mime_type)) => same_type(&mime_type, mime),
+            _ => false,
+        }
+    }
+
+    async fn is_json(&self) -> bool {
+        self.content_type_is(&mime::APPLICATION_JSON).await
+    }
+
     async fn body_buf(&mut self) -> Result<Vec<u8>> {
         let mut data = Vec::new();
         self.req_mut().await.read_to_end(&mut data).await?;
@@ -179,21 +626,25 @@ impl<S: State> PowerBody for Context<S> {
     }
 
     // return BAD_REQUEST status while parsing Content-Type fails.
-    // Content-Type can only be JSON or URLENCODED, otherwise this function will return UNSUPPORTED_MEDIA_TYPE error.
+    // Content-Type can only be JSON, URLENCODED or XML(with feature "xml"),
+    // otherwise this function will return UNSUPPORTED_MEDIA_TYPE error.
     async fn read<B: DeserializeOwned>(&mut self) -> Result<B> {
         match self.request_type().await {
             None => self.read_json().await,
             Some(ret) => {
                 let mime_type = ret?.pure_type();
-                if mime_type == mime::APPLICATION_JSON {
+                if same_type(&mime_type, &mime::APPLICATION_JSON) {
                     self.read_json().await
-                } else if mime_type == mime::APPLICATION_WWW_FORM_URLENCODED {
+                } else if same_type(&mime_type, &mime::APPLICATION_WWW_FORM_URLENCODED) {
                     self.read_form().await
                 } else {
-                    throw!(
-                        StatusCode::UNSUPPORTED_MEDIA_TYPE,
-                        "Content-Type can only be JSON or URLENCODED"
-                    )
+                    #[cfg(feature = "xml")]
+                    {
+                        if is_xml(&mime_type) {
+                            return self.read_xml().await;
+                        }
+                    }
+                    throw!(StatusCode::UNSUPPORTED_MEDIA_TYPE, UNSUPPORTED_TYPE)
                 }
             }
         }
@@ -204,7 +655,7 @@ impl<S: State> PowerBody for Context<S> {
         match self.request_type().await {
             None | Some(Err(_)) => json::from_bytes(&data),
             Some(Ok(mime_type)) => {
-                if mime_type.pure_type() != mime::APPLICATION_JSON {
+                if !same_type(&mime_type, &mime::APPLICATION_JSON) {
                     json::from_bytes(&data)
                 } else {
                     match mime_type.get_param("charset") {
@@ -222,6 +673,37 @@ impl<S: State> PowerBody for Context<S> {
         urlencoded::from_bytes(&self.body_buf().await?)
     }
 
+    #[cfg(feature = "xml")]
+    async fn read_xml<B: DeserializeOwned>(&mut self) -> Result<B> {
+        let data = self.body_buf().await?;
+        match self.request_type().await {
+            None | Some(Err(_)) => xml::from_bytes(&data),
+            Some(Ok(mime_type)) => {
+                if !is_xml(&mime_type.pure_type()) {
+                    xml::from_bytes(&data)
+                } else {
+                    match mime_type.get_param("charset") {
+                        None | Some(mime::UTF_8) => xml::from_bytes(&data),
+                        Some(charset) => {
+                            xml::from_str(&decode::decode(&data, charset.as_str())?)
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn read_text(&mut self) -> Result<String> {
+        let data = self.body_buf().await?;
+        let charset = match self.request_type().await {
+            Some(mime_type) => mime_type?
+                .get_param("charset")
+                .map(|charset| charset.as_str().to_string()),
+            None => None,
+        };
+        decode::decode(&data, charset.as_deref().unwrap_or("utf-8"))
+    }
+
     async fn write_json<B: Serialize + Sync>(&mut self, data: &B) -> Result {
         self.resp_mut().await.write_bytes(json::to_bytes(data)?);
         self.resp_mut()
@@ -230,23 +712,93 @@ impl<S: State> PowerBody for Context<S> {
         Ok(())
     }
 
-    async fn render<B: Template + Sync>(&mut self, data: &B) -> Result {
-        self.resp_mut().await.write_str(
-            data.render().map_err(|err| {
-                Error::new(StatusCode::INTERNAL_SERVER_ERROR, err, false)
-            })?,
-        );
+    async fn write_value(&mut self, value: serde_json::Value) -> Result {
+        self.write_json(&value).await
+    }
+
+    async fn write_json_array<St>(&mut self, stream: St) -> Result
+    where
+        St: 'static + Stream + Unpin + Sync + Send,
+        St::Item: Serialize,
+    {
+        self.resp_mut()
+            .await
+            .write_buf(json::JsonArray::new(stream));
+        self.resp_mut()
+            .await
+            .insert(http::header::CONTENT_TYPE, APPLICATION_JSON_UTF_8)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "validate")]
+    async fn read_json_validated<B: DeserializeOwned + Validate>(
+        &mut self,
+    ) -> Result<B> {
+        let err = match validate::validate(self.read_json().await?) {
+            Ok(data) => return Ok(data),
+            Err(err) => err,
+        };
+        Err(validation_failed(self, err).await)
+    }
+
+    #[cfg(feature = "validate")]
+    async fn read_form_validated<B: DeserializeOwned + Validate>(
+        &mut self,
+    ) -> Result<B> {
+        let err = match validate::validate(self.read_form().await?) {
+            Ok(data) => return Ok(data),
+            Err(err) => err,
+        };
+        Err(validation_failed(self, err).await)
+    }
+
+    #[cfg(feature = "schema")]
+    async fn read_json_schema<B: DeserializeOwned>(
+        &mut self,
+        schema: &JSONSchema,
+    ) -> Result<B> {
+        let value: serde_json::Value = self.read_json().await?;
+        if let Err(err) = schema::validate(schema, &value) {
+            return Err(validation_failed(self, err).await);
+        }
+        json::from_value(value)
+    }
+
+    #[cfg(feature = "xml")]
+    async fn write_xml<B: Serialize + Sync>(&mut self, data: &B) -> Result {
+        self.resp_mut().await.write_bytes(xml::to_bytes(data)?);
         self.resp_mut()
             .await
-            .insert(http::header::CONTENT_TYPE, &mime::TEXT_HTML_UTF_8)?;
+            .insert(http::header::CONTENT_TYPE, APPLICATION_XML_UTF_8)?;
         Ok(())
     }
 
+    async fn render<B: Template + Sync>(&mut self, data: &B) -> Result {
+        let html = data
+            .render()
+            .map_err(|err| Error::new(StatusCode::INTERNAL_SERVER_ERROR, err, false))?;
+        self.write_html(html).await
+    }
+
     async fn write_text<Str: ToString + Send>(&mut self, string: Str) -> Result {
-        self.resp_mut().await.write_str(string.to_string());
+        self.write_with_type(string.to_string(), mime::TEXT_PLAIN_UTF_8)
+            .await
+    }
+
+    async fn write_html<Str: ToString + Send>(&mut self, string: Str) -> Result {
+        self.write_with_type(string.to_string(), mime::TEXT_HTML_UTF_8)
+            .await
+    }
+
+    async fn write_with_type<B: Into<Vec<u8>> + Send>(
+        &mut self,
+        bytes: B,
+        content_type: Mime,
+    ) -> Result {
+        self.resp_mut().await.write_bytes(bytes);
         self.resp_mut()
             .await
-            .insert(http::header::CONTENT_TYPE, &mime::TEXT_PLAIN_UTF_8)?;
+            .insert(http::header::CONTENT_TYPE, content_type)?;
         Ok(())
     }
 
@@ -268,14 +820,14 @@ impl<S: State> PowerBody for Context<S> {
         if let Some(filename) = path.file_name() {
             self.resp_mut().await.insert(
                 http::header::CONTENT_TYPE,
-                &mime_guess::from_path(&filename).first_or_octet_stream(),
+                mime_guess::from_path(filename).first_or_octet_stream(),
             )?;
             let encoded_filename =
                 utf8_percent_encode(&filename.to_string_lossy(), NON_ALPHANUMERIC)
                     .to_string();
             self.resp_mut().await.insert(
                 http::header::CONTENT_DISPOSITION,
-                &format!(
+                format!(
                     "filename={}; filename*=utf-8''{}",
                     &encoded_filename, &encoded_filename
                 ),
@@ -283,18 +835,49 @@ impl<S: State> PowerBody for Context<S> {
         }
         Ok(())
     }
+
+    async fn send_file<P: AsRef<Path> + Send>(&mut self, path: P) -> Result {
+        let path = path.as_ref();
+        let file = file::open(path).await?;
+        self.resp_mut().await.write(file);
+        self.resp_mut().await.insert(
+            http::header::CONTENT_TYPE,
+            mime_guess::from_path(path).first_or_octet_stream(),
+        )?;
+        Ok(())
+    }
+
+    async fn respond_negotiated<B: Serialize + Sync>(&mut self, data: &B) -> Result {
+        self.respond_negotiated_with(data, Format::ALL).await
+    }
+
+    async fn respond_negotiated_with<B: Serialize + Sync>(
+        &mut self,
+        data: &B,
+        formats: &[Format],
+    ) -> Result {
+        let format = match self.header(http::header::ACCEPT).await {
+            Some(Ok(accept)) => negotiate::negotiate(Some(&accept), formats)?,
+            _ => negotiate::negotiate(None, formats)?,
+        };
+        self.resp_mut().await.write_bytes(format.to_bytes(data)?);
+        self.resp_mut()
+            .await
+            .insert(http::header::CONTENT_TYPE, format.content_type())?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{PowerBody, APPLICATION_JSON_UTF_8};
+    use super::{Format, PowerBody, APPLICATION_JSON_UTF_8, UNSUPPORTED_TYPE};
     use crate::core::App;
     use askama::Template;
     use async_std::fs::File;
     use async_std::task::spawn;
     use encoding::EncoderTrap;
     use futures::io::BufReader;
-    use http::header::CONTENT_TYPE;
+    use http::header::{ACCEPT, CONTENT_TYPE};
     use http::StatusCode;
     use serde::{Deserialize, Serialize};
 
@@ -305,6 +888,36 @@ mod tests {
         name: String,
     }
 
+    #[tokio::test]
+    async fn content_type_is() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(move |mut ctx| async move {
+                let is_json = ctx.is_json().await;
+                let is_text = ctx.content_type_is(&mime::TEXT_PLAIN_UTF_8).await;
+                ctx.write_text(format!("{} {}", is_json, is_text)).await
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        for (content_type, expected) in &[
+            ("application/json", "true false"),
+            ("application/json; charset=utf-8", "true false"),
+            ("text/plain; charset=gbk", "false true"),
+            ("application/vnd.api+json", "false false"),
+            ("text/plain/html", "false false"),
+        ] {
+            let resp = client
+                .post(&format!("http://{}", addr))
+                .header(CONTENT_TYPE, *content_type)
+                .send()
+                .await?;
+            assert_eq!(*expected, resp.text().await?);
+        }
+        let resp = client.post(&format!("http://{}", addr)).send().await?;
+        assert_eq!("false false", resp.text().await?);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn read() -> Result<(), Box<dyn std::error::Error>> {
         // miss key
@@ -368,14 +981,11 @@ mod tests {
         let resp = client
             .get(&format!("http://{}", addr))
             .body(serde_json::to_vec(&data)?)
-            .header(CONTENT_TYPE, "text/xml")
+            .header(CONTENT_TYPE, "text/csv")
             .send()
             .await?;
         assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, resp.status());
-        assert_eq!(
-            "Content-Type can only be JSON or URLENCODED",
-            resp.text().await?
-        );
+        assert_eq!(UNSUPPORTED_TYPE, resp.text().await?);
 
         // json; encoding
         let resp = client
@@ -393,6 +1003,105 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn read_chunked() -> Result<(), Box<dyn std::error::Error>> {
+        use async_std::net::TcpStream;
+        use futures::{AsyncReadExt, AsyncWriteExt};
+        let (addr, server) = App::new(())
+            .end(move |mut ctx| async move {
+                assert!(ctx.req().await.headers.get("content-length").is_none());
+                let user: User = ctx.read_json().await?;
+                ctx.write_json(&user).await
+            })
+            .run_local()?;
+        spawn(server);
+        let mut stream = TcpStream::connect(addr).await?;
+        let chunks = [r#"{"id":0,"#, r#""name":"#, r#""Hexilee"}"#];
+        let mut request = String::from(
+            "POST / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+             Content-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n",
+        );
+        for chunk in chunks.iter() {
+            request.push_str(&format!("{:x}\r\n{}\r\n", chunk.len(), chunk));
+        }
+        request.push_str("0\r\n\r\n");
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains(r#"{"id":0,"name":"Hexilee"}"#));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_text() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(move |mut ctx| async move {
+                let text = ctx.read_text().await?;
+                ctx.write_text(text).await
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+
+        // default utf-8
+        let resp = client.post(&url).body("你好").send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("你好", resp.text().await?);
+
+        let resp = client
+            .post(&url)
+            .body(
+                encoding::label::encoding_from_whatwg_label("gbk")
+                    .unwrap()
+                    .encode("你好", EncoderTrap::Strict)
+                    .unwrap(),
+            )
+            .header(CONTENT_TYPE, "text/plain; charset=gbk")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("你好", resp.text().await?);
+
+        // invalid utf-8
+        let resp = client.post(&url).body(vec![255u8]).send().await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+
+        // unknown charset
+        let resp = client
+            .post(&url)
+            .body("hello")
+            .header(CONTENT_TYPE, "text/plain; charset=rust")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_value() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(move |mut ctx| async move {
+                ctx.write_value(serde_json::json!({ "id": 0, "name": "Hexilee" }))
+                    .await
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(APPLICATION_JSON_UTF_8, resp.headers()[CONTENT_TYPE]);
+        let user: User = resp.json().await?;
+        assert_eq!(
+            User {
+                id: 0,
+                name: "Hexilee".to_string()
+            },
+            user
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn render() -> Result<(), Box<dyn std::error::Error>> {
         // miss key
@@ -426,6 +1135,114 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn write_json_fails() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(move |mut ctx| async move {
+                let mut map = std::collections::HashMap::new();
+                map.insert((0, 0), 0);
+                let err = ctx.write_json(&map).await.unwrap_err();
+                assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, err.status_code);
+                assert!(!err.expose);
+                assert!(ctx.response_type().await.is_none());
+                ctx.write_json(&User {
+                    id: 0,
+                    name: "Hexilee".to_string(),
+                })
+                .await
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(APPLICATION_JSON_UTF_8, resp.headers()[CONTENT_TYPE]);
+        assert_eq!(r#"{"id":0,"name":"Hexilee"}"#, resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_json_array() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(move |mut ctx| async move {
+                let users = (0..1000).map(|id| User {
+                    id,
+                    name: "Hexilee".to_string(),
+                });
+                ctx.write_json_array(futures::stream::iter(users)).await
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(APPLICATION_JSON_UTF_8, resp.headers()[CONTENT_TYPE]);
+        let users: Vec<User> = resp.json().await?;
+        assert_eq!(1000, users.len());
+        assert_eq!(999, users[999].id);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_html() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(move |mut ctx| async move { ctx.write_html("<p>Hello</p>").await })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("text/html; charset=utf-8", resp.headers()[CONTENT_TYPE]);
+        assert_eq!("<p>Hello</p>", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_with_type() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(move |mut ctx| async move {
+                ctx.write_with_type(b"\x89PNG".as_ref(), mime::IMAGE_PNG)
+                    .await
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("image/png", resp.headers()[CONTENT_TYPE]);
+        assert_eq!(b"\x89PNG".as_ref(), resp.bytes().await?.as_ref());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn send_file() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(move |mut ctx| async move {
+                let path = ctx.uri().await.path().trim_start_matches('/').to_string();
+                ctx.send_file(path).await
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/assets/author.txt", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("text/plain", resp.headers()[CONTENT_TYPE]);
+        assert!(resp
+            .headers()
+            .get(http::header::CONTENT_DISPOSITION)
+            .is_none());
+        assert_eq!("Hexilee", resp.text().await?);
+
+        let resp = reqwest::get(&format!("http://{}/LICENSE", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(
+            mime::APPLICATION_OCTET_STREAM.as_ref(),
+            resp.headers()[CONTENT_TYPE]
+        );
+
+        let resp =
+            reqwest::get(&format!("http://{}/assets/not-exist.txt", addr)).await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        let resp = reqwest::get(&format!("http://{}/assets", addr)).await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn write_octet() -> Result<(), Box<dyn std::error::Error>> {
         // miss key
@@ -446,6 +1263,317 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "xml")]
+    #[tokio::test]
+    async fn xml() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(move |mut ctx| async move {
+                let user: User = ctx.read().await?;
+                assert_eq!("Hexilee", user.name);
+                ctx.write_xml(&user).await
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let body = "<User><id>0</id><name>Hexilee</name></User>";
+        for content_type in &["application/xml", "text/xml; charset=utf-8"] {
+            let resp = client
+                .get(&format!("http://{}", addr))
+                .body(body)
+                .header(CONTENT_TYPE, *content_type)
+                .send()
+                .await?;
+            assert_eq!(StatusCode::OK, resp.status());
+            assert_eq!(super::APPLICATION_XML_UTF_8, resp.headers()[CONTENT_TYPE]);
+            let user: User = serde_xml_rs::from_str(&resp.text().await?)?;
+            assert_eq!(0, user.id);
+            assert_eq!("Hexilee", user.name);
+        }
+
+        // xml; encoding
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .body(
+                encoding::label::encoding_from_whatwg_label("gbk")
+                    .unwrap()
+                    .encode(body, EncoderTrap::Strict)
+                    .unwrap(),
+            )
+            .header(CONTENT_TYPE, "application/xml; charset=gbk")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        // invalid body
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .body("<User>")
+            .header(CONTENT_TYPE, "application/xml")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        assert!(resp.text().await?.ends_with("invalid body"));
+        Ok(())
+    }
+
+    #[cfg(feature = "validate")]
+    #[tokio::test]
+    async fn validated() -> Result<(), Box<dyn std::error::Error>> {
+        use validator::Validate;
+        use validator_derive::Validate;
+
+        #[derive(Debug, Serialize, Deserialize, Validate)]
+        struct Account {
+            #[validate(length(min = 1, max = 8))]
+            name: String,
+            #[validate(email)]
+            email: String,
+        }
+
+        let (addr, server) = App::new(())
+            .end(move |mut ctx| async move {
+                let account: Account = if ctx.request_type().await.is_some() {
+                    ctx.read_form_validated().await?
+                } else {
+                    ctx.read_json_validated().await?
+                };
+                ctx.write_json(&account).await
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+
+        let valid = Account {
+            name: "Hexilee".to_string(),
+            email: "i@hexilee.me".to_string(),
+        };
+        let invalid = Account {
+            name: "".to_string(),
+            email: "hexilee".to_string(),
+        };
+
+        // valid json
+        let resp = client
+            .post(&format!("http://{}", addr))
+            .body(serde_json::to_vec(&valid)?)
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        // invalid json
+        let resp = client
+            .post(&format!("http://{}", addr))
+            .body(serde_json::to_vec(&invalid)?)
+            .send()
+            .await?;
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+        assert_eq!(APPLICATION_JSON_UTF_8, resp.headers()[CONTENT_TYPE]);
+        let body: serde_json::Value = resp.json().await?;
+        assert_eq!("validation failed", body["message"]);
+        assert_eq!("length", body["errors"]["name"][0]["code"]);
+        assert_eq!(1, body["errors"]["name"][0]["params"]["min"]);
+        assert_eq!("email", body["errors"]["email"][0]["code"]);
+
+        // valid form
+        let resp = client
+            .post(&format!("http://{}", addr))
+            .form(&valid)
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        // invalid form
+        let resp = client
+            .post(&format!("http://{}", addr))
+            .form(&invalid)
+            .send()
+            .await?;
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+        let body: serde_json::Value = resp.json().await?;
+        assert_eq!(2, body["errors"].as_object().unwrap().len());
+
+        // malformed body is still 400
+        let resp = client
+            .post(&format!("http://{}", addr))
+            .body("{")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        Ok(())
+    }
+
+    #[cfg(feature = "schema")]
+    #[tokio::test]
+    async fn json_schema() -> Result<(), Box<dyn std::error::Error>> {
+        use super::JSONSchema;
+        use serde_json::json;
+        use std::sync::Arc;
+
+        #[derive(Debug, Serialize, Deserialize)]
+        struct Account {
+            name: String,
+            age: u8,
+        }
+
+        let schema = Arc::new(
+            JSONSchema::compile(&json!({
+                "type": "object",
+                "properties": {
+                    "name": {"type": "string", "minLength": 1},
+                    "age": {"type": "integer"}
+                },
+                "required": ["name", "age"]
+            }))
+            .unwrap(),
+        );
+        let (addr, server) = App::new(())
+            .gate_fn(move |mut ctx, _next| {
+                let schema = schema.clone();
+                async move {
+                    let account: Account = ctx.read_json_schema(&schema).await?;
+                    ctx.write_json(&account).await
+                }
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+
+        let resp = client
+            .post(&url)
+            .json(&json!({"name": "Hexilee", "age": 20}))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("Hexilee", resp.json::<serde_json::Value>().await?["name"]);
+
+        let resp = client
+            .post(&url)
+            .json(&json!({"name": "", "age": "20"}))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+        assert_eq!(APPLICATION_JSON_UTF_8, resp.headers()[CONTENT_TYPE]);
+        let body: serde_json::Value = resp.json().await?;
+        assert_eq!("schema validation failed", body["message"]);
+        let mut paths: Vec<_> = body["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|err| err["path"].as_str().unwrap().to_string())
+            .collect();
+        paths.sort();
+        assert_eq!(vec!["/age", "/name"], paths);
+
+        // passes schema but cannot be deserialized
+        let resp = client
+            .post(&url)
+            .json(&json!({"name": "Hexilee", "age": 256}))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+
+        // malformed body is still 400
+        let resp = client.post(&url).body("{").send().await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn respond_negotiated() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(move |mut ctx| async move {
+                let user = User {
+                    id: 0,
+                    name: "Hexilee".to_string(),
+                };
+                ctx.respond_negotiated(&user).await
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+
+        // no Accept, default json
+        let resp = client.get(&format!("http://{}", addr)).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(APPLICATION_JSON_UTF_8, resp.headers()[CONTENT_TYPE]);
+        assert_eq!(r#"{"id":0,"name":"Hexilee"}"#, resp.text().await?);
+
+        // form is not in Format::ALL
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(
+                ACCEPT,
+                "application/json;q=0.8, application/x-www-form-urlencoded",
+            )
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(APPLICATION_JSON_UTF_8, resp.headers()[CONTENT_TYPE]);
+
+        // not acceptable
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(ACCEPT, "text/html")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NOT_ACCEPTABLE, resp.status());
+
+        // q-value
+        let (addr, server) = App::new(())
+            .end(move |mut ctx| async move {
+                let user = User {
+                    id: 0,
+                    name: "Hexilee".to_string(),
+                };
+                ctx.respond_negotiated_with(&user, &[Format::Json, Format::Form])
+                    .await
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(
+                ACCEPT,
+                "application/json;q=0.8, application/x-www-form-urlencoded",
+            )
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(
+            "application/x-www-form-urlencoded",
+            resp.headers()[CONTENT_TYPE]
+        );
+        assert_eq!("id=0&name=Hexilee", resp.text().await?);
+
+        // configured formats
+        let (addr, server) = App::new(())
+            .end(move |mut ctx| async move {
+                ctx.respond_negotiated_with(&vec![1, 2], &[Format::Json])
+                    .await
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(ACCEPT, "application/x-www-form-urlencoded")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NOT_ACCEPTABLE, resp.status());
+        assert_eq!(
+            "none of accepted types is supported, supported types: application/json",
+            resp.text().await?
+        );
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(ACCEPT, "*/*")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("[1,2]", resp.text().await?);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn response_type() -> Result<(), Box<dyn std::error::Error>> {
         // miss key
@@ -0,0 +1,172 @@
+use super::mime_ext::MimeExt;
+use super::PowerBody;
+use crate::core::{
+    async_trait, throw, Context, Middleware, Next, Result, State, StatusCode,
+};
+use http::Method;
+use mime::Mime;
+use std::sync::Arc;
+
+/// A middleware to reject requests of unexpected "Content-Type", returned by `accepts`.
+pub struct Accepts {
+    mime: Mime,
+}
+
+/// Reject requests whose "Content-Type" doesn't match mime with 415 UNSUPPORTED MEDIA TYPE,
+/// the downstream middleware will not be called.
+///
+/// Parameters like charset are ignored, and mime can be a range like "application/*".
+/// GET and HEAD requests are always passed as they have no body.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::body::{accepts, PowerBody};
+/// use roa::core::App;
+/// use roa::core::header::CONTENT_TYPE;
+/// use async_std::task::spawn;
+/// use http::StatusCode;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let (addr, server) = App::new(())
+///         .gate(accepts(mime::APPLICATION_JSON))
+///         .end(|mut ctx| async move {
+///             let _data: Vec<u8> = ctx.read_json().await?;
+///             Ok(())
+///         })
+///         .run_local()?;
+///     spawn(server);
+///     let client = reqwest::Client::new();
+///     let resp = client
+///         .post(&format!("http://{}", addr))
+///         .header(CONTENT_TYPE, "text/plain")
+///         .body("Hello, World!")
+///         .send()
+///         .await?;
+///     assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, resp.status());
+///
+///     let resp = client
+///         .post(&format!("http://{}", addr))
+///         .json(&vec![0u8])
+///         .send()
+///         .await?;
+///     assert_eq!(StatusCode::OK, resp.status());
+///     Ok(())
+/// }
+/// ```
+pub fn accepts(mime: Mime) -> Accepts {
+    Accepts { mime }
+}
+
+impl Accepts {
+    fn matches(&self, content_type: &Mime) -> bool {
+        if self.mime.type_() == mime::STAR {
+            true
+        } else if self.mime.subtype() == mime::STAR {
+            self.mime.type_() == content_type.type_()
+        } else {
+            self.mime.pure_type() == content_type.pure_type()
+        }
+    }
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for Accepts {
+    async fn handle(self: Arc<Self>, ctx: Context<S>, next: Next) -> Result {
+        let method = ctx.method().await;
+        if method == Method::GET || method == Method::HEAD {
+            return next().await;
+        }
+        match ctx.request_type().await {
+            None => throw!(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("Content-Type is required, expected {}", self.mime)
+            ),
+            Some(content_type) => {
+                let content_type = content_type?;
+                if !self.matches(&content_type) {
+                    throw!(
+                        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                        format!(
+                            "Content-Type {} is not supported, expected {}",
+                            content_type, self.mime
+                        )
+                    )
+                }
+            }
+        }
+        next().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::accepts;
+    use crate::core::App;
+    use async_std::task::spawn;
+    use http::header::CONTENT_TYPE;
+    use http::StatusCode;
+
+    #[tokio::test]
+    async fn accepts_json() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(accepts(mime::APPLICATION_JSON))
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+
+        let resp = client.get(&url).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let resp = client.head(&url).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        let resp = client
+            .post(&url)
+            .header(CONTENT_TYPE, "application/json; charset=utf-8")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        let resp = client.post(&url).send().await?;
+        assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, resp.status());
+        assert_eq!(
+            "Content-Type is required, expected application/json",
+            resp.text().await?
+        );
+
+        let resp = client
+            .put(&url)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, resp.status());
+        assert_eq!(
+            "Content-Type application/x-www-form-urlencoded is not supported, expected application/json",
+            resp.text().await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn accepts_range() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(()).gate(accepts(mime::TEXT_STAR)).run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+        let resp = client
+            .post(&url)
+            .header(CONTENT_TYPE, "text/html")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let resp = client
+            .post(&url)
+            .header(CONTENT_TYPE, "application/json")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, resp.status());
+        Ok(())
+    }
+}
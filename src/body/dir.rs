@@ -0,0 +1,353 @@
+use super::embed::asset_path;
+use super::file::open;
+use crate::core::{
+    async_trait, throw, Context, Middleware, Next, Result, State, StatusCode,
+};
+use crate::header::FriendlyHeaders;
+use bytesize::ByteSize;
+use futures::StreamExt;
+use http::header::CONTENT_TYPE;
+use percent_encoding::{
+    percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC,
+};
+use std::fmt::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// Characters to be percent-encoded in a path segment of listing links.
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// An endpoint to serve files in a directory, returned by `serve_dir`.
+pub struct ServeDir {
+    root: PathBuf,
+    index: Option<String>,
+    listing: bool,
+}
+
+/// Serve files in a directory as an endpoint, the downstream middleware is never called.
+///
+/// The file is looked up by the router variable `path`,
+/// or by the request path without leading '/' if the variable is missing.
+/// "Content-Type" is set by extension name of the file,
+/// and 404 NOT FOUND is thrown if the file is missing or the path escapes the root.
+///
+/// Requests to a directory are served with its index file if `ServeDir::index` is set.
+/// If there is no index file, an HTML listing of the directory is rendered
+/// when `ServeDir::directory_listing` is enabled, otherwise 404 NOT FOUND is thrown.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::body::serve_dir;
+/// use roa::router::Router;
+/// use roa::core::App;
+/// use roa::core::header::CONTENT_TYPE;
+/// use async_std::task::spawn;
+/// use http::{Method, StatusCode};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut router = Router::<()>::new();
+///     let assets = serve_dir("assets").index("index.html");
+///     router.end(&[Method::GET], "/static/*{path}", assets);
+///     let (addr, server) = App::new(()).gate(router.routes("/")?).run_local()?;
+///     spawn(server);
+///     let resp = reqwest::get(&format!("http://{}/static/docs/", addr)).await?;
+///     assert_eq!(StatusCode::OK, resp.status());
+///     assert_eq!("text/html", resp.headers()[CONTENT_TYPE]);
+///     Ok(())
+/// }
+/// ```
+pub fn serve_dir(root: impl Into<PathBuf>) -> ServeDir {
+    ServeDir {
+        root: root.into(),
+        index: None,
+        listing: false,
+    }
+}
+
+impl ServeDir {
+    /// Serve file `name` in a directory for requests to the directory.
+    pub fn index(mut self, name: impl ToString) -> Self {
+        self.index = Some(name.to_string());
+        self
+    }
+
+    /// Render an HTML listing of entries (names, sizes and mtimes)
+    /// for requests to a directory without index file. Disabled by default.
+    ///
+    /// Enable it with care, as it exposes names of all files under the root.
+    pub fn directory_listing(mut self, enabled: bool) -> Self {
+        self.listing = enabled;
+        self
+    }
+
+    /// Resolve a request path under root, None if it escapes the root.
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let path = percent_decode_str(path).decode_utf8().ok()?;
+        let mut resolved = self.root.clone();
+        for component in Path::new(path.as_ref()).components() {
+            match component {
+                Component::Normal(segment) => resolved.push(segment),
+                Component::CurDir => (),
+                _ => return None,
+            }
+        }
+        Some(resolved)
+    }
+}
+
+async fn is_dir(path: &Path) -> bool {
+    matches!(async_std::fs::metadata(path).await, Ok(meta) if meta.is_dir())
+}
+
+async fn is_file(path: &Path) -> bool {
+    matches!(async_std::fs::metadata(path).await, Ok(meta) if meta.is_file())
+}
+
+/// Escape text to be embedded in HTML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Render an HTML listing of directory `path`, whose url path is `base`.
+async fn listing(path: &Path, base: &str, is_root: bool) -> Result<String> {
+    let mut entries = Vec::new();
+    let mut dir = async_std::fs::read_dir(path).await?;
+    while let Some(entry) = dir.next().await {
+        let entry = entry?;
+        let meta = entry.metadata().await?;
+        let mut name = entry.file_name().to_string_lossy().into_owned();
+        let size = if meta.is_dir() {
+            name.push('/');
+            "-".to_string()
+        } else {
+            ByteSize(meta.len()).to_string()
+        };
+        let modified = meta
+            .modified()
+            .map(httpdate::fmt_http_date)
+            .unwrap_or_default();
+        entries.push((name, size, modified));
+    }
+    entries.sort();
+
+    let decoded = percent_decode_str(base).decode_utf8_lossy();
+    let title = escape(&format!("Index of {}/", decoded));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{0}</title></head>\n\
+         <body>\n<h1>{0}</h1>\n<table>\n\
+         <tr><th>Name</th><th>Size</th><th>Modified</th></tr>\n",
+        title
+    );
+    if !is_root {
+        let parent = base.rsplit_once('/').map_or("", |(parent, _)| parent);
+        let _ = writeln!(
+            html,
+            "<tr><td><a href=\"{}/\">../</a></td><td>-</td><td></td></tr>",
+            escape(parent)
+        );
+    }
+    for (name, size, modified) in entries {
+        let segment = name.trim_end_matches('/');
+        let suffix = &name[segment.len()..];
+        let _ = writeln!(
+            html,
+            "<tr><td><a href=\"{}/{}{}\">{}</a></td><td>{}</td><td>{}</td></tr>",
+            escape(base),
+            utf8_percent_encode(segment, SEGMENT),
+            suffix,
+            escape(&name),
+            size,
+            modified
+        );
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    Ok(html)
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for ServeDir {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, _next: Next) -> Result {
+        let relative = asset_path(&ctx).await;
+        let mut path = match self.resolve(&relative) {
+            Some(path) => path,
+            None => throw!(StatusCode::NOT_FOUND),
+        };
+        if is_dir(&path).await {
+            let index = self.index.as_ref().map(|index| path.join(index));
+            match index {
+                Some(index) if is_file(&index).await => path = index,
+                _ if self.listing => {
+                    let base = ctx.uri().await.path().trim_end_matches('/').to_string();
+                    let html =
+                        listing(&path, &base, relative.trim_matches('/').is_empty())
+                            .await?;
+                    ctx.resp_mut().await.write_str(html);
+                    ctx.resp_mut()
+                        .await
+                        .insert(CONTENT_TYPE, "text/html; charset=utf-8")?;
+                    return Ok(());
+                }
+                _ => throw!(StatusCode::NOT_FOUND),
+            }
+        }
+        let file = open(&path).await?;
+        ctx.resp_mut().await.write(file);
+        ctx.resp_mut().await.insert(
+            CONTENT_TYPE,
+            mime_guess::from_path(&path).first_or_octet_stream(),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::serve_dir;
+    use crate::core::App;
+    use async_std::task::spawn;
+    use futures::{AsyncReadExt, AsyncWriteExt};
+    use http::header::CONTENT_TYPE;
+    use http::StatusCode;
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(()).gate(serve_dir("assets")).run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/author.txt", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("text/plain", resp.headers()[CONTENT_TYPE]);
+        assert_eq!("Hexilee", resp.text().await?);
+
+        let resp = reqwest::get(&format!("http://{}/css/table.css", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("text/css", resp.headers()[CONTENT_TYPE]);
+
+        for path in &["/not-exist.txt", "/docs/"] {
+            let resp = reqwest::get(&format!("http://{}{}", addr, path)).await?;
+            assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        }
+
+        // reqwest normalizes dot segments, so send them by a raw stream.
+        for path in &[
+            "/../Cargo.toml",
+            "/%2E%2E/Cargo.toml",
+            "/css/%2e%2e/../Cargo.toml",
+        ] {
+            let mut stream = async_std::net::TcpStream::connect(addr).await?;
+            let request = format!(
+                "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                path
+            );
+            stream.write_all(request.as_bytes()).await?;
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await?;
+            assert!(
+                response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+                "{} should be rejected: {}",
+                path,
+                response
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn directory_with_index() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(serve_dir("assets").index("index.html"))
+            .run_local()?;
+        spawn(server);
+        for path in &["/docs/", "/docs"] {
+            let resp = reqwest::get(&format!("http://{}{}", addr, path)).await?;
+            assert_eq!(StatusCode::OK, resp.status());
+            assert_eq!("text/html", resp.headers()[CONTENT_TYPE]);
+            assert_eq!("<html><body>docs</body></html>", resp.text().await?);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn directory_without_index() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(serve_dir("assets").index("index.html"))
+            .run_local()?;
+        spawn(server);
+        for path in &["/css/", "/"] {
+            let resp = reqwest::get(&format!("http://{}{}", addr, path)).await?;
+            assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn directory_listing() -> Result<(), Box<dyn std::error::Error>> {
+        let root =
+            std::env::temp_dir().join(format!("roa-listing-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("sub dir"))?;
+        std::fs::write(root.join("a b&c.txt"), "hello")?;
+        std::fs::write(root.join("sub dir").join("<x>.txt"), "")?;
+        let (addr, server) = App::new(())
+            .gate(serve_dir(&root).index("index.html").directory_listing(true))
+            .run_local()?;
+        spawn(server);
+
+        let resp = reqwest::get(&format!("http://{}/", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("text/html; charset=utf-8", resp.headers()[CONTENT_TYPE]);
+        let html = resp.text().await?;
+        assert!(html.contains("<title>Index of /</title>"));
+        assert!(html
+            .contains(r#"<a href="/a%20b%26c.txt">a b&amp;c.txt</a></td><td>5 B</td>"#));
+        assert!(html.contains(r#"<a href="/sub%20dir/">sub dir/</a></td><td>-</td>"#));
+        assert!(html.contains(" GMT</td></tr>"));
+        assert!(!html.contains("../"));
+
+        let resp = reqwest::get(&format!("http://{}/sub%20dir", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let html = resp.text().await?;
+        assert!(html.contains("<title>Index of /sub dir/</title>"));
+        assert!(html.contains(r#"<a href="/">../</a>"#));
+        assert!(html.contains(r#"<a href="/sub%20dir/%3Cx%3E.txt">&lt;x&gt;.txt</a>"#));
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn resolve() {
+        let dir = serve_dir("assets");
+        assert_eq!(
+            Some(Path::new("assets/css/table.css").to_path_buf()),
+            dir.resolve("./css/table.css")
+        );
+        assert_eq!(
+            Some(Path::new("assets/a b").to_path_buf()),
+            dir.resolve("a%20b")
+        );
+        for path in &[
+            "../Cargo.toml",
+            "css/../../Cargo.toml",
+            "%2E%2E/Cargo.toml",
+            "/etc",
+        ] {
+            assert!(dir.resolve(path).is_none(), "{} should be rejected", path);
+        }
+    }
+}
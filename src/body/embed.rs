@@ -0,0 +1,210 @@
+use crate::core::{
+    async_trait, throw, Context, Middleware, Next, Result, State, StatusCode,
+};
+use crate::header::FriendlyHeaders;
+use crate::precondition::any_matches;
+use http::header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Static assets compiled into the binary, pairs of path and content.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::body::Assets;
+///
+/// const ASSETS: Assets = &[
+///     ("author.txt", include_bytes!("../../assets/author.txt")),
+///     ("welcome.html", include_bytes!("../../assets/welcome.html")),
+/// ];
+/// ```
+pub type Assets = &'static [(&'static str, &'static [u8])];
+
+/// An endpoint to serve embedded assets, returned by `serve_embedded`.
+pub struct ServeEmbedded {
+    assets: HashMap<&'static str, (&'static [u8], String)>,
+}
+
+/// Serve static assets compiled into the binary as an endpoint,
+/// the downstream middleware will never be called.
+///
+/// The asset is looked up by the router variable `path`,
+/// or by the request path without leading '/' if the variable is missing.
+/// "Content-Type" is set by extension name of the asset path, and a strong "ETag" is set by its content.
+/// Respond 304 NOT MODIFIED if "If-None-Match" matches, or throw 404 NOT FOUND if the asset is missing.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::body::{serve_embedded, Assets};
+/// use roa::router::Router;
+/// use roa::core::App;
+/// use roa::core::header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+/// use async_std::task::spawn;
+/// use http::{Method, StatusCode};
+///
+/// const ASSETS: Assets = &[
+///     ("author.txt", include_bytes!("../../assets/author.txt")),
+///     ("css/style.css", b"body { margin: 0 }"),
+/// ];
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut router = Router::<()>::new();
+///     router.end(&[Method::GET], "/static/*{path}", serve_embedded(ASSETS));
+///     let (addr, server) = App::new(()).gate(router.routes("/")?).run_local()?;
+///     spawn(server);
+///     let resp = reqwest::get(&format!("http://{}/static/css/style.css", addr)).await?;
+///     assert_eq!(StatusCode::OK, resp.status());
+///     assert_eq!("text/css", resp.headers()[CONTENT_TYPE]);
+///     let etag = resp.headers()[ETAG].clone();
+///
+///     let resp = reqwest::Client::new()
+///         .get(&format!("http://{}/static/css/style.css", addr))
+///         .header(IF_NONE_MATCH, etag)
+///         .send()
+///         .await?;
+///     assert_eq!(StatusCode::NOT_MODIFIED, resp.status());
+///     Ok(())
+/// }
+/// ```
+pub fn serve_embedded(assets: Assets) -> ServeEmbedded {
+    ServeEmbedded {
+        assets: assets
+            .iter()
+            .map(|(path, content)| {
+                (path.trim_start_matches('/'), (*content, etag(content)))
+            })
+            .collect(),
+    }
+}
+
+fn etag(content: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    format!(r#""{:016x}""#, hasher.finish())
+}
+
+/// Get the requested path by router variable `path`, or by the request path.
+pub(crate) async fn asset_path<S: State>(ctx: &Context<S>) -> String {
+    #[cfg(feature = "router")]
+    {
+        use crate::router::RouterParam;
+        if let Some(path) = ctx.param("path").await {
+            return path.into_value();
+        }
+    }
+    ctx.uri().await.path().trim_start_matches('/').to_string()
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for ServeEmbedded {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, _next: Next) -> Result {
+        let path = asset_path(&ctx).await;
+        let (content, etag) = match self.assets.get(path.as_str()) {
+            Some(asset) => asset,
+            None => throw!(StatusCode::NOT_FOUND),
+        };
+        ctx.resp_mut().await.insert(ETAG, etag.as_str())?;
+        if let Some(Ok(if_none_match)) = ctx.header(IF_NONE_MATCH).await {
+            if any_matches(&if_none_match, Some(etag), true) {
+                ctx.resp_mut().await.status = StatusCode::NOT_MODIFIED;
+                return Ok(());
+            }
+        }
+        ctx.resp_mut().await.write_bytes(*content);
+        ctx.resp_mut().await.insert(
+            CONTENT_TYPE,
+            mime_guess::from_path(&path).first_or_octet_stream(),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{serve_embedded, Assets};
+    use crate::core::App;
+    use async_std::task::spawn;
+    use http::StatusCode;
+
+    const ASSETS: Assets = &[
+        ("author.txt", include_bytes!("../../assets/author.txt")),
+        ("/index.html", b"<html></html>"),
+        ("js/app.js", b"console.log(1)"),
+    ];
+
+    #[cfg(feature = "router")]
+    #[tokio::test]
+    async fn embedded() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::router::Router;
+        use http::header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+        use http::Method;
+
+        let mut router = Router::<()>::new();
+        router.end(&[Method::GET], "/static/*{path}", serve_embedded(ASSETS));
+        let (addr, server) = App::new(()).gate(router.routes("/")?).run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+
+        for (path, content_type, body) in &[
+            ("author.txt", "text/plain", "Hexilee"),
+            ("index.html", "text/html", "<html></html>"),
+            ("js/app.js", "text/javascript", "console.log(1)"),
+        ] {
+            let resp = client
+                .get(&format!("http://{}/static/{}", addr, path))
+                .send()
+                .await?;
+            assert_eq!(StatusCode::OK, resp.status());
+            assert_eq!(*content_type, resp.headers()[CONTENT_TYPE]);
+            let etag = resp.headers()[ETAG].clone();
+            assert_eq!(*body, resp.text().await?);
+
+            // conditional
+            let resp = client
+                .get(&format!("http://{}/static/{}", addr, path))
+                .header(IF_NONE_MATCH, etag.clone())
+                .send()
+                .await?;
+            assert_eq!(StatusCode::NOT_MODIFIED, resp.status());
+            assert_eq!(etag, resp.headers()[ETAG]);
+            assert_eq!("", resp.text().await?);
+
+            let resp = client
+                .get(&format!("http://{}/static/{}", addr, path))
+                .header(IF_NONE_MATCH, r#""other""#)
+                .send()
+                .await?;
+            assert_eq!(StatusCode::OK, resp.status());
+
+            // a comma in a quoted tag does not split it
+            let resp = client
+                .get(&format!("http://{}/static/{}", addr, path))
+                .header(IF_NONE_MATCH, r#""a,*,b""#)
+                .send()
+                .await?;
+            assert_eq!(StatusCode::OK, resp.status());
+        }
+
+        let resp = client
+            .get(&format!("http://{}/static/not-exist.txt", addr))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn without_router() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(()).gate(serve_embedded(ASSETS)).run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/js/app.js", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("console.log(1)", resp.text().await?);
+        Ok(())
+    }
+}
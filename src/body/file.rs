@@ -0,0 +1,167 @@
+use crate::core::{
+    async_trait, throw, Bytes, Context, Error, Middleware, Next, Result, State,
+    StatusCode,
+};
+use crate::header::FriendlyHeaders;
+use async_std::fs::File;
+use async_std::io::ReadExt;
+use async_std::sync::RwLock;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// An endpoint to serve a single file, returned by `serve_file`.
+pub struct ServeFile {
+    path: PathBuf,
+    cache: Option<RwLock<Option<Bytes>>>,
+}
+
+/// Serve a single file as an endpoint, the downstream middleware will never be called.
+///
+/// "Content-Type" is set by extension name of the file,
+/// and 404 NOT FOUND is thrown if the file is missing at request time.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::body::serve_file;
+/// use roa::router::Router;
+/// use roa::core::App;
+/// use roa::core::header::CONTENT_TYPE;
+/// use async_std::task::spawn;
+/// use http::{Method, StatusCode};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut router = Router::<()>::new();
+///     router.end(&[Method::GET], "/welcome", serve_file("assets/welcome.html").cache());
+///     let (addr, server) = App::new(()).gate(router.routes("/")?).run_local()?;
+///     spawn(server);
+///     let resp = reqwest::get(&format!("http://{}/welcome", addr)).await?;
+///     assert_eq!(StatusCode::OK, resp.status());
+///     assert_eq!("text/html", resp.headers()[CONTENT_TYPE]);
+///     Ok(())
+/// }
+/// ```
+pub fn serve_file(path: impl Into<PathBuf>) -> ServeFile {
+    ServeFile {
+        path: path.into(),
+        cache: None,
+    }
+}
+
+impl ServeFile {
+    /// Read the file only once at the first request and keep its content in memory.
+    pub fn cache(mut self) -> Self {
+        self.cache = Some(RwLock::new(None));
+        self
+    }
+
+    async fn cached(&self, cache: &RwLock<Option<Bytes>>) -> Result<Bytes> {
+        if let Some(ref content) = *cache.read().await {
+            return Ok(content.clone());
+        }
+        let mut cache = cache.write().await;
+        match *cache {
+            Some(ref content) => Ok(content.clone()),
+            None => {
+                let mut content = Vec::new();
+                open(&self.path).await?.read_to_end(&mut content).await?;
+                let content = Bytes::from(content);
+                *cache = Some(content.clone());
+                Ok(content)
+            }
+        }
+    }
+}
+
+fn not_found(err: std::io::Error) -> Error {
+    if err.kind() == ErrorKind::NotFound {
+        Error::new(StatusCode::NOT_FOUND, "", true)
+    } else {
+        err.into()
+    }
+}
+
+/// Open a regular file, throw 404 NOT FOUND if it's missing or not a file.
+pub(crate) async fn open(path: impl AsRef<Path>) -> Result<File> {
+    let file = File::open(path.as_ref()).await.map_err(not_found)?;
+    if !file.metadata().await?.is_file() {
+        throw!(StatusCode::NOT_FOUND)
+    }
+    Ok(file)
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for ServeFile {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, _next: Next) -> Result {
+        match self.cache {
+            Some(ref cache) => {
+                let content = self.cached(cache).await?;
+                ctx.resp_mut().await.write_shared(content);
+            }
+            None => {
+                let file = open(&self.path).await?;
+                ctx.resp_mut().await.write(file);
+            }
+        }
+        ctx.resp_mut().await.insert(
+            http::header::CONTENT_TYPE,
+            mime_guess::from_path(&self.path).first_or_octet_stream(),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::serve_file;
+    use crate::core::App;
+    use async_std::task::spawn;
+    use http::header::CONTENT_TYPE;
+    use http::StatusCode;
+
+    #[tokio::test]
+    async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(serve_file("assets/author.txt"))
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("text/plain", resp.headers()[CONTENT_TYPE]);
+        assert_eq!("Hexilee", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn serve_cached() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(serve_file("assets/author.txt").cache())
+            .run_local()?;
+        spawn(server);
+        for _ in 0..2 {
+            let resp = reqwest::get(&format!("http://{}", addr)).await?;
+            assert_eq!(StatusCode::OK, resp.status());
+            assert_eq!("text/plain", resp.headers()[CONTENT_TYPE]);
+            assert_eq!("Hexilee", resp.text().await?);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn not_found() -> Result<(), Box<dyn std::error::Error>> {
+        for endpoint in [
+            serve_file("assets/not-exist.txt"),
+            serve_file("assets/not-exist.txt").cache(),
+            serve_file("assets"),
+            serve_file("assets").cache(),
+        ] {
+            let (addr, server) = App::new(()).gate(endpoint).run_local()?;
+            spawn(server);
+            let resp = reqwest::get(&format!("http://{}", addr)).await?;
+            assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        }
+        Ok(())
+    }
+}
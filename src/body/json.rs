@@ -1,6 +1,11 @@
 use crate::core::{Error, Result, StatusCode};
+use futures::io::{AsyncBufRead, AsyncRead};
+use futures::stream::{Stream, StreamExt};
+use futures::task::{Context, Poll};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::io;
+use std::pin::Pin;
 
 pub fn from_bytes<B: DeserializeOwned>(data: &[u8]) -> Result<B> {
     serde_json::from_slice(data).map_err(|err| {
@@ -22,8 +27,22 @@ pub fn from_str<B: DeserializeOwned>(data: &str) -> Result<B> {
     })
 }
 
+#[cfg(feature = "schema")]
+pub fn from_value<B: DeserializeOwned>(value: serde_json::Value) -> Result<B> {
+    serde_json::from_value(value).map_err(|err| {
+        Error::new(
+            StatusCode::BAD_REQUEST,
+            format!("{}\ninvalid body", err),
+            true,
+        )
+    })
+}
+
+/// Serialization failure is a fault of server,
+/// it's logged and never exposed to client.
 pub fn to_bytes<B: Serialize>(object: &B) -> Result<Vec<u8>> {
     serde_json::to_vec(object).map_err(|err| {
+        log::error!("object cannot be serialized to json: {}", err);
         Error::new(
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("{}\nobject cannot be serialized to json", err),
@@ -32,6 +51,102 @@ pub fn to_bytes<B: Serialize>(object: &B) -> Result<Vec<u8>> {
     })
 }
 
+enum ArrayState {
+    Start,
+    Items,
+    Done,
+}
+
+/// A reader serializing items of a stream into a json array lazily.
+pub struct JsonArray<St> {
+    stream: St,
+    state: ArrayState,
+    first: bool,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<St> JsonArray<St> {
+    pub fn new(stream: St) -> Self {
+        Self {
+            stream,
+            state: ArrayState::Start,
+            first: true,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl<St> AsyncBufRead for JsonArray<St>
+where
+    St: Stream + Unpin,
+    St::Item: Serialize,
+{
+    fn poll_fill_buf(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        loop {
+            if this.pos < this.buf.len() {
+                return Poll::Ready(Ok(&this.buf[this.pos..]));
+            }
+            this.buf.clear();
+            this.pos = 0;
+            match this.state {
+                ArrayState::Start => {
+                    this.buf.push(b'[');
+                    this.state = ArrayState::Items;
+                }
+                ArrayState::Items => match futures::ready!(this
+                    .stream
+                    .poll_next_unpin(cx))
+                {
+                    Some(item) => {
+                        if !this.first {
+                            this.buf.push(b',');
+                        }
+                        this.first = false;
+                        serde_json::to_writer(&mut this.buf, &item).map_err(|err| {
+                            log::error!("item cannot be serialized to json: {}", err);
+                            io::Error::new(io::ErrorKind::InvalidData, err)
+                        })?;
+                    }
+                    None => {
+                        this.buf.push(b']');
+                        this.state = ArrayState::Done;
+                    }
+                },
+                ArrayState::Done => return Poll::Ready(Ok(&[])),
+            }
+        }
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        this.pos = std::cmp::min(this.pos + amt, this.buf.len());
+    }
+}
+
+impl<St> AsyncRead for JsonArray<St>
+where
+    St: Stream + Unpin,
+    St::Item: Serialize,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let data = futures::ready!(self.as_mut().poll_fill_buf(cx))?;
+        let nums = std::cmp::min(data.len(), buf.len());
+        buf[..nums].copy_from_slice(&data[..nums]);
+        self.consume(nums);
+        Poll::Ready(Ok(nums))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -46,6 +161,38 @@ mod tests {
         assert!(status.message.ends_with("invalid body"));
     }
 
+    #[test]
+    fn to_bytes_fails() {
+        use std::collections::HashMap;
+        let mut map = HashMap::new();
+        map.insert(vec![0u8], 0);
+        let ret = to_bytes(&map);
+        assert!(ret.is_err());
+        let status = ret.unwrap_err();
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, status.status_code);
+        assert!(!status.expose);
+        assert!(status
+            .message
+            .ends_with("object cannot be serialized to json"));
+    }
+
+    #[async_std::test]
+    async fn json_array() -> std::io::Result<()> {
+        use futures::io::AsyncReadExt;
+        let mut data = String::new();
+        JsonArray::new(futures::stream::iter(Vec::<i32>::new()))
+            .read_to_string(&mut data)
+            .await?;
+        assert_eq!("[]", data);
+
+        data.clear();
+        JsonArray::new(futures::stream::iter(vec!["a", "b", "c"]))
+            .read_to_string(&mut data)
+            .await?;
+        assert_eq!(r#"["a","b","c"]"#, data);
+        Ok(())
+    }
+
     #[test]
     fn from_bytes_fails() {
         let ret = from_bytes::<i32>(b"");
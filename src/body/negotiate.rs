@@ -0,0 +1,341 @@
+use super::{json, urlencoded};
+use crate::core::header::ACCEPT;
+use crate::core::{
+    async_trait, throw, Context, Middleware, Next, Result, State, StatusCode,
+};
+use mime::Mime;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Formats supported by `PowerBody::respond_negotiated`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Format {
+    /// "application/json; charset=utf-8"
+    Json,
+
+    /// "application/x-www-form-urlencoded"
+    Form,
+
+    /// "application/xml; charset=utf-8"
+    #[cfg(feature = "xml")]
+    Xml,
+}
+
+impl Format {
+    /// All formats able to serialize any object, in order of server preference.
+    ///
+    /// `Format::Form` is left out as it only serializes flat structs,
+    /// pass it to `PowerBody::respond_negotiated_with` explicitly.
+    #[cfg(not(feature = "xml"))]
+    pub const ALL: &'static [Format] = &[Format::Json];
+
+    /// All formats able to serialize any object, in order of server preference.
+    ///
+    /// `Format::Form` is left out as it only serializes flat structs,
+    /// pass it to `PowerBody::respond_negotiated_with` explicitly.
+    #[cfg(feature = "xml")]
+    pub const ALL: &'static [Format] = &[Format::Json, Format::Xml];
+
+    /// The mime type of this format, without parameters.
+    pub fn mime(&self) -> Mime {
+        match self {
+            Format::Json => mime::APPLICATION_JSON,
+            Format::Form => mime::APPLICATION_WWW_FORM_URLENCODED,
+            #[cfg(feature = "xml")]
+            Format::Xml => "application/xml".parse().unwrap(),
+        }
+    }
+
+    /// The value of "Content-Type" of responses in this format.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json => super::APPLICATION_JSON_UTF_8,
+            Format::Form => mime::APPLICATION_WWW_FORM_URLENCODED.as_ref(),
+            #[cfg(feature = "xml")]
+            Format::Xml => super::APPLICATION_XML_UTF_8,
+        }
+    }
+
+    /// Serialize object in this format.
+    pub(crate) fn to_bytes<B: Serialize>(self, data: &B) -> Result<Vec<u8>> {
+        match self {
+            Format::Json => json::to_bytes(data),
+            Format::Form => urlencoded::to_bytes(data),
+            #[cfg(feature = "xml")]
+            Format::Xml => super::xml::to_bytes(data),
+        }
+    }
+}
+
+/// Parse q-value of a media range, 1.0 by default.
+fn quality(range: &Mime) -> f32 {
+    range
+        .get_param("q")
+        .and_then(|q| q.as_str().parse().ok())
+        .unwrap_or(1.0)
+}
+
+/// Get quality of a mime type by the most specific matched media range,
+/// return `None` if no range matches.
+fn match_quality(ranges: &[Mime], mime: &Mime) -> Option<f32> {
+    ranges
+        .iter()
+        .filter_map(|range| {
+            let specificity = if range.type_() == mime::STAR {
+                1
+            } else if range.type_() != mime.type_() {
+                return None;
+            } else if range.subtype() == mime::STAR {
+                2
+            } else if range.subtype() != mime.subtype() {
+                return None;
+            } else {
+                3
+            };
+            Some((specificity, quality(range)))
+        })
+        .max_by_key(|(specificity, _)| *specificity)
+        .map(|(_, q)| q)
+}
+
+/// Parse media ranges of "Accept" header value, invalid ones are ignored.
+fn media_ranges(accept: Option<&str>) -> Vec<Mime> {
+    accept
+        .unwrap_or("")
+        .split(',')
+        .filter_map(|range| range.trim().parse().ok())
+        .collect()
+}
+
+/// Select the best format by "Accept" header value.
+///
+/// The first format is selected if accept is absent or empty.
+/// Formats with the same quality are preferred in order.
+pub fn negotiate(accept: Option<&str>, formats: &[Format]) -> Result<Format> {
+    let ranges = media_ranges(accept);
+    if ranges.is_empty() {
+        if let Some(format) = formats.first() {
+            return Ok(*format);
+        }
+    }
+
+    let mut best: Option<(Format, f32)> = None;
+    for format in formats {
+        if let Some(q) = match_quality(&ranges, &format.mime()) {
+            if q > 0.0 && best.map(|(_, best_q)| q > best_q).unwrap_or(true) {
+                best = Some((*format, q));
+            }
+        }
+    }
+
+    match best {
+        Some((format, _)) => Ok(format),
+        None => throw!(
+            StatusCode::NOT_ACCEPTABLE,
+            format!(
+                "none of accepted types is supported, supported types: {}",
+                formats
+                    .iter()
+                    .map(|format| format.mime().to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )
+        ),
+    }
+}
+
+/// A middleware to reject requests accepting none of producible types,
+/// returned by `enforce_accept`.
+pub struct EnforceAccept {
+    producible: Vec<Mime>,
+}
+
+/// Reject requests whose "Accept" header matches none of producible types
+/// with 406 NOT ACCEPTABLE, the downstream middleware will not be called.
+///
+/// Media ranges like "*/*" and "application/*" are matched, ranges with "q=0" are refused.
+/// Requests without "Accept" header accept any type.
+///
+/// ### Panics
+///
+/// Panics if any of producible types is not a valid mime type.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::body::{enforce_accept, PowerBody};
+/// use roa::core::App;
+/// use roa::core::header::ACCEPT;
+/// use async_std::task::spawn;
+/// use http::StatusCode;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let (addr, server) = App::new(())
+///         .gate(enforce_accept(&["application/json"]))
+///         .end(|mut ctx| async move { ctx.write_json(&"Hello, World!").await })
+///         .run_local()?;
+///     spawn(server);
+///     let resp = reqwest::Client::new()
+///         .get(&format!("http://{}", addr))
+///         .header(ACCEPT, "application/xml")
+///         .send()
+///         .await?;
+///     assert_eq!(StatusCode::NOT_ACCEPTABLE, resp.status());
+///     Ok(())
+/// }
+/// ```
+pub fn enforce_accept(producible: &[&str]) -> EnforceAccept {
+    EnforceAccept {
+        producible: producible
+            .iter()
+            .map(|mime| {
+                mime.parse()
+                    .unwrap_or_else(|_| panic!("{} is not a valid mime type", mime))
+            })
+            .collect(),
+    }
+}
+
+impl EnforceAccept {
+    fn acceptable(&self, ranges: &[Mime]) -> bool {
+        ranges.is_empty()
+            || self
+                .producible
+                .iter()
+                .any(|mime| matches!(match_quality(ranges, mime), Some(q) if q > 0.0))
+    }
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for EnforceAccept {
+    async fn handle(self: Arc<Self>, ctx: Context<S>, next: Next) -> Result {
+        let accept = ctx.header(ACCEPT).await.and_then(|accept| accept.ok());
+        if !self.acceptable(&media_ranges(accept.as_deref())) {
+            throw!(
+                StatusCode::NOT_ACCEPTABLE,
+                format!(
+                    "none of accepted types is supported, supported types: {}",
+                    self.producible
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                )
+            )
+        }
+        next().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{enforce_accept, negotiate, Format};
+    use crate::core::App;
+    use async_std::task::spawn;
+    use http::header::ACCEPT;
+    use http::StatusCode;
+
+    #[test]
+    fn default_format() {
+        assert_eq!(Format::Json, negotiate(None, Format::ALL).unwrap());
+        assert_eq!(Format::Json, negotiate(Some(""), Format::ALL).unwrap());
+        assert_eq!(Format::Form, negotiate(None, &[Format::Form]).unwrap());
+    }
+
+    #[test]
+    fn quality_and_specificity() {
+        let formats = &[Format::Json, Format::Form];
+        assert_eq!(
+            Format::Form,
+            negotiate(
+                Some("application/json;q=0.5, application/x-www-form-urlencoded"),
+                formats
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            Format::Json,
+            negotiate(Some("application/*"), formats).unwrap()
+        );
+        assert_eq!(
+            Format::Form,
+            negotiate(Some("*/*, application/json;q=0"), formats).unwrap()
+        );
+        assert_eq!(
+            Format::Json,
+            negotiate(Some("text/html, */*;q=0.1"), formats).unwrap()
+        );
+        assert!(
+            negotiate(Some("application/x-www-form-urlencoded"), Format::ALL).is_err()
+        );
+    }
+
+    #[cfg(feature = "xml")]
+    #[test]
+    fn xml() {
+        assert_eq!(
+            Format::Xml,
+            negotiate(Some("application/xml, application/json;q=0.9"), Format::ALL)
+                .unwrap()
+        );
+        assert_eq!(
+            Format::Json,
+            negotiate(Some("application/*"), Format::ALL).unwrap()
+        );
+    }
+
+    #[test]
+    fn not_acceptable() {
+        let formats = &[Format::Json, Format::Form];
+        let ret = negotiate(Some("text/html, application/json;q=0"), formats);
+        assert!(ret.is_err());
+        let status = ret.unwrap_err();
+        assert_eq!(StatusCode::NOT_ACCEPTABLE, status.status_code);
+        assert_eq!(
+            "none of accepted types is supported, supported types: application/json, application/x-www-form-urlencoded",
+            status.message
+        );
+    }
+
+    #[tokio::test]
+    async fn enforce() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(enforce_accept(&["application/json", "text/plain"]))
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+        for accept in [
+            "*/*",
+            "application/json",
+            "application/*",
+            "text/html, text/plain;q=0.1",
+        ]
+        .iter()
+        {
+            let resp = client.get(&url).header(ACCEPT, *accept).send().await?;
+            assert_eq!(StatusCode::OK, resp.status());
+        }
+
+        // absent
+        let resp = client.get(&url).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        for accept in [
+            "application/xml",
+            "*/*;q=0",
+            "text/html, application/json;q=0",
+        ]
+        .iter()
+        {
+            let resp = client.get(&url).header(ACCEPT, *accept).send().await?;
+            assert_eq!(StatusCode::NOT_ACCEPTABLE, resp.status());
+            assert_eq!(
+                "none of accepted types is supported, supported types: application/json, text/plain",
+                resp.text().await?
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use crate::core::{Error, Result, StatusCode};
+use jsonschema::JSONSchema;
+use serde::Serialize;
+use serde_json::Value;
+
+/// A schema validation error in the body of 422 UNPROCESSABLE ENTITY.
+#[derive(Serialize)]
+struct SchemaError {
+    path: String,
+    message: String,
+}
+
+/// The body of 422 UNPROCESSABLE ENTITY responded when schema validation fails.
+#[derive(Serialize)]
+struct ErrorBody {
+    message: &'static str,
+    errors: Vec<SchemaError>,
+}
+
+/// Validate value against schema, return an exposed 422 Error with json message if fails.
+pub fn validate(schema: &JSONSchema, value: &Value) -> Result {
+    let errors: Vec<SchemaError> = match schema.validate(value) {
+        Ok(()) => return Ok(()),
+        Err(errors) => errors
+            .map(|err| SchemaError {
+                path: err.instance_path.to_string(),
+                message: err.to_string(),
+            })
+            .collect(),
+    };
+    let body = ErrorBody {
+        message: "schema validation failed",
+        errors,
+    };
+    match serde_json::to_string(&body) {
+        Ok(message) => Err(Error::new(StatusCode::UNPROCESSABLE_ENTITY, message, true)),
+        Err(err) => Err(Error::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("{}\nschema errors cannot be serialized to json", err),
+            false,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate;
+    use http::StatusCode;
+    use jsonschema::JSONSchema;
+    use serde_json::json;
+
+    #[test]
+    fn validate_fails() {
+        let schema = JSONSchema::compile(&json!({
+            "type": "object",
+            "properties": {"name": {"type": "string", "minLength": 1}},
+            "required": ["name"]
+        }))
+        .unwrap();
+        assert!(validate(&schema, &json!({"name": "Hexilee"})).is_ok());
+        let status = validate(&schema, &json!({"name": ""})).unwrap_err();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, status.status_code);
+        assert!(status.expose);
+        assert_eq!(
+            r#"{"message":"schema validation failed","errors":[{"path":"/name","message":"\"\" is shorter than 1 character"}]}"#,
+            status.message
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use crate::core::{Error, Result, StatusCode};
 use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 pub fn from_bytes<B: DeserializeOwned>(data: &[u8]) -> Result<B> {
     serde_urlencoded::from_bytes(data).map_err(|err| {
@@ -11,6 +12,21 @@ pub fn from_bytes<B: DeserializeOwned>(data: &[u8]) -> Result<B> {
     })
 }
 
+pub fn to_bytes<B: Serialize>(object: &B) -> Result<Vec<u8>> {
+    serde_urlencoded::to_string(object)
+        .map(String::into_bytes)
+        .map_err(|err| {
+            Error::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!(
+                    "{}\nobject cannot be serialized to x-www-form-urlencoded",
+                    err
+                ),
+                false,
+            )
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
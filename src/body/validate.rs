@@ -0,0 +1,59 @@
+use crate::core::{Error, Result, StatusCode};
+use serde::Serialize;
+use validator::{Validate, ValidationErrors};
+
+/// The body of 422 UNPROCESSABLE ENTITY responded when validation fails.
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    message: &'static str,
+    errors: &'a ValidationErrors,
+}
+
+/// Validate object, return an exposed 422 Error with json message if fails.
+pub fn validate<B: Validate>(data: B) -> Result<B> {
+    data.validate().map(|_| data).map_err(|errors| {
+        let body = ErrorBody {
+            message: "validation failed",
+            errors: &errors,
+        };
+        match serde_json::to_string(&body) {
+            Ok(message) => Error::new(StatusCode::UNPROCESSABLE_ENTITY, message, true),
+            Err(err) => Error::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("{}\nvalidation errors cannot be serialized to json", err),
+                false,
+            ),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate;
+    use http::StatusCode;
+    use validator::{Validate, ValidationError, ValidationErrors};
+
+    #[derive(Debug)]
+    struct Always;
+
+    impl Validate for Always {
+        fn validate(&self) -> Result<(), ValidationErrors> {
+            let mut errors = ValidationErrors::new();
+            errors.add("name", ValidationError::new("length"));
+            Err(errors)
+        }
+    }
+
+    #[test]
+    fn validate_fails() {
+        let ret = validate(Always);
+        assert!(ret.is_err());
+        let status = ret.unwrap_err();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, status.status_code);
+        assert!(status.expose);
+        assert_eq!(
+            r#"{"message":"validation failed","errors":{"name":[{"code":"length","message":null,"params":{}}]}}"#,
+            status.message
+        );
+    }
+}
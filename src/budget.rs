@@ -0,0 +1,186 @@
+//! The budget module of roa.
+//! This module provides a middleware `Budget` tracking a total time budget of a request,
+//! a middleware `budget_guard` to short-circuit exhausted requests,
+//! and a context extension `TimeBudget` to get the remaining budget.
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::budget::{budget, budget_guard, TimeBudget};
+//! use roa::core::App;
+//! use async_std::task::spawn;
+//! use http::StatusCode;
+//! use std::time::Duration;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let (addr, server) = App::new(())
+//!         .gate(budget(Duration::from_secs(1)))
+//!         // slow middleware consume the budget
+//!         .gate(budget_guard)
+//!         .end(|mut ctx| async move {
+//!             let remaining = ctx.remaining_budget().await.unwrap();
+//!             if remaining < Duration::from_millis(100) {
+//!                 ctx.resp_mut().await.write_str("degraded response");
+//!             } else {
+//!                 ctx.resp_mut().await.write_str("full response");
+//!             }
+//!             Ok(())
+//!         })
+//!         .run_local()?;
+//!     spawn(server);
+//!     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+//!     assert_eq!(StatusCode::OK, resp.status());
+//!     assert_eq!("full response", resp.text().await?);
+//!     Ok(())
+//! }
+//! ```
+
+use crate::core::{async_trait, Context, Middleware, Next, Result, State, StatusCode};
+use lazy_static::lazy_static;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A unique symbol to store and load the deadline in Context::storage.
+struct BudgetSymbol;
+
+/// Deadlines are stored as nanoseconds since this process-wide anchor,
+/// as context storage only holds strings.
+fn anchor() -> Instant {
+    lazy_static! {
+        static ref ANCHOR: Instant = Instant::now();
+    }
+    *ANCHOR
+}
+
+/// A middleware to start the time budget of a request, returned by `budget`.
+pub struct Budget(Duration);
+
+/// Start a total time budget of the request before downstream middleware run.
+///
+/// The budget is consumed as time elapses, downstream can get the remaining by
+/// `TimeBudget::remaining_budget` to decide whether to degrade the response.
+/// If a budget is already started by an upstream `Budget`, the earlier deadline is kept.
+///
+/// The budget is not enforced by itself, use `budget_guard` to short-circuit
+/// exhausted requests, or a timeout to cancel slow handlers.
+pub fn budget(duration: Duration) -> Budget {
+    Budget(duration)
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for Budget {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, next: Next) -> Result {
+        let anchor = anchor();
+        let deadline = Instant::now() + self.0;
+        let inherited = deadline_of(&ctx).await;
+        if !matches!(inherited, Some(inherited) if inherited <= deadline) {
+            let nanos = deadline.duration_since(anchor).as_nanos();
+            ctx.store::<BudgetSymbol>("deadline", nanos.to_string())
+                .await;
+        }
+        next().await
+    }
+}
+
+/// A middleware to short-circuit a request whose budget is exhausted
+/// with 503 SERVICE UNAVAILABLE, usually placed right before the handler.
+///
+/// Requests without budget are passed through.
+/// The response is written directly instead of returning an error,
+/// see `roa::core::Error` for server errors.
+pub async fn budget_guard<S: State>(mut ctx: Context<S>, next: Next) -> Result {
+    if ctx.remaining_budget().await == Some(Duration::from_secs(0)) {
+        let mut response = ctx.resp_mut().await;
+        response.status = StatusCode::SERVICE_UNAVAILABLE;
+        response.write_str("request budget is exhausted");
+        return Ok(());
+    }
+    next().await
+}
+
+async fn deadline_of<S: State>(ctx: &Context<S>) -> Option<Instant> {
+    let nanos: u64 = ctx.load::<BudgetSymbol>("deadline").await?.parse().ok()?;
+    Some(anchor() + Duration::from_nanos(nanos))
+}
+
+/// A context extension to get the remaining time budget.
+#[async_trait]
+pub trait TimeBudget {
+    /// Get the remaining budget, zero if exhausted, None if no budget is started.
+    async fn remaining_budget(&self) -> Option<Duration>;
+}
+
+#[async_trait]
+impl<S: State> TimeBudget for Context<S> {
+    async fn remaining_budget(&self) -> Option<Duration> {
+        deadline_of(self)
+            .await
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{budget, budget_guard, TimeBudget};
+    use crate::core::App;
+    use async_std::task::{sleep, spawn};
+    use http::StatusCode;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn remaining_budget() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate_fn(|ctx, next| async move {
+                assert_eq!(None, ctx.remaining_budget().await);
+                next().await
+            })
+            .gate(budget(Duration::from_secs(10)))
+            // a nested larger budget doesn't extend the deadline
+            .gate(budget(Duration::from_secs(100)))
+            .gate_fn(|_ctx, next| async move {
+                sleep(Duration::from_millis(100)).await;
+                next().await
+            })
+            .end(|mut ctx| async move {
+                let remaining = ctx.remaining_budget().await.unwrap();
+                assert!(remaining <= Duration::from_millis(9900));
+                assert!(remaining > Duration::from_secs(5));
+                ctx.resp_mut().await.write_str("full response");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("full response", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn exhausted() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(budget(Duration::from_millis(50)))
+            .gate_fn(|ctx, next| async move {
+                if ctx.uri().await.path() == "/slow" {
+                    sleep(Duration::from_millis(100)).await;
+                }
+                next().await
+            })
+            .gate(budget_guard)
+            .end(|mut ctx| async move {
+                ctx.resp_mut().await.write_str("handled");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/slow", addr)).await?;
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, resp.status());
+        assert_eq!("request budget is exhausted", resp.text().await?);
+
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("handled", resp.text().await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,515 @@
+//! The cache module of roa.
+//! This module provides a middleware `Cache` to cache responses in memory,
+//! with a pluggable `CacheStore` and cache key.
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::cache::cache;
+//! use roa::core::App;
+//! use async_std::task::spawn;
+//! use http::StatusCode;
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::sync::Arc;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let counter = Arc::new(AtomicUsize::new(0));
+//!     let count = counter.clone();
+//!     let (addr, server) = App::new(())
+//!         .gate(cache(1024))
+//!         .gate_fn(move |mut ctx, _next| {
+//!             count.fetch_add(1, Ordering::SeqCst);
+//!             async move {
+//!                 ctx.resp_mut().await.write_str("expensive computation");
+//!                 Ok(())
+//!             }
+//!         })
+//!         .run_local()?;
+//!     spawn(server);
+//!     for _ in 0..2 {
+//!         let resp = reqwest::get(&format!("http://{}", addr)).await?;
+//!         assert_eq!(StatusCode::OK, resp.status());
+//!         assert_eq!("expensive computation", resp.text().await?);
+//!     }
+//!     assert_eq!(1, counter.load(Ordering::SeqCst));
+//!     Ok(())
+//! }
+//! ```
+
+use crate::core::header::{HeaderName, CACHE_CONTROL, SET_COOKIE, VARY};
+use crate::core::{async_trait, Context, Middleware, Next, Request, Result, State};
+use crate::idempotency::{buffer, replay, CachedResponse};
+use async_std::sync::{Arc, Mutex};
+use http::Method;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+/// A storage of cached responses.
+///
+/// Implement it to share responses across processes, in redis for example.
+#[async_trait]
+pub trait CacheStore: 'static + Sync + Send {
+    /// Get an unexpired response by key.
+    async fn get(&self, key: &str) -> Option<CachedResponse>;
+
+    /// Store a response, it should expire after ttl.
+    async fn set(&self, key: String, response: CachedResponse, ttl: Duration);
+}
+
+/// The default in-memory `CacheStore`,
+/// evicting the least recently used response when it's full.
+pub struct MemoryCache {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    tick: u64,
+    entries: HashMap<String, Entry>,
+
+    /// Keys ordered by the last time they were used.
+    order: BTreeMap<u64, String>,
+}
+
+struct Entry {
+    last_used: u64,
+    deadline: Instant,
+    response: CachedResponse,
+}
+
+impl MemoryCache {
+    /// Construct a store holding at most `capacity` responses.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+}
+
+impl Inner {
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.last_used);
+        }
+    }
+}
+
+#[async_trait]
+impl CacheStore for MemoryCache {
+    async fn get(&self, key: &str) -> Option<CachedResponse> {
+        let mut inner = self.inner.lock().await;
+        let inner = &mut *inner;
+        inner.tick += 1;
+        let tick = inner.tick;
+        let entry = inner.entries.get_mut(key)?;
+        if entry.deadline <= Instant::now() {
+            inner.remove(key);
+            return None;
+        }
+        let last_used = std::mem::replace(&mut entry.last_used, tick);
+        let response = entry.response.clone();
+        if let Some(key) = inner.order.remove(&last_used) {
+            inner.order.insert(tick, key);
+        }
+        Some(response)
+    }
+
+    async fn set(&self, key: String, response: CachedResponse, ttl: Duration) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().await;
+        let inner = &mut *inner;
+        inner.remove(&key);
+        if inner.entries.len() >= self.capacity {
+            let least_used = inner.order.values().next().cloned();
+            if let Some(key) = least_used {
+                inner.remove(&key);
+            }
+        }
+        inner.tick += 1;
+        let tick = inner.tick;
+        inner.order.insert(tick, key.clone());
+        inner.entries.insert(
+            key,
+            Entry {
+                last_used: tick,
+                deadline: Instant::now() + ttl,
+                response,
+            },
+        );
+    }
+}
+
+type KeyFn = dyn 'static + Sync + Send + Fn(&Request) -> Option<String>;
+
+/// A middleware to cache responses of GET requests, returned by `cache`.
+pub struct Cache {
+    store: Arc<dyn CacheStore>,
+    key: Option<Box<KeyFn>>,
+    vary: Vec<HeaderName>,
+    ttl: Duration,
+}
+
+/// Cache successful responses of GET requests in a `MemoryCache` with `capacity`,
+/// and serve them to subsequent requests with the same key until expiry.
+///
+/// - The key is method, uri and values of headers set by `Cache::vary`.
+/// - The ttl is "max-age" of the "Cache-Control" response header, 60 seconds by default.
+/// - Responses marked "no-store" or "private", with "Set-Cookie",
+///   or with streaming body (like a file) are never cached.
+/// - Responses with "Vary" are cached only if all headers it names
+///   are set by `Cache::vary`, so "Vary: *" is never cached.
+/// - A replayed response keeps headers set by upstream before the cache,
+///   cached headers are added only if upstream doesn't set them.
+///
+/// ### Config
+///
+/// ```rust
+/// use roa::cache::{cache, MemoryCache};
+/// use roa::core::header::ACCEPT_LANGUAGE;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let shared = cache(1024)
+///     .store(Arc::new(MemoryCache::new(4096)))
+///     .vary(ACCEPT_LANGUAGE)
+///     .ttl(Duration::from_secs(10));
+///
+/// // or a custom key
+/// let by_path = cache(1024).key(|req| Some(req.uri.path().to_string()));
+/// ```
+pub fn cache(capacity: usize) -> Cache {
+    Cache {
+        store: Arc::new(MemoryCache::new(capacity)),
+        key: None,
+        vary: Vec::new(),
+        ttl: Duration::from_secs(60),
+    }
+}
+
+impl Cache {
+    /// Use a custom store, the capacity is ignored.
+    pub fn store(mut self, store: Arc<dyn CacheStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Use a custom key of request instead of the default one,
+    /// returning `None` skips the cache.
+    pub fn key(
+        mut self,
+        key: impl 'static + Sync + Send + Fn(&Request) -> Option<String>,
+    ) -> Self {
+        self.key = Some(Box::new(key));
+        self
+    }
+
+    /// Add a request header to the default key,
+    /// requests with different values of it are cached separately.
+    pub fn vary(mut self, name: HeaderName) -> Self {
+        self.vary.push(name);
+        self
+    }
+
+    /// Set the ttl of responses without "max-age".
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    fn default_key(&self, req: &Request) -> String {
+        let mut key = format!("{} {}", req.method, req.uri);
+        for name in self.vary.iter() {
+            key.push('\n');
+            for value in req.headers.get_all(name) {
+                key.push_str(&String::from_utf8_lossy(value.as_bytes()));
+                key.push(',');
+            }
+        }
+        key
+    }
+
+    /// Decide the ttl of a response, return None if it cannot be cached.
+    fn response_ttl(&self, response: &CachedResponse) -> Option<Duration> {
+        // a response setting cookies must never be replayed to other clients
+        if response.headers.contains_key(SET_COOKIE) {
+            return None;
+        }
+        // a response varying on a header out of the key would be replayed to wrong clients
+        for value in response.headers.get_all(VARY) {
+            for name in value.to_str().ok()?.split(',').map(str::trim) {
+                let in_key = self
+                    .vary
+                    .iter()
+                    .any(|vary| vary.as_str().eq_ignore_ascii_case(name));
+                if !name.is_empty() && !in_key {
+                    return None;
+                }
+            }
+        }
+        let mut ttl = self.ttl;
+        for value in response.headers.get_all(CACHE_CONTROL) {
+            let value = value.to_str().ok()?;
+            for directive in value.split(',') {
+                let directive = directive.trim().to_ascii_lowercase();
+                match directive.as_str() {
+                    "no-store" | "private" => return None,
+                    _ => {
+                        if let Some(secs) = directive.strip_prefix("max-age=") {
+                            ttl = Duration::from_secs(secs.parse().ok()?);
+                        }
+                    }
+                }
+            }
+        }
+        if ttl.as_secs() == 0 {
+            None
+        } else {
+            Some(ttl)
+        }
+    }
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for Cache {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, next: Next) -> Result {
+        let key = {
+            let req = ctx.req().await;
+            if req.method != Method::GET {
+                None
+            } else {
+                match self.key {
+                    Some(ref key) => key(&req),
+                    None => Some(self.default_key(&req)),
+                }
+            }
+        };
+        let key = match key {
+            Some(key) => key,
+            None => return next().await,
+        };
+        if let Some(cached) = self.store.get(&key).await {
+            // headers set by upstream (like cors or request id) are kept
+            replay(&mut *ctx.resp_mut().await, cached);
+            return Ok(());
+        }
+        next().await?;
+        if let Some(cached) = buffer(&mut ctx).await? {
+            ctx.resp_mut().await.write_bytes(cached.body.clone());
+            if let Some(ttl) = self.response_ttl(&cached) {
+                self.store.set(key, cached, ttl).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cache, CacheStore, MemoryCache};
+    use crate::core::App;
+    use crate::idempotency::CachedResponse;
+    use async_std::task::spawn;
+    use http::header::{HeaderValue, ACCEPT_LANGUAGE, CACHE_CONTROL};
+    use http::StatusCode;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn cache_control() -> Result<(), Box<dyn std::error::Error>> {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let count = counter.clone();
+        let (addr, server) = App::new(())
+            .gate(cache(16).vary(ACCEPT_LANGUAGE))
+            .gate_fn(move |mut ctx, _next| {
+                let id = count.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    let cache_control = match ctx.uri().await.path() {
+                        "/no-store" => "no-store",
+                        "/expire" => "public, max-age=0",
+                        _ => "max-age=60",
+                    };
+                    ctx.resp_mut()
+                        .await
+                        .headers
+                        .insert(CACHE_CONTROL, HeaderValue::from_static(cache_control));
+                    ctx.resp_mut().await.write_str(id.to_string());
+                    Ok(())
+                }
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let get = |path: &'static str, lang: &'static str| {
+            client
+                .get(&format!("http://{}{}", addr, path))
+                .header(ACCEPT_LANGUAGE, lang)
+                .send()
+        };
+
+        assert_eq!("0", get("/", "en").await?.text().await?);
+        let resp = get("/", "en").await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("max-age=60", resp.headers()[CACHE_CONTROL]);
+        assert_eq!("0", resp.text().await?);
+        // vary
+        assert_eq!("1", get("/", "zh").await?.text().await?);
+        assert_eq!("1", get("/", "zh").await?.text().await?);
+
+        assert_eq!("2", get("/no-store", "en").await?.text().await?);
+        assert_eq!("3", get("/no-store", "en").await?.text().await?);
+        assert_eq!("4", get("/expire", "en").await?.text().await?);
+        assert_eq!("5", get("/expire", "en").await?.text().await?);
+
+        // non-GET
+        let resp = client.post(&format!("http://{}", addr)).send().await?;
+        assert_eq!("6", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upstream_headers_and_cookies() -> Result<(), Box<dyn std::error::Error>> {
+        use http::header::SET_COOKIE;
+        let counter = Arc::new(AtomicUsize::new(0));
+        let count = counter.clone();
+        let (addr, server) = App::new(())
+            .gate_fn(move |mut ctx, next| {
+                let id = count.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    ctx.resp_mut()
+                        .await
+                        .headers
+                        .insert("x-request-id", HeaderValue::from(id));
+                    next().await
+                }
+            })
+            .gate(cache(16))
+            .gate_fn(|mut ctx, _next| async move {
+                if ctx.uri().await.path() == "/login" {
+                    ctx.resp_mut()
+                        .await
+                        .headers
+                        .insert(SET_COOKIE, HeaderValue::from_static("session=secret"));
+                }
+                let id = ctx.resp().await.headers["x-request-id"].clone();
+                ctx.resp_mut().await.write_bytes(id.as_bytes().to_vec());
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!("0", resp.headers()["x-request-id"]);
+        assert_eq!("0", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        // replayed body with the header of this request
+        assert_eq!("1", resp.headers()["x-request-id"]);
+        assert_eq!("0", resp.text().await?);
+
+        let resp = reqwest::get(&format!("http://{}/login", addr)).await?;
+        assert_eq!("session=secret", resp.headers()[SET_COOKIE]);
+        assert_eq!("2", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}/login", addr)).await?;
+        assert_eq!("3", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn response_vary() -> Result<(), Box<dyn std::error::Error>> {
+        use http::header::{ACCEPT_ENCODING, VARY};
+        let counter = Arc::new(AtomicUsize::new(0));
+        let endpoint = |count: Arc<AtomicUsize>| {
+            move |mut ctx: crate::core::Context<()>, _next| {
+                count.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    let encoding = ctx.req().await.headers[ACCEPT_ENCODING].clone();
+                    let mut resp = ctx.resp_mut().await;
+                    resp.headers
+                        .insert(VARY, HeaderValue::from_static("accept-encoding"));
+                    resp.write_bytes(encoding.as_bytes().to_vec());
+                    Ok(())
+                }
+            }
+        };
+        let (uncached, server) = App::new(())
+            .gate(cache(16))
+            .gate_fn(endpoint(counter.clone()))
+            .run_local()?;
+        spawn(server);
+        let (cached, server) = App::new(())
+            .gate(cache(16).vary(ACCEPT_ENCODING))
+            .gate_fn(endpoint(counter.clone()))
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::builder().gzip(false).build()?;
+        for addr in [uncached, cached].iter() {
+            for encoding in ["gzip", "br", "gzip", "br"].iter() {
+                let resp = client
+                    .get(&format!("http://{}", addr))
+                    .header(ACCEPT_ENCODING, *encoding)
+                    .send()
+                    .await?;
+                assert_eq!(*encoding, resp.text().await?);
+            }
+        }
+        // 4 requests to the uncached app, 2 to the cached one
+        assert_eq!(6, counter.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn custom_key() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(cache(16).key(|req| match req.uri.path() {
+                "/skip" => None,
+                path => Some(path.to_string()),
+            }))
+            .gate_fn(|mut ctx, _next| async move {
+                let uri = ctx.uri().await;
+                ctx.resp_mut().await.write_str(uri.to_string());
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/?id=0", addr)).await?;
+        assert_eq!("/?id=0", resp.text().await?);
+        // query is ignored by key
+        let resp = reqwest::get(&format!("http://{}/?id=1", addr)).await?;
+        assert_eq!("/?id=0", resp.text().await?);
+
+        let resp = reqwest::get(&format!("http://{}/skip?id=0", addr)).await?;
+        assert_eq!("/skip?id=0", resp.text().await?);
+        let resp = reqwest::get(&format!("http://{}/skip?id=1", addr)).await?;
+        assert_eq!("/skip?id=1", resp.text().await?);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn memory_cache_lru() {
+        let store = MemoryCache::new(2);
+        let response = |body: &[u8]| CachedResponse {
+            status: StatusCode::OK,
+            headers: Default::default(),
+            body: body.to_vec(),
+        };
+        let ttl = Duration::from_secs(60);
+        store.set("a".to_string(), response(b"a"), ttl).await;
+        store.set("b".to_string(), response(b"b"), ttl).await;
+        assert!(store.get("a").await.is_some());
+        // "b" is the least recently used
+        store.set("c".to_string(), response(b"c"), ttl).await;
+        assert!(store.get("b").await.is_none());
+        assert_eq!(b"a".as_ref(), store.get("a").await.unwrap().body.as_slice());
+        assert_eq!(b"c".as_ref(), store.get("c").await.unwrap().body.as_slice());
+
+        store
+            .set("a".to_string(), response(b"a"), Duration::from_millis(0))
+            .await;
+        assert!(store.get("a").await.is_none());
+        assert_eq!(1, store.inner.lock().await.entries.len());
+    }
+}
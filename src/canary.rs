@@ -0,0 +1,318 @@
+//! The canary module of roa.
+//! This module provides a middleware `Canary` to split traffic for canary deployments.
+
+#[cfg(feature = "cookies")]
+use crate::cookie::Cookier;
+use crate::core::{async_trait, Context, Middleware, Next, Result, State};
+use crate::preload::*;
+use std::sync::Arc;
+
+/// A middleware to route a percentage of clients to an alternate middleware,
+/// returned by `canary`.
+pub struct Canary<S> {
+    percentage: u8,
+    alternate: Arc<dyn Middleware<S>>,
+    #[cfg(feature = "cookies")]
+    cookie: Option<String>,
+    forwarded: bool,
+}
+
+/// Route `percentage` percent of clients to `alternate`, others are passed to downstream.
+///
+/// Clients are assigned by a stable hash of a sticky key, so the same client
+/// always goes the same way, even across restarts or instances.
+/// The key is the peer address by default, call `Canary::forwarded`
+/// to use `Forward::client_ip` behind a trusted proxy,
+/// or `Canary::cookie` to prefer the value of a cookie, like a session id.
+///
+/// The `alternate` middleware may call `next` to continue the downstream,
+/// or respond by itself as an endpoint.
+///
+/// ### Panic
+///
+/// Panic if `percentage` is greater than 100.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::canary::canary;
+/// use roa::core::{App, Context, Next};
+/// use roa::header::FriendlyHeaders;
+/// use async_std::task::spawn;
+/// use http::StatusCode;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let beta = |mut ctx: Context<()>, next: Next| async move {
+///         ctx.resp_mut().await.insert("x-release", "beta")?;
+///         next().await
+///     };
+///     let (addr, server) = App::new(())
+///         .gate(canary(5, beta))
+///         .run_local()?;
+///     spawn(server);
+///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+///     assert_eq!(StatusCode::OK, resp.status());
+///     Ok(())
+/// }
+/// ```
+pub fn canary<S: State>(percentage: u8, alternate: impl Middleware<S>) -> Canary<S> {
+    assert!(
+        percentage <= 100,
+        "percentage of canary should be no greater than 100, but got {}",
+        percentage
+    );
+    Canary {
+        percentage,
+        alternate: Arc::new(alternate),
+        #[cfg(feature = "cookies")]
+        cookie: None,
+        forwarded: false,
+    }
+}
+
+impl<S> Canary<S> {
+    /// Use the value of cookie `name` as the sticky key if it's present,
+    /// fall back to client ip otherwise.
+    ///
+    /// Cookies are read by `Cookier::cookie`,
+    /// so this middleware must be used in downstream of `cookie_parser`.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::canary::canary;
+    /// use roa::cookie::cookie_parser;
+    /// use roa::core::{App, Context, Next};
+    /// use roa::header::FriendlyHeaders;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let beta = |mut ctx: Context<()>, next: Next| async move {
+    ///         ctx.resp_mut().await.insert("x-release", "beta")?;
+    ///         next().await
+    ///     };
+    ///     let (addr, server) = App::new(())
+    ///         .gate(cookie_parser)
+    ///         .gate(canary(5, beta).cookie("session"))
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "cookies")]
+    pub fn cookie(mut self, name: impl ToString) -> Self {
+        self.cookie = Some(name.to_string());
+        self
+    }
+
+    /// Use `Forward::client_ip` instead of the peer address as the client ip.
+    ///
+    /// Any client can choose its cohort by a fake "X-Forwarded-For",
+    /// only call this method behind a trusted proxy which overwrites it.
+    pub fn forwarded(mut self) -> Self {
+        self.forwarded = true;
+        self
+    }
+
+    /// Whether a sticky key is assigned to the canary.
+    fn assign(&self, key: &str) -> bool {
+        (fnv1a(key.as_bytes()) % 100) < u64::from(self.percentage)
+    }
+}
+
+/// 64-bit FNV-1a, which is stable across processes unlike `DefaultHasher`.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for Canary<S> {
+    async fn handle(self: Arc<Self>, ctx: Context<S>, next: Next) -> Result {
+        #[cfg(feature = "cookies")]
+        let key = match &self.cookie {
+            Some(name) => ctx.cookie(name).await.filter(|value| !value.is_empty()),
+            None => None,
+        };
+        #[cfg(not(feature = "cookies"))]
+        let key: Option<String> = None;
+        let key = match key {
+            Some(key) => key,
+            None if self.forwarded => ctx.client_ip().await.to_string(),
+            None => ctx.remote_addr().ip().to_string(),
+        };
+        if self.assign(&key) {
+            self.alternate.clone().handle(ctx, next).await
+        } else {
+            next().await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::canary;
+    use crate::core::{App, Context, Next};
+    use async_std::task::spawn;
+    use http::StatusCode;
+
+    async fn beta(mut ctx: Context<()>, _next: Next) -> crate::core::Result {
+        ctx.resp_mut().await.write_str("beta");
+        Ok(())
+    }
+
+    #[test]
+    fn split_ratio() {
+        for percentage in &[0, 1, 10, 50, 100] {
+            let canary = canary(*percentage, beta);
+            let assigned = (0..10000)
+                .filter(|i| canary.assign(&format!("10.0.{}.{}", i / 256, i % 256)))
+                .count();
+            let expected = usize::from(*percentage) * 100;
+            assert!(
+                (expected as isize - assigned as isize).abs() <= 200,
+                "{} of 10000 clients are assigned with {}%",
+                assigned,
+                percentage
+            );
+        }
+        assert_eq!(
+            0,
+            (0..1000)
+                .filter(|i| canary(0, beta).assign(&i.to_string()))
+                .count()
+        );
+        assert_eq!(
+            1000,
+            (0..1000)
+                .filter(|i| canary(100, beta).assign(&i.to_string()))
+                .count()
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn invalid_percentage() {
+        canary(101, beta);
+    }
+
+    #[tokio::test]
+    async fn sticky() -> Result<(), Box<dyn std::error::Error>> {
+        let router = canary(50, beta).forwarded();
+        let ips: Vec<String> = (0..256).map(|i| format!("10.0.0.{}", i)).collect();
+        let canary_ip = ips.iter().find(|ip| router.assign(ip)).unwrap().clone();
+        let stable_ip = ips.iter().find(|ip| !router.assign(ip)).unwrap().clone();
+
+        let (addr, server) = App::new(())
+            .gate(router)
+            .end(|mut ctx| async move {
+                ctx.resp_mut().await.write_str("stable");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+        for _ in 0..3 {
+            let resp = client
+                .get(&url)
+                .header("x-forwarded-for", canary_ip.as_str())
+                .send()
+                .await?;
+            assert_eq!(StatusCode::OK, resp.status());
+            assert_eq!("beta", resp.text().await?);
+            let resp = client
+                .get(&url)
+                .header("x-forwarded-for", stable_ip.as_str())
+                .send()
+                .await?;
+            assert_eq!("stable", resp.text().await?);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn peer_address() -> Result<(), Box<dyn std::error::Error>> {
+        let router = canary(50, beta);
+        let ips: Vec<String> = (0..256).map(|i| format!("10.0.0.{}", i)).collect();
+        let canary_ip = ips.iter().find(|ip| router.assign(ip)).unwrap().clone();
+        let stable_ip = ips.iter().find(|ip| !router.assign(ip)).unwrap().clone();
+        let expected = if router.assign("127.0.0.1") {
+            "beta"
+        } else {
+            "stable"
+        };
+
+        let (addr, server) = App::new(())
+            .gate(router)
+            .end(|mut ctx| async move {
+                ctx.resp_mut().await.write_str("stable");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+        // "X-Forwarded-For" is ignored by default
+        for ip in &[canary_ip, stable_ip] {
+            let resp = client
+                .get(&url)
+                .header("x-forwarded-for", ip.as_str())
+                .send()
+                .await?;
+            assert_eq!(expected, resp.text().await?);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "cookies")]
+    #[tokio::test]
+    async fn cookie() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::cookie::cookie_parser;
+        use http::header::COOKIE;
+
+        let router = canary(50, beta).cookie("session").forwarded();
+        let ips: Vec<String> = (0..256).map(|i| format!("10.0.0.{}", i)).collect();
+        let stable_ip = ips.iter().find(|ip| !router.assign(ip)).unwrap().clone();
+        let sessions: Vec<String> = (0..256).map(|i| i.to_string()).collect();
+        let canary_session = sessions
+            .iter()
+            .find(|id| router.assign(id))
+            .unwrap()
+            .clone();
+
+        let (addr, server) = App::new(())
+            .gate(cookie_parser)
+            .gate(router)
+            .end(|mut ctx| async move {
+                ctx.resp_mut().await.write_str("stable");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+        let resp = client
+            .get(&url)
+            .header("x-forwarded-for", stable_ip.as_str())
+            .send()
+            .await?;
+        assert_eq!("stable", resp.text().await?);
+
+        // cookie is preferred
+        let resp = client
+            .get(&url)
+            .header("x-forwarded-for", stable_ip.as_str())
+            .header(COOKIE, format!("lang=en; session={}", canary_session))
+            .send()
+            .await?;
+        assert_eq!("beta", resp.text().await?);
+        Ok(())
+    }
+}
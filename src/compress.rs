@@ -1,5 +1,6 @@
 //! The compress module of roa.
-//! This module provides a middleware `Compress`.
+//! This module provides a middleware `Compress` to compress response body,
+//! and a middleware `Decompress` to decompress request body.
 //!
 //! ### Example
 //!
@@ -39,18 +40,30 @@
 //! ```
 pub use async_compression::Level;
 
-use crate::core::header::CONTENT_ENCODING;
+use crate::core::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH};
 use crate::core::{
-    async_trait, Body, Context, Error, Middleware, Next, Result, State, StatusCode,
+    async_trait, throw, Body, Context, Error, Middleware, Next, Result, State,
+    StatusCode,
 };
-use accept_encoding::{parse, Encoding};
+use accept_encoding::{encodings, Encoding};
 use async_compression::futures::bufread::{
-    BrotliEncoder, GzipEncoder, ZlibEncoder, ZstdEncoder,
+    BrotliDecoder, BrotliEncoder, GzipDecoder, GzipEncoder, ZlibDecoder, ZlibEncoder,
+    ZstdDecoder, ZstdEncoder,
 };
+use futures::task::{Context as TaskContext, Poll};
+use futures::AsyncRead;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use typed_builder::TypedBuilder;
 
 /// A middleware to negotiate with client and compress response body automatically,
 /// supports gzip, deflate, brotli, zstd and identity.
+///
+/// Encodings with `q=0` are refused, and `*` applies to all encodings not listed.
+/// Identity is acceptable unless it is refused explicitly or by `*;q=0`.
+/// Throw 406 NOT ACCEPTABLE if all encodings are refused.
 #[derive(Debug, Copy, Clone)]
 pub struct Compress(pub Level);
 
@@ -60,39 +73,98 @@ impl Default for Compress {
     }
 }
 
+/// Supported encodings, in order of server preference.
+const SUPPORTED_ENCODINGS: [Encoding; 5] = [
+    Encoding::Gzip,
+    Encoding::Deflate,
+    Encoding::Brotli,
+    Encoding::Zstd,
+    Encoding::Identity,
+];
+
+/// Select the best encoding by parsed "Accept-Encoding", return `None` if all encodings are refused.
+///
+/// Encodings with the same q-value are preferred in order of client.
+fn select_encoding(accepted: &[(Option<Encoding>, f32)]) -> Option<Encoding> {
+    let wildcard = accepted
+        .iter()
+        .find(|(encoding, _)| encoding.is_none())
+        .map(|(_, q)| *q);
+    let quality = |encoding: Encoding| {
+        accepted
+            .iter()
+            .find(|(accepted, _)| *accepted == Some(encoding))
+            .map(|(_, q)| *q)
+            .or(wildcard)
+            .unwrap_or(if encoding == Encoding::Identity {
+                // identity is always acceptable unless refused.
+                f32::MIN_POSITIVE
+            } else {
+                0.0
+            })
+    };
+    let mut best: Option<(Encoding, f32)> = None;
+    let candidates = accepted
+        .iter()
+        .filter_map(|(encoding, _)| *encoding)
+        .chain(SUPPORTED_ENCODINGS.iter().copied());
+    for encoding in candidates {
+        let q = quality(encoding);
+        if q > 0.0 && best.map(|(_, best_q)| q > best_q).unwrap_or(true) {
+            best = Some((encoding, q));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
 #[async_trait]
 impl<S: State> Middleware<S> for Compress {
     async fn handle(self: Arc<Self>, mut ctx: Context<S>, next: Next) -> Result {
         next().await?;
+        let accepted = {
+            let headers = &ctx.req().await.headers;
+            // compress in gzip by default if "Accept-Encoding" is absent.
+            if headers.contains_key(ACCEPT_ENCODING) {
+                encodings(headers)
+                    .map_err(|err| Error::new(StatusCode::BAD_REQUEST, err, true))?
+            } else {
+                vec![(Some(Encoding::Gzip), 1.0)]
+            }
+        };
+        let best_encoding = match select_encoding(&accepted) {
+            Some(encoding) => encoding,
+            None => throw!(
+                StatusCode::NOT_ACCEPTABLE,
+                "none of supported encodings is acceptable"
+            ),
+        };
         let body: Body = std::mem::take(&mut *ctx.resp_mut().await);
-        let best_encoding = parse(&ctx.req().await.headers)
-            .map_err(|err| Error::new(StatusCode::BAD_REQUEST, err, true))?;
         let content_encoding = match best_encoding {
-            None | Some(Encoding::Gzip) => {
+            Encoding::Gzip => {
                 ctx.resp_mut()
                     .await
                     .write(GzipEncoder::with_quality(body, self.0));
                 Encoding::Gzip.to_header_value()
             }
-            Some(Encoding::Deflate) => {
+            Encoding::Deflate => {
                 ctx.resp_mut()
                     .await
                     .write(ZlibEncoder::with_quality(body, self.0));
                 Encoding::Deflate.to_header_value()
             }
-            Some(Encoding::Brotli) => {
+            Encoding::Brotli => {
                 ctx.resp_mut()
                     .await
                     .write(BrotliEncoder::with_quality(body, self.0));
                 Encoding::Brotli.to_header_value()
             }
-            Some(Encoding::Zstd) => {
+            Encoding::Zstd => {
                 ctx.resp_mut()
                     .await
                     .write(ZstdEncoder::with_quality(body, self.0));
                 Encoding::Zstd.to_header_value()
             }
-            Some(Encoding::Identity) => {
+            Encoding::Identity => {
                 ctx.resp_mut().await.write_buf(body);
                 Encoding::Identity.to_header_value()
             }
@@ -104,3 +176,304 @@ impl<S: State> Middleware<S> for Compress {
         Ok(())
     }
 }
+
+/// A middleware to decompress request body by "Content-Encoding",
+/// supports gzip, deflate, brotli, zstd and identity.
+///
+/// The body is decompressed as it is read by downstream, without buffering,
+/// so it can be transformed and streamed into a response compressed by `Compress`.
+/// "Content-Encoding" and "Content-Length" are removed from request headers once decoded.
+/// Throw 415 UNSUPPORTED MEDIA TYPE if the encoding is unsupported.
+///
+/// ### Size limit
+///
+/// A tiny compressed body may expand to gigabytes, so the decompressed size is limited
+/// by `max_size` (10 MiB by default), regardless of the compressed size.
+/// Reading more than `max_size` bytes fails, then 413 PAYLOAD TOO LARGE is thrown.
+/// If the body is streamed into the response instead of being read by downstream,
+/// the failure happens after the response is handed to hyper,
+/// so the connection is aborted and no 413 is sent.
+///
+/// ```rust
+/// use roa::compress::Decompress;
+///
+/// let decompress = Decompress::builder().max_size(1024 * 1024).build();
+/// ```
+#[derive(Debug, Copy, Clone, TypedBuilder)]
+pub struct Decompress {
+    /// Max size of decompressed body in bytes.
+    #[builder(default = 10 * 1024 * 1024)]
+    max_size: u64,
+}
+
+impl Default for Decompress {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// A reader failing once more than `remaining` bytes are read.
+struct Limited<R> {
+    reader: R,
+    remaining: u64,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Limited<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let size = futures::ready!(Pin::new(&mut self.reader).poll_read(cx, buf))?;
+        match self.remaining.checked_sub(size as u64) {
+            Some(remaining) => {
+                self.remaining = remaining;
+                Poll::Ready(Ok(size))
+            }
+            None => {
+                self.exceeded.store(true, Ordering::SeqCst);
+                Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "decompressed body is too large",
+                )))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for Decompress {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, next: Next) -> Result {
+        let encoding = match ctx.header(CONTENT_ENCODING).await {
+            Some(value) => value
+                .map_err(|err| Error::new(StatusCode::BAD_REQUEST, err, true))?
+                .trim()
+                .to_ascii_lowercase(),
+            None => return next().await,
+        };
+        let exceeded = Arc::new(AtomicBool::new(false));
+        {
+            let mut request = ctx.req_mut().await;
+            let body: Body = std::mem::take(&mut **request);
+            let reader: Box<dyn AsyncRead + Sync + Send + Unpin> =
+                match encoding.as_str() {
+                    "gzip" | "x-gzip" => Box::new(GzipDecoder::new(body)),
+                    "deflate" => Box::new(ZlibDecoder::new(body)),
+                    "br" => Box::new(BrotliDecoder::new(body)),
+                    "zstd" => Box::new(ZstdDecoder::new(body)),
+                    "identity" => Box::new(body),
+                    _ => throw!(
+                        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                        format!("content encoding `{}` is unsupported", encoding)
+                    ),
+                };
+            request.write(Limited {
+                reader,
+                remaining: self.max_size,
+                exceeded: exceeded.clone(),
+            });
+            request.headers.remove(CONTENT_ENCODING);
+            request.headers.remove(CONTENT_LENGTH);
+        }
+        let result = next().await;
+        if exceeded.load(Ordering::SeqCst) {
+            throw!(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("decompressed body exceeds {} bytes", self.max_size)
+            )
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{select_encoding, Compress, Decompress};
+    use crate::core::header::{ACCEPT_ENCODING, CONTENT_ENCODING};
+    use crate::core::{App, StatusCode};
+    use accept_encoding::{encodings, Encoding};
+    use async_std::task::spawn;
+    use http::{HeaderMap, HeaderValue};
+
+    fn select(accept_encoding: &str) -> Option<Encoding> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            ACCEPT_ENCODING,
+            HeaderValue::from_str(accept_encoding).unwrap(),
+        );
+        select_encoding(&encodings(&headers).unwrap())
+    }
+
+    #[test]
+    fn negotiate() {
+        assert_eq!(Some(Encoding::Identity), select_encoding(&[]));
+        assert_eq!(Some(Encoding::Brotli), select("br, gzip"));
+        assert_eq!(Some(Encoding::Gzip), select("br;q=0.5, gzip;q=0.8"));
+        assert_eq!(Some(Encoding::Gzip), select("*"));
+        assert_eq!(Some(Encoding::Identity), select("unknown"));
+        assert_eq!(Some(Encoding::Identity), select("identity"));
+    }
+
+    #[test]
+    fn refuse_by_zero_quality() {
+        assert_eq!(Some(Encoding::Deflate), select("gzip;q=0, deflate"));
+        assert_eq!(Some(Encoding::Identity), select("gzip;q=0"));
+        assert_eq!(Some(Encoding::Deflate), select("*;q=0, deflate;q=0.5"));
+        assert_eq!(Some(Encoding::Deflate), select("*;q=0.1, gzip;q=0"));
+        assert_eq!(None, select("*;q=0"));
+        assert_eq!(None, select("gzip;q=0, identity;q=0"));
+        assert_eq!(Some(Encoding::Gzip), select("gzip;q=0.1, identity;q=0"));
+    }
+
+    #[tokio::test]
+    async fn not_acceptable() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(Compress::default())
+            .end(|mut ctx| async move {
+                ctx.resp_mut().await.write_str("Hello, World!");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(ACCEPT_ENCODING, "*;q=0")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NOT_ACCEPTABLE, resp.status());
+
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(ACCEPT_ENCODING, "gzip;q=0")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("identity", resp.headers()["content-encoding"]);
+        assert_eq!("Hello, World!", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compress_deferred() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::core::Body;
+        let (addr, server) = App::new(())
+            .gate(Compress::default())
+            .end(|mut ctx| async move {
+                let mut resp = ctx.resp_mut().await;
+                resp.write_str("Hello, ");
+                resp.write_deferred(async {
+                    let mut body = Body::default();
+                    body.write_str("World");
+                    Ok(body)
+                });
+                resp.write_str("!");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::Client::builder()
+            .gzip(false)
+            .build()?
+            .get(&format!("http://{}", addr))
+            .header(ACCEPT_ENCODING, "gzip")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("gzip", resp.headers()[CONTENT_ENCODING]);
+        let body = gunzip(&resp.bytes().await?).await?;
+        assert_eq!(b"Hello, World!".as_ref(), body.as_slice());
+        Ok(())
+    }
+
+    async fn gunzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+        use async_compression::futures::bufread::GzipDecoder;
+        use futures::AsyncReadExt;
+        let mut decompressed = Vec::new();
+        GzipDecoder::new(futures::io::Cursor::new(data))
+            .read_to_end(&mut decompressed)
+            .await?;
+        Ok(decompressed)
+    }
+
+    async fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+        use async_compression::futures::bufread::GzipEncoder;
+        use futures::AsyncReadExt;
+        let mut compressed = Vec::new();
+        GzipEncoder::new(futures::io::Cursor::new(data))
+            .read_to_end(&mut compressed)
+            .await?;
+        Ok(compressed)
+    }
+
+    #[tokio::test]
+    async fn decompress() -> Result<(), Box<dyn std::error::Error>> {
+        use futures::AsyncReadExt;
+        let (addr, server) = App::new(())
+            .gate(Decompress::builder().max_size(1024 * 1024).build())
+            .end(|mut ctx| async move {
+                let mut data = String::new();
+                let mut request = ctx.req_mut().await;
+                assert!(!request.headers.contains_key(CONTENT_ENCODING));
+                request.read_to_string(&mut data).await?;
+                drop(request);
+                ctx.resp_mut().await.write_str(data);
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+
+        let resp = client
+            .post(&url)
+            .header(CONTENT_ENCODING, "gzip")
+            .body(gzip(b"Hello, World!").await?)
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("Hello, World!", resp.text().await?);
+
+        let resp = client.post(&url).body("Hello, World!").send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("Hello, World!", resp.text().await?);
+
+        let resp = client
+            .post(&url)
+            .header(CONTENT_ENCODING, "compress")
+            .body("Hello, World!")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn decompression_bomb() -> Result<(), Box<dyn std::error::Error>> {
+        use futures::AsyncReadExt;
+        let (addr, server) = App::new(())
+            .gate(Decompress::builder().max_size(1024 * 1024).build())
+            .end(|mut ctx| async move {
+                let mut data = Vec::new();
+                ctx.req_mut().await.read_to_end(&mut data).await?;
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let bomb = gzip(&vec![0; 8 * 1024 * 1024]).await?;
+        assert!(bomb.len() < 64 * 1024);
+        let resp = reqwest::Client::new()
+            .post(&format!("http://{}", addr))
+            .header(CONTENT_ENCODING, "gzip")
+            .body(bomb)
+            .send()
+            .await?;
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, resp.status());
+        assert_eq!(
+            "decompressed body exceeds 1048576 bytes",
+            resp.text().await?
+        );
+        Ok(())
+    }
+}
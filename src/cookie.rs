@@ -1,5 +1,11 @@
 //! The cookie module of roa.
-//! This module provides a middleware `cookie_parser` and a context extension `Cookier`.
+//! This module provides middlewares `cookie_parser` and `CookieParser`,
+//! and a context extension `Cookier`.
+//!
+//! The cookie middleware caches incoming cookies in context,
+//! and collects cookies set by downstream to flush them at once in its upstream phase,
+//! one `Set-Cookie` header per cookie. Without it, `Cookier::set_cookie`
+//! appends a `Set-Cookie` header to response immediately.
 //!
 //! ### Example
 //!
@@ -31,11 +37,15 @@
 //! ```
 
 use crate::core::{
-    async_trait, header, throw, Context, Next, Result, State, StatusCode,
+    async_trait, header, throw, Context, Middleware, Next, Result, State, StatusCode,
 };
 use crate::header::FriendlyHeaders;
 pub use cookie::Cookie;
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use std::collections::HashMap;
+use std::sync::Arc;
+use time::Duration;
+use typed_builder::TypedBuilder;
 
 const WWW_AUTHENTICATE_BUG_HELP: &str = "
 Invalid WWW_AUTHENTICATE value, this is a bug of roa::cookie.
@@ -45,6 +55,13 @@ Please report it to https://github.com/Hexilee/roa.
 /// A unique symbol to store and load variables in Context::storage.
 struct CookieSymbol;
 
+/// A unique symbol to store and load pending `Set-Cookie` values in Context::storage,
+/// which are joined by '\n' as it never appears in a header value.
+struct SetCookieSymbol;
+
+/// Key of pending `Set-Cookie` values, only exists if the cookie middleware is installed.
+const PENDING: &str = "pending";
+
 /// A context extension.
 /// The `cookie` and `must_cookie` method of this extension
 /// must be used in downstream of middleware `cookier_parser`,
@@ -148,7 +165,44 @@ pub trait Cookier {
     /// ```
     async fn cookie(&self, name: &str) -> Option<String>;
 
+    /// Get all cookies parsed by `cookie_parser`, return an empty map if there is no cookie.
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::cookie::{cookie_parser, Cookier};
+    /// use roa::core::{App, StatusCode};
+    /// use roa::core::header::COOKIE;
+    /// use async_std::task::spawn;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .gate(cookie_parser)
+    ///         .end( |ctx| async move {
+    ///             let cookies = ctx.cookies().await;
+    ///             assert_eq!(2, cookies.len());
+    ///             assert_eq!("Hexilee", cookies["name"]);
+    ///             assert_eq!("1", cookies["id"]);
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let client = reqwest::Client::new();
+    ///     let resp = client
+    ///         .get(&format!("http://{}", addr))
+    ///         .header(COOKIE, "name=Hexilee; id=1")
+    ///         .send()
+    ///         .await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn cookies(&self) -> HashMap<String, String>;
+
     /// Set a cookie in pecent encoding, should not return Err.
+    ///
+    /// If the cookie middleware is installed, the cookie is collected in context
+    /// and flushed in its upstream phase, otherwise it's appended to response immediately.
     /// ### Example
     ///
     /// ```rust
@@ -174,22 +228,163 @@ pub trait Cookier {
     /// }
     /// ```
     async fn set_cookie(&mut self, cookie: Cookie<'_>) -> Result;
+
+    /// Remove a cookie by appending a `Set-Cookie` with an empty value and `Max-Age=0`,
+    /// should not return Err.
+    ///
+    /// Browsers only drop the cookie if its `Path` and `Domain` match the original one,
+    /// so set them on the passed cookie if they were set when the cookie was created.
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::cookie::{Cookier, Cookie};
+    /// use roa::core::{App, StatusCode};
+    /// use roa::core::header::SET_COOKIE;
+    /// use async_std::task::spawn;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end( |mut ctx| async move {
+    ///             let mut cookie = Cookie::named("name");
+    ///             cookie.set_path("/");
+    ///             ctx.remove_cookie(cookie).await?;
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     assert_eq!("name=; Path=/; Max-Age=0", resp.headers()[SET_COOKIE].to_str()?);
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn remove_cookie(&mut self, cookie: Cookie<'_>) -> Result;
 }
 
-/// A middleware to parse cookie.
+/// Default max size in bytes of the `Cookie` header.
+pub const DEFAULT_MAX_SIZE: usize = 8 * 1024;
+
+/// Default max number of cookies in the `Cookie` header.
+pub const DEFAULT_MAX_COUNT: usize = 128;
+
+/// A middleware to parse cookie with limits of `DEFAULT_MAX_SIZE` and `DEFAULT_MAX_COUNT`.
+///
+/// Use `CookieParser` to configure these limits.
 pub async fn cookie_parser<S: State>(mut ctx: Context<S>, next: Next) -> Result {
+    parse_cookies(&mut ctx, DEFAULT_MAX_SIZE, DEFAULT_MAX_COUNT).await?;
+    let result = next().await;
+    flush_cookies(&mut ctx).await?;
+    result
+}
+
+/// A configurable middleware to parse cookie.
+///
+/// Throw 400 BAD REQUEST if the size of `Cookie` header is larger than `max_size`,
+/// or it contains more than `max_count` cookies.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::cookie::{CookieParser, Cookier};
+/// use roa::core::{App, StatusCode};
+/// use roa::core::header::COOKIE;
+/// use async_std::task::spawn;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let (addr, server) = App::new(())
+///         .gate(CookieParser::builder().max_size(32).max_count(2).build())
+///         .end(|mut ctx| async move {
+///             assert_eq!("Hexilee", ctx.must_cookie("name").await?);
+///             Ok(())
+///         })
+///         .run_local()?;
+///     spawn(server);
+///     let client = reqwest::Client::new();
+///     let resp = client
+///         .get(&format!("http://{}", addr))
+///         .header(COOKIE, "name=Hexilee")
+///         .send()
+///         .await?;
+///     assert_eq!(StatusCode::OK, resp.status());
+///
+///     let resp = client
+///         .get(&format!("http://{}", addr))
+///         .header(COOKIE, "name=Hexilee; id=1; age=18")
+///         .send()
+///         .await?;
+///     assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct CookieParser {
+    /// Max size in bytes of the `Cookie` header.
+    #[builder(default = DEFAULT_MAX_SIZE)]
+    max_size: usize,
+
+    /// Max number of cookies in the `Cookie` header.
+    #[builder(default = DEFAULT_MAX_COUNT)]
+    max_count: usize,
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for CookieParser {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, next: Next) -> Result {
+        parse_cookies(&mut ctx, self.max_size, self.max_count).await?;
+        let result = next().await;
+        flush_cookies(&mut ctx).await?;
+        result
+    }
+}
+
+async fn parse_cookies<S: State>(
+    ctx: &mut Context<S>,
+    max_size: usize,
+    max_count: usize,
+) -> Result {
     if let Some(Ok(cookies)) = ctx.header(header::COOKIE).await {
-        for cookie in cookies
+        if cookies.len() > max_size {
+            throw!(
+                StatusCode::BAD_REQUEST,
+                format!("cookie header is larger than {} bytes", max_size)
+            )
+        }
+        for (index, cookie) in cookies
             .split(';')
             .map(|cookie| cookie.trim())
             .map(Cookie::parse_encoded)
             .filter_map(|cookie| cookie.ok())
+            .enumerate()
         {
+            if index >= max_count {
+                throw!(
+                    StatusCode::BAD_REQUEST,
+                    format!("cookie header contains more than {} cookies", max_count)
+                )
+            }
             ctx.store::<CookieSymbol>(cookie.name(), cookie.value().to_string())
                 .await;
         }
     }
-    next().await
+    ctx.store::<SetCookieSymbol>(PENDING, String::new()).await;
+    Ok(())
+}
+
+/// Append all pending cookies to response, even if downstream failed.
+async fn flush_cookies<S: State>(ctx: &mut Context<S>) -> Result {
+    let pending = match ctx.load::<SetCookieSymbol>(PENDING).await {
+        Some(pending) => pending.into_value(),
+        None => return Ok(()),
+    };
+    ctx.store_unscoped::<SetCookieSymbol>(PENDING, String::new())
+        .await;
+    let mut response = ctx.resp_mut().await;
+    for value in pending.split('\n').filter(|value| !value.is_empty()) {
+        response.append(header::SET_COOKIE, value)?;
+    }
+    Ok(())
 }
 
 #[async_trait]
@@ -200,7 +395,7 @@ impl<S: State> Cookier for Context<S> {
             None => {
                 let www_authenticate = format!(
                     r#"Cookie name="{}""#,
-                    utf8_percent_encode(name, NON_ALPHANUMERIC).to_string()
+                    utf8_percent_encode(name, NON_ALPHANUMERIC)
                 );
                 self.resp_mut().await.headers.insert(
                     header::WWW_AUTHENTICATE,
@@ -215,19 +410,47 @@ impl<S: State> Cookier for Context<S> {
             .await
             .map(|var| var.into_value())
     }
+    async fn cookies(&self) -> HashMap<String, String> {
+        self.bucket::<CookieSymbol>()
+            .await
+            .map(|bucket| bucket.into_inner())
+            .unwrap_or_default()
+    }
     async fn set_cookie(&mut self, cookie: Cookie<'_>) -> Result {
         let cookie_value = cookie.encoded().to_string();
-        self.resp_mut()
-            .await
-            .append(header::SET_COOKIE, cookie_value)?;
+        match self.load::<SetCookieSymbol>(PENDING).await {
+            Some(pending) => {
+                let mut pending = pending.into_value();
+                if !pending.is_empty() {
+                    pending.push('\n');
+                }
+                pending.push_str(&cookie_value);
+                // pending cookies belong to the response, never discarded with a scope.
+                self.store_unscoped::<SetCookieSymbol>(PENDING, pending)
+                    .await;
+            }
+            None => {
+                self.resp_mut()
+                    .await
+                    .append(header::SET_COOKIE, cookie_value)?;
+            }
+        }
         Ok(())
     }
+    async fn remove_cookie(&mut self, mut cookie: Cookie<'_>) -> Result {
+        cookie.set_value("");
+        cookie.set_max_age(Duration::zero());
+        self.set_cookie(cookie).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{cookie_parser, Cookie, Cookier};
-    use crate::core::App;
+    use super::{
+        cookie_parser, Cookie, CookieParser, Cookier, DEFAULT_MAX_COUNT,
+        DEFAULT_MAX_SIZE,
+    };
+    use crate::core::{throw, App};
     use async_std::task::spawn;
     use http::{header, StatusCode};
 
@@ -322,6 +545,116 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn cookies() -> Result<(), Box<dyn std::error::Error>> {
+        // miss cookie
+        let (addr, server) = App::new(())
+            .gate(cookie_parser)
+            .end(move |ctx| async move {
+                assert!(ctx.cookies().await.is_empty());
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        // multiple cookies in one header
+        let (addr, server) = App::new(())
+            .gate(cookie_parser)
+            .end(move |mut ctx| async move {
+                let cookies = ctx.cookies().await;
+                assert_eq!(3, cookies.len());
+                assert_eq!("Hexilee", cookies["name"]);
+                assert_eq!("bar baz", cookies["bar baz"]);
+                assert_eq!("1", cookies["id"]);
+                assert_eq!("Hexilee", ctx.must_cookie("name").await?);
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(header::COOKIE, "name=Hexilee; bar%20baz=bar%20baz; id=1")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cookie_limits() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(cookie_parser)
+            .end(move |_ctx| async move { Ok(()) })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+
+        // pathologically large cookie header
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(
+                header::COOKIE,
+                format!("name={}", "x".repeat(DEFAULT_MAX_SIZE)),
+            )
+            .send()
+            .await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        assert_eq!(
+            format!("cookie header is larger than {} bytes", DEFAULT_MAX_SIZE),
+            resp.text().await?
+        );
+
+        // too many cookies
+        let cookies: Vec<String> = (0..=DEFAULT_MAX_COUNT)
+            .map(|i| format!("{}={}", i, i))
+            .collect();
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(header::COOKIE, cookies.join("; "))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        assert_eq!(
+            format!(
+                "cookie header contains more than {} cookies",
+                DEFAULT_MAX_COUNT
+            ),
+            resp.text().await?
+        );
+
+        // configured limits
+        let (addr, server) = App::new(())
+            .gate(CookieParser::builder().max_size(16).max_count(1).build())
+            .end(move |mut ctx| async move {
+                assert_eq!("1", ctx.must_cookie("id").await?);
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(header::COOKIE, "id=1")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(header::COOKIE, "id=1; name=Hexilee")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(header::COOKIE, "name=Hexilee-Hexilee")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn set_cookie() -> Result<(), Box<dyn std::error::Error>> {
         let (addr, server) = App::new(())
@@ -342,4 +675,107 @@ mod tests {
         assert_eq!(("foo%20baz"), cookies[1].value());
         Ok(())
     }
+
+    #[tokio::test]
+    async fn flush_cookies() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(cookie_parser)
+            .gate_fn(|mut ctx, next| async move {
+                next().await?;
+                // nothing is appended to response before flushing
+                assert!(!ctx.resp().await.headers.contains_key(header::SET_COOKIE));
+                ctx.set_cookie(Cookie::new("outer", "1")).await?;
+                Ok(())
+            })
+            .end(move |mut ctx| async move {
+                let name = ctx.must_cookie("name").await?;
+                ctx.set_cookie(Cookie::new("name", name)).await?;
+                ctx.set_cookie(Cookie::new("id", "1")).await?;
+                ctx.remove_cookie(Cookie::named("session")).await?;
+                if ctx.uri().await.path() == "/fail" {
+                    throw!(StatusCode::BAD_REQUEST, "fail")
+                }
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(header::COOKIE, "name=Hexilee")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let set_cookies: Vec<&str> = resp
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect();
+        assert_eq!(
+            vec!["name=Hexilee", "id=1", "session=; Max-Age=0", "outer=1"],
+            set_cookies
+        );
+
+        // flushed even if downstream failed
+        let resp = client
+            .get(&format!("http://{}/fail", addr))
+            .header(header::COOKIE, "name=Hexilee")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        assert_eq!(3, resp.headers().get_all(header::SET_COOKIE).iter().count());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn scoped_set_cookie() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(cookie_parser)
+            .gate_fn(|mut ctx, next| async move { ctx.scoped(next).await })
+            .end(move |mut ctx| async move {
+                ctx.set_cookie(Cookie::new("session", "1")).await?;
+                ctx.set_cookie(Cookie::new("theme", "dark")).await?;
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let cookies: Vec<_> =
+            resp.headers().get_all(header::SET_COOKIE).iter().collect();
+        assert_eq!(vec!["session=1", "theme=dark"], cookies);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn remove_cookie() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(move |mut ctx| async move {
+                ctx.set_cookie(Cookie::new("id", "1")).await?;
+                let mut cookie = Cookie::named("bar baz");
+                cookie.set_path("/user");
+                cookie.set_domain("github.com");
+                ctx.remove_cookie(cookie).await?;
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let set_cookies: Vec<&str> = resp
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                "id=1",
+                "bar%20baz=; Path=/user; Domain=github.com; Max-Age=0"
+            ],
+            set_cookies
+        );
+        Ok(())
+    }
 }
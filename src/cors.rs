@@ -83,6 +83,30 @@ use typed_builder::TypedBuilder;
 ///     .credentials(false)
 ///     .build();
 /// ```
+///
+/// ### Preflight
+///
+/// Preflight requests are answered by this middleware directly and never reach downstream,
+/// so a route only registered for GET can still pass preflight as long as `Cors` is used before the router.
+/// The "access-control-allow-methods" of preflight response is populated by `allow_methods`,
+/// keep it consistent with methods your router accepts.
+/// Other OPTIONS requests pass through to downstream, see `Router::auto_options`.
+///
+/// ```rust
+/// use roa::cors::Cors;
+/// use roa::router::Router;
+/// use roa::core::App;
+/// use http::Method;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut router = Router::<()>::new();
+/// router.get("/", |_ctx| async { Ok(()) });
+/// App::new(())
+///     .gate(Cors::builder().allow_methods(vec![Method::GET]).build())
+///     .gate(router.routes("/")?);
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Debug, TypedBuilder)]
 pub struct Cors {
     #[builder(default)]
@@ -186,7 +210,8 @@ impl<S: State> Middleware<S> for Cors {
             }
             next().await
         } else {
-            // Preflight Request
+            // Preflight Request, terminate here without calling next,
+            // so downstream router won't respond 405 for OPTIONS.
             // Set "Access-Control-Max-Age"
             ctx.resp_mut()
                 .await
@@ -209,7 +234,7 @@ impl<S: State> Middleware<S> for Cors {
                 }
             }
 
-            // Try to set "Access-Control-Allow-Methods"
+            // Try to set "Access-Control-Allow-Headers"
             if !allow_headers.is_empty() {
                 ctx.resp_mut()
                     .await
@@ -228,6 +253,7 @@ mod tests {
     use super::Cors;
     use crate::core::App;
     use crate::preload::*;
+    use crate::router::Router;
     use async_std::task::spawn;
     use http::header::{
         ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
@@ -236,7 +262,7 @@ mod tests {
         ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, CONTENT_TYPE,
         ORIGIN, VARY,
     };
-    use http::{HeaderValue, StatusCode};
+    use http::{HeaderValue, Method, StatusCode};
 
     #[tokio::test]
     async fn default_cors() -> Result<(), Box<dyn std::error::Error>> {
@@ -287,7 +313,7 @@ mod tests {
 
         // options, no Access-Control-Request-Method
         let resp = client
-            .request(http::Method::OPTIONS, &format!("http://{}", addr))
+            .request(Method::OPTIONS, &format!("http://{}", addr))
             .header(ORIGIN, "github.com")
             .send()
             .await?;
@@ -301,7 +327,7 @@ mod tests {
 
         // options, contains Access-Control-Request-Method
         let resp = client
-            .request(http::Method::OPTIONS, &format!("http://{}", addr))
+            .request(Method::OPTIONS, &format!("http://{}", addr))
             .header(ORIGIN, "github.com")
             .header(ACCESS_CONTROL_REQUEST_METHOD, "GET, POST")
             .header(
@@ -350,4 +376,54 @@ mod tests {
         //
         Ok(())
     }
+
+    #[tokio::test]
+    async fn preflight_before_router() -> Result<(), Box<dyn std::error::Error>> {
+        let mut router = Router::<()>::new();
+        router.get("/", |mut ctx| async move {
+            ctx.write_text("Hello, World").await
+        });
+        let (addr, server) = App::new(())
+            .gate(Cors::builder().allow_methods(vec![Method::GET]).build())
+            .gate(router.routes("/")?)
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+
+        // preflight never reaches router
+        let resp = client
+            .request(Method::OPTIONS, &format!("http://{}", addr))
+            .header(ORIGIN, "github.com")
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NO_CONTENT, resp.status());
+        assert_eq!(
+            "GET",
+            resp.headers()
+                .get(ACCESS_CONTROL_ALLOW_METHODS)
+                .unwrap()
+                .to_str()?
+        );
+
+        // options without Access-Control-Request-Method is not a preflight,
+        // pass to router and get answered by auto options
+        let resp = client
+            .request(Method::OPTIONS, &format!("http://{}", addr))
+            .header(ORIGIN, "github.com")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NO_CONTENT, resp.status());
+        assert_eq!("GET, OPTIONS", resp.headers()[http::header::ALLOW]);
+
+        // simple request
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header(ORIGIN, "github.com")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("Hello, World", resp.text().await?);
+        Ok(())
+    }
 }
@@ -0,0 +1,253 @@
+//! The dispatch module of roa.
+//! This module provides a middleware `JsonDispatch`
+//! to dispatch requests by a field of json body,
+//! like webhooks sending different types of events to the same endpoint.
+
+use crate::body::PowerBody;
+use crate::core::header::CONTENT_TYPE;
+use crate::core::{
+    async_trait, Context, Error, Middleware, Next, Result, State, StatusCode,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A middleware to dispatch requests by a field of json body,
+/// returned by `dispatch_on_json_field`.
+pub struct JsonDispatch<S> {
+    field: String,
+    handlers: HashMap<String, Arc<dyn Middleware<S>>>,
+    fallback: Option<Arc<dyn Middleware<S>>>,
+}
+
+/// Dispatch requests to `handlers` by the value of `field` in json body.
+///
+/// The `field` is a top-level key, or a json pointer like "/event/type" for a nested one.
+/// String values are matched as they are, other values are matched by their json text,
+/// like "1" or "true".
+/// Requests whose value is missing or unmatched are passed to downstream,
+/// or to the middleware set by `JsonDispatch::fallback`.
+///
+/// Requests with an empty body or a non-json "Content-Type" are treated as unmatched,
+/// a request without "Content-Type" is read as json.
+/// The body is buffered and written back before dispatching,
+/// so handlers can read the original bytes again, to verify a signature for example.
+/// Throw 400 BAD REQUEST if the body of a json request is not valid json.
+///
+/// Handlers may call `next` to continue the downstream,
+/// or respond by themselves as endpoints.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::dispatch::dispatch_on_json_field;
+/// use roa::core::{App, Context, Middleware, Result};
+/// use async_std::task::spawn;
+/// use http::StatusCode;
+/// use std::collections::HashMap;
+/// use std::sync::Arc;
+///
+/// async fn push(mut ctx: Context<()>) -> Result {
+///     ctx.resp_mut().await.write_str("push received");
+///     Ok(())
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///     let mut handlers: HashMap<String, Arc<dyn Middleware<()>>> = HashMap::new();
+///     handlers.insert("push".to_string(), Arc::new(push as fn(_) -> _));
+///     let (addr, server) = App::new(())
+///         .gate(dispatch_on_json_field("type", handlers))
+///         .end(|_ctx| async { Ok(()) })
+///         .run_local()?;
+///     spawn(server);
+///     let resp = reqwest::Client::new()
+///         .post(&format!("http://{}", addr))
+///         .body(r#"{"type": "push"}"#)
+///         .send()
+///         .await?;
+///     assert_eq!(StatusCode::OK, resp.status());
+///     assert_eq!("push received", resp.text().await?);
+///     Ok(())
+/// }
+/// ```
+pub fn dispatch_on_json_field<S: State>(
+    field: impl ToString,
+    handlers: HashMap<String, Arc<dyn Middleware<S>>>,
+) -> JsonDispatch<S> {
+    JsonDispatch {
+        field: field.to_string(),
+        handlers,
+        fallback: None,
+    }
+}
+
+impl<S: State> JsonDispatch<S> {
+    /// Dispatch requests whose value is missing or unmatched to `fallback`,
+    /// instead of passing them to downstream.
+    pub fn fallback(mut self, fallback: impl Middleware<S>) -> Self {
+        self.fallback = Some(Arc::new(fallback));
+        self
+    }
+
+    /// Extract the value of field as a key of handlers.
+    fn key(&self, body: &Value) -> Option<String> {
+        let value = if self.field.starts_with('/') {
+            body.pointer(&self.field)
+        } else {
+            body.get(&self.field)
+        }?;
+        match value {
+            Value::String(value) => Some(value.clone()),
+            Value::Null => None,
+            value => Some(value.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for JsonDispatch<S> {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, next: Next) -> Result {
+        let has_type = ctx.req().await.headers.contains_key(CONTENT_TYPE);
+        let mut key = None;
+        if !has_type || ctx.is_json().await {
+            let data = ctx.body_buf().await?;
+            if !data.is_empty() {
+                let body: Value = serde_json::from_slice(&data).map_err(|err| {
+                    Error::new(
+                        StatusCode::BAD_REQUEST,
+                        format!("{}\ninvalid body", err),
+                        true,
+                    )
+                })?;
+                key = self.key(&body);
+            }
+            ctx.req_mut().await.write_bytes(data);
+        }
+        let handler = key
+            .and_then(|key| self.handlers.get(&key))
+            .or(self.fallback.as_ref());
+        match handler {
+            Some(handler) => handler.clone().handle(ctx, next).await,
+            None => next().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dispatch_on_json_field;
+    use crate::body::PowerBody;
+    use crate::core::{App, Context, Middleware, Result};
+    use async_std::task::spawn;
+    use http::header::CONTENT_TYPE;
+    use http::StatusCode;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    async fn push(mut ctx: Context<()>) -> Result {
+        // the original body can be read again
+        let body = ctx.read_text().await?;
+        ctx.resp_mut().await.write_str(format!("push: {}", body));
+        Ok(())
+    }
+
+    async fn issue(mut ctx: Context<()>) -> Result {
+        ctx.resp_mut().await.write_str("issue");
+        Ok(())
+    }
+
+    async fn unknown(mut ctx: Context<()>) -> Result {
+        ctx.resp_mut().await.write_str("unknown");
+        Ok(())
+    }
+
+    fn handlers() -> HashMap<String, Arc<dyn Middleware<()>>> {
+        let mut handlers: HashMap<String, Arc<dyn Middleware<()>>> = HashMap::new();
+        handlers.insert("push".to_string(), Arc::new(push as fn(_) -> _));
+        handlers.insert("issue".to_string(), Arc::new(issue as fn(_) -> _));
+        handlers
+    }
+
+    #[tokio::test]
+    async fn dispatch() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(dispatch_on_json_field("type", handlers()))
+            .end(|mut ctx| async move {
+                ctx.resp_mut().await.write_str("downstream");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+        let body = r#"{"type":"push", "ref": "main"}"#;
+        let resp = client.post(&url).body(body).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(format!("push: {}", body), resp.text().await?);
+
+        let resp = client.post(&url).body(r#"{"type":"issue"}"#).send().await?;
+        assert_eq!("issue", resp.text().await?);
+
+        let resp = client.post(&url).body(r#"{"type":"star"}"#).send().await?;
+        assert_eq!("downstream", resp.text().await?);
+
+        let resp = client
+            .post(&url)
+            .body(r#"{"action":"push"}"#)
+            .send()
+            .await?;
+        assert_eq!("downstream", resp.text().await?);
+
+        let resp = client.get(&url).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("downstream", resp.text().await?);
+
+        let resp = client
+            .post(&url)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body("type=push")
+            .send()
+            .await?;
+        assert_eq!("downstream", resp.text().await?);
+
+        let resp = client.post(&url).body("type=push").send().await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+
+        let resp = client
+            .post(&url)
+            .header(CONTENT_TYPE, "application/json")
+            .body("type=push")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fallback() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(
+                dispatch_on_json_field("/event/type", handlers())
+                    .fallback(unknown as fn(_) -> _),
+            )
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+        let resp = client
+            .post(&url)
+            .body(r#"{"event": {"type": "issue"}}"#)
+            .send()
+            .await?;
+        assert_eq!("issue", resp.text().await?);
+
+        let resp = client
+            .post(&url)
+            .body(r#"{"event": {"type": 1}}"#)
+            .send()
+            .await?;
+        assert_eq!("unknown", resp.text().await?);
+        Ok(())
+    }
+}
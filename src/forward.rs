@@ -1,11 +1,15 @@
 //! The forward module of roa.
 //! This module provides a context extension `Forward`,
-//! which is used to parse `X-Forwarded-*` request headers.
+//! which is used to parse `X-Forwarded-*` request headers,
+//! and a middleware `RequireHttps` based on it.
 
-use crate::core::header::HOST;
-use crate::core::{async_trait, throw, Context, Result, State, StatusCode};
+use crate::core::header::{HOST, LOCATION};
+use crate::core::{
+    async_trait, throw, Context, Middleware, Next, Result, State, StatusCode,
+};
 use crate::preload::*;
 use std::net::IpAddr;
+use std::sync::Arc;
 
 /// A context extension `Forward` used to parse `X-Forwarded-*` request headers.
 #[async_trait]
@@ -122,12 +126,103 @@ impl<S: State> Forward for Context<S> {
     }
 }
 
+/// A middleware to redirect plain HTTP requests to HTTPS, returned by `require_https`.
+pub struct RequireHttps {
+    skipped: Vec<String>,
+}
+
+/// Redirect requests arrived over plain HTTP to the `https://` equivalent
+/// with 301 MOVED PERMANENTLY, the downstream middleware will not be called.
+///
+/// The protocol is the first one of "x-forwarded-proto" if it's set,
+/// otherwise `Context::scheme()`; the host is `Forward::host`.
+/// Note that clients may change the method of a redirected non-GET request to GET.
+///
+/// Paths registered by `RequireHttps::skip`, like health checks, are never redirected.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::forward::require_https;
+/// use roa::core::App;
+/// use roa::core::header::LOCATION;
+/// use async_std::task::spawn;
+/// use http::StatusCode;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let (addr, server) = App::new(())
+///         .gate(require_https().skip("/health"))
+///         .run_local()?;
+///     spawn(server);
+///     let client = reqwest::Client::builder()
+///         .redirect(reqwest::redirect::Policy::none())
+///         .build()?;
+///     let resp = client
+///         .get(&format!("http://{}/user?id=0", addr))
+///         .header("x-forwarded-proto", "http")
+///         .header("x-forwarded-host", "example.com")
+///         .send()
+///         .await?;
+///     assert_eq!(StatusCode::MOVED_PERMANENTLY, resp.status());
+///     assert_eq!("https://example.com/user?id=0", resp.headers()[LOCATION]);
+///
+///     let resp = client.get(&format!("http://{}/health", addr)).send().await?;
+///     assert_eq!(StatusCode::OK, resp.status());
+///     Ok(())
+/// }
+/// ```
+pub fn require_https() -> RequireHttps {
+    RequireHttps {
+        skipped: Vec::new(),
+    }
+}
+
+impl RequireHttps {
+    /// Never redirect requests to this path.
+    pub fn skip(mut self, path: impl ToString) -> Self {
+        self.skipped.push(path.to_string());
+        self
+    }
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for RequireHttps {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, next: Next) -> Result {
+        let uri = ctx.uri().await;
+        if self.skipped.iter().any(|path| path == uri.path()) {
+            return next().await;
+        }
+        let proto = match ctx.forwarded_proto().await {
+            Some(proto) => proto?,
+            None => ctx.scheme().to_string(),
+        };
+        let is_https = proto
+            .split(',')
+            .next()
+            .map(|proto| proto.trim().eq_ignore_ascii_case("https"))
+            .unwrap_or(false);
+        if is_https {
+            return next().await;
+        }
+        let path_and_query = uri
+            .path_and_query()
+            .map(|path_and_query| path_and_query.as_str())
+            .unwrap_or("/");
+        let location = format!("https://{}{}", ctx.host().await?, path_and_query);
+        let mut response = ctx.resp_mut().await;
+        response.status = StatusCode::MOVED_PERMANENTLY;
+        response.insert(LOCATION, location)?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Forward;
+    use super::{require_https, Forward};
     use crate::core::App;
     use async_std::task::spawn;
-    use http::header::HOST;
+    use http::header::{HOST, LOCATION};
     use http::{HeaderValue, StatusCode};
 
     #[tokio::test]
@@ -222,4 +317,54 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn require_https_redirect() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(require_https().skip("/health"))
+            .end(|mut ctx| async move {
+                ctx.resp_mut().await.write_str("secure");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+
+        // plain http
+        let resp = client
+            .get(&format!("http://{}/user?id=0", addr))
+            .header(HOST, "example.com")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::MOVED_PERMANENTLY, resp.status());
+        assert_eq!("https://example.com/user?id=0", resp.headers()[LOCATION]);
+
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header("x-forwarded-proto", "http")
+            .header("x-forwarded-host", "example.com")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::MOVED_PERMANENTLY, resp.status());
+        assert_eq!("https://example.com/", resp.headers()[LOCATION]);
+
+        // https
+        let resp = client
+            .get(&format!("http://{}/user", addr))
+            .header("x-forwarded-proto", "HTTPS, http")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("secure", resp.text().await?);
+
+        // health check
+        let resp = client
+            .get(&format!("http://{}/health", addr))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
 }
@@ -0,0 +1,446 @@
+//! The idempotency module of roa.
+//! This module provides a middleware `Idempotency` and a pluggable `IdempotencyStore`.
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::idempotency::Idempotency;
+//! use roa::core::App;
+//! use async_std::task::spawn;
+//! use http::StatusCode;
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::sync::Arc;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let counter = Arc::new(AtomicUsize::new(0));
+//!     let count = counter.clone();
+//!     let (addr, server) = App::new(())
+//!         .gate(Idempotency::builder().build())
+//!         .gate_fn(move |mut ctx, _next| {
+//!             let id = count.fetch_add(1, Ordering::SeqCst);
+//!             async move {
+//!                 ctx.resp_mut().await.status = StatusCode::CREATED;
+//!                 ctx.resp_mut().await.write_str(format!("payment {}", id));
+//!                 Ok(())
+//!             }
+//!         })
+//!         .run_local()?;
+//!     spawn(server);
+//!     let client = reqwest::Client::new();
+//!     for _ in 0..2 {
+//!         let resp = client
+//!             .post(&format!("http://{}", addr))
+//!             .header("idempotency-key", "8e03978e")
+//!             .send()
+//!             .await?;
+//!         assert_eq!(StatusCode::CREATED, resp.status());
+//!         assert_eq!("payment 0", resp.text().await?);
+//!     }
+//!     assert_eq!(1, counter.load(Ordering::SeqCst));
+//!     Ok(())
+//! }
+//! ```
+
+use crate::core::header::{HeaderMap, HeaderValue};
+use crate::core::{
+    async_trait, BodyKind, Context, Error, Middleware, Next, Response, Result, State,
+    StatusCode,
+};
+use crate::preload::*;
+use async_std::sync::{Arc, RwLock};
+use futures::AsyncReadExt;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use typed_builder::TypedBuilder;
+
+/// Name of the request header carrying idempotency key.
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// Name of the response header marking a replayed response.
+pub const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
+/// A buffered response stored by `IdempotencyStore`.
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    /// Status code.
+    pub status: StatusCode,
+
+    /// Raw header map.
+    pub headers: HeaderMap<HeaderValue>,
+
+    /// Buffered body.
+    pub body: Vec<u8>,
+}
+
+/// A storage of responses by idempotency key.
+///
+/// Implement it to share responses across processes, in redis for example.
+#[async_trait]
+pub trait IdempotencyStore: 'static + Sync + Send {
+    /// Get an unexpired response by key.
+    async fn get(&self, key: &str) -> Option<CachedResponse>;
+
+    /// Store a response, it should expire after ttl.
+    async fn set(&self, key: String, response: CachedResponse, ttl: Duration);
+}
+
+/// The default in-memory `IdempotencyStore`.
+///
+/// Expired responses are removed lazily, once the number of stored responses
+/// doubles since the last sweep, so storing a response is O(1) amortized
+/// and the memory usage is bounded by twice of responses stored within a ttl.
+#[derive(Default)]
+pub struct MemoryStore(RwLock<MemoryResponses>);
+
+/// Responses of `MemoryStore` and the size to sweep expired ones.
+#[derive(Default)]
+struct MemoryResponses {
+    responses: HashMap<String, (Instant, CachedResponse)>,
+    sweep_at: usize,
+}
+
+/// Min size to sweep expired responses of `MemoryStore`.
+const MIN_SWEEP_SIZE: usize = 1024;
+
+#[async_trait]
+impl IdempotencyStore for MemoryStore {
+    async fn get(&self, key: &str) -> Option<CachedResponse> {
+        match self.0.read().await.responses.get(key) {
+            Some((deadline, response)) if *deadline > Instant::now() => {
+                Some(response.clone())
+            }
+            _ => None,
+        }
+    }
+
+    async fn set(&self, key: String, response: CachedResponse, ttl: Duration) {
+        let now = Instant::now();
+        let mut store = self.0.write().await;
+        if store.responses.len() >= store.sweep_at.max(MIN_SWEEP_SIZE) {
+            store.responses.retain(|_, (deadline, _)| *deadline > now);
+            store.sweep_at = store.responses.len() * 2;
+        }
+        store.responses.insert(key, (now + ttl, response));
+    }
+}
+
+/// A middleware to replay the response of a request with the same "Idempotency-Key".
+///
+/// Requests without "Idempotency-Key" are passed through.
+/// Keys are scoped by method and path, the same key on another endpoint is another key.
+/// Only successful (2xx) responses with buffered body are stored,
+/// responses with streaming body (like a file) are never stored.
+/// A replayed response has a header "Idempotent-Replayed: true",
+/// it keeps headers set by upstream before this middleware (like cors or request id),
+/// stored headers are added only if upstream doesn't set them.
+///
+/// ### Tradeoffs
+///
+/// Every stored response is kept in the store for `ttl` (24 hours by default),
+/// so the default `MemoryStore` may take much memory under heavy traffic:
+/// use a shorter ttl or a custom external store.
+///
+/// Concurrent requests with the same key are not serialized,
+/// both of them may reach downstream before any response is stored.
+///
+/// ### Config
+///
+/// ```rust
+/// use roa::idempotency::{Idempotency, MemoryStore};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let idempotency = Idempotency::builder()
+///     .store(Arc::new(MemoryStore::default()))
+///     .ttl(Duration::from_secs(60))
+///     .build();
+/// ```
+#[derive(TypedBuilder)]
+pub struct Idempotency {
+    /// Storage of responses.
+    #[builder(default = Arc::new(MemoryStore::default()))]
+    store: Arc<dyn IdempotencyStore>,
+
+    /// How long a response is stored.
+    #[builder(default = Duration::from_secs(24 * 60 * 60))]
+    ttl: Duration,
+}
+
+/// Take and buffer a successful response with buffered body.
+pub(crate) async fn buffer<S: State>(
+    ctx: &mut Context<S>,
+) -> Result<Option<CachedResponse>> {
+    let mut response = ctx.resp_mut().await;
+    if !response.status.is_success() {
+        return Ok(None);
+    }
+    if let BodyKind::Stream = response.kind() {
+        return Ok(None);
+    }
+    let mut body = Vec::new();
+    std::mem::take(&mut **response)
+        .read_to_end(&mut body)
+        .await?;
+    Ok(Some(CachedResponse {
+        status: response.status,
+        headers: response.headers.clone(),
+        body,
+    }))
+}
+
+/// Write a buffered response, keeping headers already set on `response`.
+pub(crate) fn replay(response: &mut Response, cached: CachedResponse) {
+    response.status = cached.status;
+    for name in cached.headers.keys() {
+        if !response.headers.contains_key(name) {
+            for value in cached.headers.get_all(name) {
+                response.headers.append(name, value.clone());
+            }
+        }
+    }
+    response.write_bytes(cached.body);
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for Idempotency {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, next: Next) -> Result {
+        // the request guard must be released before `next`,
+        // or downstream reading the request body waits for it forever.
+        let key = match ctx.header(IDEMPOTENCY_KEY).await {
+            Some(key) => {
+                key.map_err(|err| Error::new(StatusCode::BAD_REQUEST, err, true))?
+            }
+            None => return next().await,
+        };
+        // scope the key by endpoint, a key of an endpoint never replays another one.
+        let key = format!("{} {} {}", ctx.method().await, ctx.uri().await.path(), key);
+        if let Some(cached) = self.store.get(&key).await {
+            let mut response = ctx.resp_mut().await;
+            replay(&mut response, cached);
+            response.insert(IDEMPOTENT_REPLAYED, "true")?;
+            return Ok(());
+        }
+        next().await?;
+        if let Some(cached) = buffer(&mut ctx).await? {
+            ctx.resp_mut().await.write_bytes(cached.body.clone());
+            self.store.set(key, cached, self.ttl).await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Idempotency, IdempotencyStore, MemoryStore, IDEMPOTENT_REPLAYED};
+    use crate::core::{App, StatusCode};
+    use async_std::task::spawn;
+    use http::header::{HeaderValue, CONTENT_TYPE};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn replay() -> Result<(), Box<dyn std::error::Error>> {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let count = counter.clone();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (addr, server) = App::new(())
+            .gate_fn(move |mut ctx, next| {
+                let request_id = requests.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    ctx.resp_mut()
+                        .await
+                        .headers
+                        .insert("x-request-id", HeaderValue::from(request_id));
+                    next().await
+                }
+            })
+            .gate(Idempotency::builder().build())
+            .gate_fn(move |mut ctx, _next| {
+                let id = count.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    ctx.resp_mut().await.status = StatusCode::CREATED;
+                    ctx.resp_mut()
+                        .await
+                        .headers
+                        .insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+                    ctx.resp_mut().await.write_str(id.to_string());
+                    Ok(())
+                }
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+
+        let resp = client
+            .post(&url)
+            .header("idempotency-key", "a")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::CREATED, resp.status());
+        assert!(resp.headers().get(IDEMPOTENT_REPLAYED).is_none());
+        assert_eq!("0", resp.text().await?);
+
+        let resp = client
+            .post(&url)
+            .header("idempotency-key", "a")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::CREATED, resp.status());
+        assert_eq!("true", resp.headers()[IDEMPOTENT_REPLAYED]);
+        assert_eq!("text/plain", resp.headers()[CONTENT_TYPE]);
+        // the request id of this request, not of the replayed one
+        assert_eq!("1", resp.headers()["x-request-id"]);
+        assert_eq!("0", resp.text().await?);
+
+        let resp = client
+            .post(&url)
+            .header("idempotency-key", "b")
+            .send()
+            .await?;
+        assert_eq!("1", resp.text().await?);
+
+        // without key
+        let resp = client.post(&url).send().await?;
+        assert_eq!("2", resp.text().await?);
+        let resp = client.post(&url).send().await?;
+        assert_eq!("3", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn scoped_key() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(Idempotency::builder().build())
+            .end(|mut ctx| async move {
+                let path = ctx.uri().await.path().to_string();
+                ctx.resp_mut().await.write_str(path);
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        for path in ["/a", "/b"].iter() {
+            let resp = client
+                .post(&format!("http://{}{}", addr, path))
+                .header("idempotency-key", "key")
+                .send()
+                .await?;
+            assert!(resp.headers().get(IDEMPOTENT_REPLAYED).is_none());
+            assert_eq!(*path, resp.text().await?);
+        }
+        let resp = client
+            .put(&format!("http://{}/a", addr))
+            .header("idempotency-key", "key")
+            .send()
+            .await?;
+        assert!(resp.headers().get(IDEMPOTENT_REPLAYED).is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_body_without_key() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::body::PowerBody;
+        let (addr, server) = App::new(())
+            .gate(Idempotency::builder().build())
+            .end(|mut ctx| async move {
+                let body = ctx.read_text().await?;
+                ctx.write_text(body).await
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::Client::new()
+            .post(&format!("http://{}", addr))
+            .body("Hello, World")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("Hello, World", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn skip_failed_and_streaming() -> Result<(), Box<dyn std::error::Error>> {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let count = counter.clone();
+        let (addr, server) = App::new(())
+            .gate(Idempotency::builder().build())
+            .gate_fn(move |mut ctx, _next| {
+                count.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if ctx.uri().await.path() == "/stream" {
+                        ctx.resp_mut().await.write_buf(b"stream".as_ref());
+                    } else {
+                        ctx.resp_mut().await.status = StatusCode::BAD_REQUEST;
+                    }
+                    Ok(())
+                }
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        for _ in 0..2 {
+            let resp = client
+                .post(&format!("http://{}/stream", addr))
+                .header("idempotency-key", "stream")
+                .send()
+                .await?;
+            assert_eq!(StatusCode::OK, resp.status());
+            assert_eq!("stream", resp.text().await?);
+
+            let resp = client
+                .post(&format!("http://{}/fail", addr))
+                .header("idempotency-key", "fail")
+                .send()
+                .await?;
+            assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        }
+        assert_eq!(4, counter.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn memory_store_expire() {
+        let store = MemoryStore::default();
+        let response = super::CachedResponse {
+            status: StatusCode::OK,
+            headers: Default::default(),
+            body: b"ok".to_vec(),
+        };
+        store
+            .set("a".to_string(), response.clone(), Duration::from_millis(0))
+            .await;
+        assert!(store.get("a").await.is_none());
+        store
+            .set("b".to_string(), response, Duration::from_secs(60))
+            .await;
+        assert_eq!(
+            b"ok".as_ref(),
+            store.get("b").await.unwrap().body.as_slice()
+        );
+    }
+
+    #[async_std::test]
+    async fn memory_store_sweep() {
+        let store = MemoryStore::default();
+        let response = super::CachedResponse {
+            status: StatusCode::OK,
+            headers: Default::default(),
+            body: b"ok".to_vec(),
+        };
+        for i in 0..super::MIN_SWEEP_SIZE {
+            store
+                .set(i.to_string(), response.clone(), Duration::from_millis(0))
+                .await;
+        }
+        // expired responses are kept until the sweep size is reached
+        assert_eq!(super::MIN_SWEEP_SIZE, store.0.read().await.responses.len());
+        store
+            .set("a".to_string(), response, Duration::from_secs(60))
+            .await;
+        assert_eq!(1, store.0.read().await.responses.len());
+        assert!(store.get("a").await.is_some());
+    }
+}
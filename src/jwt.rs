@@ -66,10 +66,11 @@
 
 pub use jsonwebtoken::Validation;
 
-use crate::core::header::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use crate::core::header::{HeaderValue, WWW_AUTHENTICATE};
 use crate::core::{
     async_trait, join, Context, Error, Middleware, Next, Result, State, StatusCode,
 };
+use crate::header::{Authorization, FriendlyHeaders};
 use jsonwebtoken::{dangerous_unsafe_decode, decode};
 use serde::de::DeserializeOwned;
 use serde_json::Value;
@@ -165,12 +166,9 @@ fn guard_not_set() -> Error {
 }
 
 async fn try_get_token<S: State>(ctx: &Context<S>) -> Result<String> {
-    match ctx.header(AUTHORIZATION).await {
-        None | Some(Err(_)) => Err(unauthorized("")),
-        Some(Ok(value)) => match value.find("Bearer") {
-            None => Err(unauthorized("")),
-            Some(n) => Ok(value[n + 6..].trim().to_string()),
-        },
+    match ctx.req().await.authorization() {
+        Some(Ok(Authorization::Bearer(token))) => Ok(token),
+        _ => Err(unauthorized("")),
     }
 }
 
@@ -213,25 +213,50 @@
 //! ### Other modules
 //!
 //! - body: dealing with body more conviniently.
+//! - budget: a total time budget of requests.
+//! - cache: a middleware to cache responses.
+//! - canary: a middleware to split traffic for canary deployments.
 //! - compress: supports transparent content compression.
 //! - cors: CORS support.
+//! - dispatch: a middleware to dispatch requests by a field of json body.
 //! - forward: "X-Forwarded-*" parser.
 //! - header: dealing with headers more conviniently.
 //! - jwt: json web token support.
 //! - logger: a logger middleware.
+//! - method: a middleware to reject unsafe methods.
+//! - request_id: a middleware to assign an id to each request.
+//! - shedding: a middleware to shed load.
+//! - signed_url: time-limited urls signed by HMAC.
+//! - status: helpers to set common status codes.
+//! - tap: a middleware to inspect bodies for debugging.
+//! - trace: a middleware to open a tracing span per request.
+//! - version: a middleware to reject old http versions.
 
 #![warn(missing_docs)]
 
 pub use roa_core as core;
+pub mod budget;
+pub mod cache;
+pub mod canary;
 pub mod cors;
 pub mod forward;
 pub mod header;
+pub mod idempotency;
 pub mod logger;
+pub mod method;
+pub mod precondition;
 pub mod query;
+pub mod request_id;
+pub mod shedding;
+pub mod status;
+pub mod version;
 
 #[cfg(feature = "body")]
 pub mod body;
 
+#[cfg(feature = "body")]
+pub mod dispatch;
+
 #[cfg(feature = "cookies")]
 pub mod cookie;
 
@@ -244,11 +269,24 @@ pub mod router;
 #[cfg(feature = "compress")]
 pub mod compress;
 
+#[cfg(feature = "tracing")]
+pub mod trace;
+
+#[cfg(feature = "tap")]
+pub mod tap;
+
+#[cfg(feature = "signed-url")]
+pub mod signed_url;
+
 /// Reexport all extensional traits.
 pub mod preload {
+    pub use crate::budget::TimeBudget;
     pub use crate::forward::Forward;
-    pub use crate::header::FriendlyHeaders;
+    pub use crate::header::{ContextHeaders, FriendlyHeaders};
+    pub use crate::precondition::Precondition;
     pub use crate::query::Query;
+    pub use crate::request_id::RequestId;
+    pub use crate::status::ResponseStatus;
 
     #[cfg(feature = "body")]
     pub use crate::body::PowerBody;
@@ -262,3 +300,42 @@ pub mod preload {
     #[cfg(feature = "router")]
     pub use crate::router::RouterParam;
 }
+
+/// Reexport all extensional traits in `preload` and core types used by typical apps.
+///
+/// Note that `Result` is `roa::core::Result`, which shadows `std::result::Result`.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::prelude::*;
+/// use async_std::task::spawn;
+///
+/// async fn get(ctx: Context<()>) -> Result {
+///     if ctx.query("id").await.is_none() {
+///         throw!(StatusCode::BAD_REQUEST, "id is required")
+///     }
+///     Ok(())
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///     let (addr, server) = App::new(())
+///         .gate(roa::query::query_parser)
+///         .end(get)
+///         .run_local()?;
+///     spawn(server);
+///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+///     assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+///     let resp = reqwest::get(&format!("http://{}?id=0", addr)).await?;
+///     assert_eq!(StatusCode::OK, resp.status());
+///     Ok(())
+/// }
+/// ```
+pub mod prelude {
+    pub use crate::core::{
+        throw, App, Context, Error, IntoError, Middleware, Model, Next, Result, State,
+        StatusCode,
+    };
+    pub use crate::preload::*;
+}
@@ -1,10 +1,14 @@
 //! The logger module of roa.
 //! This module provides a middleware `logger`.
 //!
+//! Install `request_id` before `logger` to correlate log lines of the same request,
+//! each line is prefixed with "[{request id}] ".
+//!
 //! ### Example
 //!
 //! ```rust
 //! use roa::logger::logger;
+//! use roa::request_id::request_id;
 //! use roa::body::PowerBody;
 //! use roa::core::{App, StatusCode};
 //! use async_std::task::spawn;
@@ -13,6 +17,7 @@
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     pretty_env_logger::init();
 //!     let (addr, server) = App::new(())
+//!         .gate(request_id)
 //!         .gate(logger)
 //!         .end(|mut ctx| async move {
 //!             ctx.write_text("Hello, World!").await
@@ -26,6 +31,7 @@
 //! ```
 
 use crate::core::{Body, BodyCallback, Context, Model, Next, Result};
+use crate::request_id::RequestId;
 use bytesize::ByteSize;
 use log::{error, info};
 use std::time::Instant;
@@ -34,11 +40,17 @@ use std::time::Instant;
 ///
 /// Based on crate `log`, the log level must be greater than `INFO` to log all information,
 /// and should be greater than `ERROR` when you need error information only.
+///
+/// If middleware `request_id` runs before it, each line is prefixed with the request id.
 pub async fn logger<M: Model>(mut ctx: Context<M>, next: Next) -> Result {
     let start = Instant::now();
     let method = ctx.method().await;
     let uri = ctx.uri().await;
-    info!("--> {} {}", method, uri.path());
+    let id = match ctx.request_id().await {
+        Some(id) => format!("[{}] ", id),
+        None => String::new(),
+    };
+    info!("{}--> {} {}", id, method, uri.path());
     let path = uri.path().to_string();
     let result = next().await;
     let callback: Box<BodyCallback> = match result {
@@ -46,7 +58,8 @@ pub async fn logger<M: Model>(mut ctx: Context<M>, next: Next) -> Result {
             let status_code = ctx.status().await;
             Box::new(move |body: &Body| {
                 info!(
-                    "<-- {} {} {}ms {} {}",
+                    "{}<-- {} {} {}ms {} {}",
+                    id,
                     method,
                     path,
                     start.elapsed().as_millis(),
@@ -60,7 +73,8 @@ pub async fn logger<M: Model>(mut ctx: Context<M>, next: Next) -> Result {
             let status_code = status.status_code;
             Box::new(move |_| {
                 error!(
-                    "<-- {} {} {}ms {}\n{}",
+                    "{}<-- {} {} {}ms {}\n{}",
+                    id,
                     method,
                     path,
                     start.elapsed().as_millis(),
@@ -78,6 +92,7 @@ pub async fn logger<M: Model>(mut ctx: Context<M>, next: Next) -> Result {
 mod tests {
     use super::logger;
     use crate::core::{throw, App};
+    use crate::request_id::{request_id, REQUEST_ID};
     use async_std::task::spawn;
     use http::StatusCode;
     use lazy_static::lazy_static;
@@ -152,6 +167,23 @@ mod tests {
         assert_eq!("ERROR", records[3].0);
         assert!(records[3].1.starts_with("<-- GET /"));
         assert!(records[3].1.ends_with("Hello, World!"));
+
+        // with request id
+        let (addr, server) = App::new(())
+            .gate_fn(request_id)
+            .gate_fn(logger)
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::Client::new()
+            .get(&format!("http://{}", addr))
+            .header(REQUEST_ID, "abc")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let records = LOGGER.records.read().unwrap().clone();
+        assert_eq!(6, records.len());
+        assert_eq!("[abc] --> GET /", records[4].1);
+        assert!(records[5].1.starts_with("[abc] <-- GET /"));
         Ok(())
     }
 }
@@ -0,0 +1,149 @@
+//! The method module of roa.
+//! This module provides a middleware `ReadOnly` to reject unsafe methods.
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::method::read_only;
+//! use roa::core::App;
+//! use async_std::task::spawn;
+//! use http::StatusCode;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let (addr, server) = App::new(())
+//!         .gate(read_only())
+//!         .end(|mut ctx| async move {
+//!             ctx.resp_mut().await.write_str("Hello, World");
+//!             Ok(())
+//!         })
+//!         .run_local()?;
+//!     spawn(server);
+//!     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+//!     assert_eq!(StatusCode::OK, resp.status());
+//!     let resp = reqwest::Client::new()
+//!         .delete(&format!("http://{}", addr))
+//!         .send()
+//!         .await?;
+//!     assert_eq!(StatusCode::METHOD_NOT_ALLOWED, resp.status());
+//!     Ok(())
+//! }
+//! ```
+
+use crate::core::header::{HeaderValue, ALLOW};
+use crate::core::{
+    async_trait, throw, Context, Middleware, Next, Result, State, StatusCode,
+};
+use http::Method;
+use std::sync::Arc;
+
+/// Methods passed by `ReadOnly`.
+const SAFE_METHODS: [Method; 3] = [Method::GET, Method::HEAD, Method::OPTIONS];
+
+/// A middleware to reject unsafe methods, returned by `read_only`.
+pub struct ReadOnly;
+
+/// Throw 405 METHOD NOT ALLOWED with "Allow: GET, HEAD, OPTIONS"
+/// to requests whose method is not one of them, others are passed to downstream.
+///
+/// It's a defense in depth for a read-only sub-tree, regardless of what is routed.
+/// Both the original method and the current one are checked,
+/// so a request cannot sneak an unsafe method in or out by method overriding.
+pub fn read_only() -> ReadOnly {
+    ReadOnly
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for ReadOnly {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, next: Next) -> Result {
+        let current = ctx.method().await;
+        let unsafe_method = vec![ctx.original_method().clone(), current]
+            .into_iter()
+            .find(|method| !SAFE_METHODS.contains(method));
+        if let Some(method) = unsafe_method {
+            ctx.resp_mut()
+                .await
+                .headers
+                .insert(ALLOW, HeaderValue::from_static("GET, HEAD, OPTIONS"));
+            throw!(
+                StatusCode::METHOD_NOT_ALLOWED,
+                format!("method {} is not allowed", method)
+            );
+        }
+        next().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_only;
+    use crate::core::App;
+    use async_std::task::spawn;
+    use http::{Method, StatusCode};
+
+    #[tokio::test]
+    async fn reject_unsafe() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(read_only())
+            .end(|mut ctx| async move {
+                ctx.resp_mut().await.write_str("Hello, World");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let resp = client.get(&format!("http://{}", addr)).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("Hello, World", resp.text().await?);
+
+        let resp = client.head(&format!("http://{}", addr)).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        let resp = client.post(&format!("http://{}", addr)).send().await?;
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, resp.status());
+        assert_eq!("GET, HEAD, OPTIONS", resp.headers()["allow"]);
+        assert_eq!("method POST is not allowed", resp.text().await?);
+
+        let resp = client
+            .request(Method::from_bytes(b"PURGE")?, &format!("http://{}", addr))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn overridden_method() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate_fn(|mut ctx, next| async move {
+                // a naive method overriding
+                let header = ctx.header("x-http-method-override").await;
+                if let Some(Ok(method)) = header {
+                    if let Ok(method) = Method::from_bytes(method.as_bytes()) {
+                        ctx.req_mut().await.method = method;
+                    }
+                }
+                next().await
+            })
+            .gate(read_only())
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let resp = client
+            .get(&format!("http://{}", addr))
+            .header("x-http-method-override", "DELETE")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, resp.status());
+        assert_eq!("method DELETE is not allowed", resp.text().await?);
+
+        let resp = client
+            .post(&format!("http://{}", addr))
+            .header("x-http-method-override", "GET")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, resp.status());
+        assert_eq!("method POST is not allowed", resp.text().await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,380 @@
+//! The precondition module of roa.
+//! This module provides a context extension `Precondition`,
+//! which is used to evaluate conditional request headers against an entity tag
+//! or a modification time.
+
+use crate::core::header::{
+    ETAG, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use crate::core::{async_trait, throw, Context, Result, State, StatusCode};
+use crate::header::split_list;
+use crate::preload::*;
+use http::Method;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A context extension `Precondition` used to evaluate conditional request headers.
+#[async_trait]
+pub trait Precondition {
+    /// Check `If-Match` and `If-None-Match` against the current entity tag of the resource,
+    /// pass `None` if the resource does not exist.
+    ///
+    /// - If "if-match" is `*`, it matches only when the resource exists.
+    /// - If "if-match" is a list of tags, one of them must strongly match the current tag.
+    /// - If "if-none-match" is `*`, it fails when the resource exists.
+    /// - If "if-none-match" is a list of tags, it fails when one of them weakly matches the current tag.
+    ///
+    /// Throw Err(412 PRECONDITION FAILED) if any precondition fails,
+    /// except that on a failed "if-none-match" of GET or HEAD,
+    /// the response status is set to 304 NOT MODIFIED and `Ok(true)` is returned,
+    /// as `Precondition::not_modified_if` does.
+    /// Throw Err(400 BAD REQUEST) if one of headers fails to string.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa::core::{App, Context, Result};
+    /// use roa::core::header::IF_MATCH;
+    /// use roa::precondition::Precondition;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// async fn update(mut ctx: Context<()>) -> Result {
+    ///     if ctx.check_precondition(Some(r#""v1""#)).await? {
+    ///         return Ok(());
+    ///     }
+    ///     // update the resource...
+    ///     Ok(())
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(()).end(update).run_local()?;
+    ///     spawn(server);
+    ///     let client = reqwest::Client::new();
+    ///     let resp = client
+    ///         .put(&format!("http://{}", addr))
+    ///         .header(IF_MATCH, r#""v0""#)
+    ///         .send()
+    ///         .await?;
+    ///     assert_eq!(StatusCode::PRECONDITION_FAILED, resp.status());
+    ///
+    ///     let resp = client
+    ///         .put(&format!("http://{}", addr))
+    ///         .header(IF_MATCH, r#""v0", "v1""#)
+    ///         .send()
+    ///         .await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn check_precondition(&mut self, current_etag: Option<&str>) -> Result<bool>;
+
+    /// Set "etag" and "last-modified" of the response,
+    /// then check `If-None-Match` and `If-Modified-Since` of GET or HEAD against them.
+    ///
+    /// - If "if-none-match" exists, it matches when one of tags weakly matches `etag`.
+    /// - Otherwise, "if-modified-since" matches when `last_modified` is not later than it,
+    ///   an invalid date is ignored.
+    ///
+    /// On a match, the response status is set to 304 NOT MODIFIED and `Ok(true)` is returned.
+    /// Handlers are expected to return early before generating the body,
+    /// so a cache hit costs only computing the validators;
+    /// upstream middleware like logger sees it as a successful response.
+    /// Throw Err(400 BAD REQUEST) if one of headers fails to string.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use roa::core::{App, Context, Result};
+    /// use roa::core::header::{ETAG, IF_NONE_MATCH};
+    /// use roa::precondition::Precondition;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// async fn report(mut ctx: Context<()>) -> Result {
+    ///     // compute validators cheaply, e.g. from a version number.
+    ///     if ctx.not_modified_if(Some(r#""v1""#), None).await? {
+    ///         return Ok(());
+    ///     }
+    ///     // only reached on a cache miss.
+    ///     ctx.resp_mut().await.write_str("an expensive report");
+    ///     Ok(())
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(()).end(report).run_local()?;
+    ///     spawn(server);
+    ///     let client = reqwest::Client::new();
+    ///     let resp = client.get(&format!("http://{}", addr)).send().await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     assert_eq!(r#""v1""#, resp.headers()[ETAG]);
+    ///
+    ///     let resp = client
+    ///         .get(&format!("http://{}", addr))
+    ///         .header(IF_NONE_MATCH, r#""v1""#)
+    ///         .send()
+    ///         .await?;
+    ///     assert_eq!(StatusCode::NOT_MODIFIED, resp.status());
+    ///     assert_eq!("", resp.text().await?);
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn not_modified_if(
+        &mut self,
+        etag: Option<&str>,
+        last_modified: Option<SystemTime>,
+    ) -> Result<bool>;
+}
+
+/// Compare two entity tags, weak tags never match in strong comparison.
+fn etag_matches(tag: &str, current: &str, weak: bool) -> bool {
+    if weak {
+        tag.trim_start_matches("W/") == current.trim_start_matches("W/")
+    } else {
+        !tag.starts_with("W/") && !current.starts_with("W/") && tag == current
+    }
+}
+
+/// Evaluate a list of entity tags or `*`.
+pub(crate) fn any_matches(header: &str, current: Option<&str>, weak: bool) -> bool {
+    match current {
+        None => false,
+        Some(current) => {
+            split_list(header).any(|tag| tag == "*" || etag_matches(tag, current, weak))
+        }
+    }
+}
+
+/// Truncate `time` to seconds, the precision of http dates.
+fn truncate_secs(time: SystemTime) -> SystemTime {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[async_trait]
+impl<S: State> Precondition for Context<S> {
+    async fn check_precondition(&mut self, current_etag: Option<&str>) -> Result<bool> {
+        if let Some(if_match) = self.req().await.get(IF_MATCH) {
+            if !any_matches(if_match?, current_etag, false) {
+                throw!(StatusCode::PRECONDITION_FAILED)
+            }
+        }
+        let not_modified = match self.req().await.get(IF_NONE_MATCH) {
+            Some(if_none_match) => any_matches(if_none_match?, current_etag, true),
+            None => false,
+        };
+        if not_modified {
+            match self.method().await {
+                Method::GET | Method::HEAD => (),
+                _ => throw!(StatusCode::PRECONDITION_FAILED),
+            }
+            self.resp_mut().await.status = StatusCode::NOT_MODIFIED;
+        }
+        Ok(not_modified)
+    }
+
+    async fn not_modified_if(
+        &mut self,
+        etag: Option<&str>,
+        last_modified: Option<SystemTime>,
+    ) -> Result<bool> {
+        let last_modified = last_modified.map(truncate_secs);
+        {
+            let mut resp = self.resp_mut().await;
+            if let Some(etag) = etag {
+                resp.insert(ETAG, etag)?;
+            }
+            if let Some(time) = last_modified {
+                resp.insert(LAST_MODIFIED, httpdate::fmt_http_date(time))?;
+            }
+        }
+        match self.method().await {
+            Method::GET | Method::HEAD => (),
+            _ => return Ok(false),
+        }
+        let req = self.req().await;
+        let matched = match req.get(IF_NONE_MATCH) {
+            Some(if_none_match) => any_matches(if_none_match?, etag, true),
+            None => match (req.get(IF_MODIFIED_SINCE), last_modified) {
+                (Some(since), Some(time)) => httpdate::parse_http_date(since?)
+                    .map(|since| time <= since)
+                    .unwrap_or(false),
+                _ => false,
+            },
+        };
+        drop(req);
+        if matched {
+            self.resp_mut().await.status = StatusCode::NOT_MODIFIED;
+        }
+        Ok(matched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{any_matches, Precondition};
+    use crate::core::App;
+    use async_std::task::spawn;
+    use http::header::{
+        ETAG, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    };
+    use http::StatusCode;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn matches() {
+        assert!(any_matches("*", Some(r#""a""#), false));
+        assert!(!any_matches("*", None, false));
+        assert!(any_matches(r#""b", "a""#, Some(r#""a""#), false));
+        assert!(!any_matches(r#"W/"a""#, Some(r#""a""#), false));
+        assert!(any_matches(r#"W/"a""#, Some(r#""a""#), true));
+        assert!(!any_matches(r#""b""#, Some(r#""a""#), true));
+        assert!(any_matches(r#""b", "a,c""#, Some(r#""a,c""#), false));
+        assert!(!any_matches(r#""a,c""#, Some(r#""a""#), true));
+    }
+
+    #[tokio::test]
+    async fn check_precondition() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(|mut ctx| async move {
+                if !ctx.check_precondition(Some(r#""v1""#)).await? {
+                    ctx.resp_mut().await.write_str("updated");
+                }
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+
+        let resp = client.put(&url).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        let resp = client.put(&url).header(IF_MATCH, "*").send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        let resp = client.put(&url).header(IF_MATCH, r#""v0""#).send().await?;
+        assert_eq!(StatusCode::PRECONDITION_FAILED, resp.status());
+
+        let resp = client.put(&url).header(IF_NONE_MATCH, "*").send().await?;
+        assert_eq!(StatusCode::PRECONDITION_FAILED, resp.status());
+
+        let resp = client
+            .put(&url)
+            .header(IF_NONE_MATCH, r#""v0""#)
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        let resp = client
+            .get(&url)
+            .header(IF_NONE_MATCH, r#"W/"v1""#)
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NOT_MODIFIED, resp.status());
+        assert_eq!("", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resource_not_exists() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(|mut ctx| async move {
+                ctx.check_precondition(None).await?;
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+
+        let resp = client.put(&url).header(IF_MATCH, "*").send().await?;
+        assert_eq!(StatusCode::PRECONDITION_FAILED, resp.status());
+
+        let resp = client.put(&url).header(IF_NONE_MATCH, "*").send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn not_modified_if() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate_fn(|_ctx, next| async move {
+                // a not modified response is not an error for upstream middlewares.
+                let result = next().await;
+                assert!(result.is_ok());
+                result
+            })
+            .end(|mut ctx| async move {
+                let mtime = UNIX_EPOCH + Duration::from_millis(784_111_777_500);
+                if ctx.not_modified_if(Some(r#""v1""#), Some(mtime)).await? {
+                    assert_eq!(StatusCode::NOT_MODIFIED, ctx.status().await);
+                    return Ok(());
+                }
+                ctx.resp_mut().await.write_str("Hello, World");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+
+        let resp = client.get(&url).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(r#""v1""#, resp.headers()[ETAG]);
+        assert_eq!(
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            resp.headers()[LAST_MODIFIED]
+        );
+        assert_eq!("Hello, World", resp.text().await?);
+
+        let resp = client
+            .get(&url)
+            .header(IF_NONE_MATCH, r#""v0", W/"v1""#)
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NOT_MODIFIED, resp.status());
+        assert_eq!(r#""v1""#, resp.headers()[ETAG]);
+        assert_eq!("", resp.text().await?);
+
+        let resp = client
+            .get(&url)
+            .header(IF_MODIFIED_SINCE, "Sun, 06 Nov 1994 08:49:37 GMT")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NOT_MODIFIED, resp.status());
+
+        let resp = client
+            .get(&url)
+            .header(IF_MODIFIED_SINCE, "Sun, 06 Nov 1994 08:49:36 GMT")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        // if-none-match takes precedence over if-modified-since.
+        let resp = client
+            .get(&url)
+            .header(IF_NONE_MATCH, r#""v0""#)
+            .header(IF_MODIFIED_SINCE, "Sun, 06 Nov 1994 08:49:37 GMT")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        let resp = client
+            .get(&url)
+            .header(IF_MODIFIED_SINCE, "invalid date")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        let resp = client
+            .post(&url)
+            .header(IF_NONE_MATCH, r#""v1""#)
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+}
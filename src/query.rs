@@ -23,15 +23,33 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! ### Arrays
+//!
+//! `Query::query` gets the last value of a key, use `Query::query_all` to get all of them.
+//! Supported syntaxes of arrays are:
+//!
+//! - repeated keys: `?ids=1&ids=2`.
+//! - keys with brackets: `?ids[]=1&ids[]=2`, query them by "ids".
+//! - comma-separated values: `?ids=1,2`, only if `QueryParser::split_comma` is enabled.
+//!
+//! They can be mixed, `?ids=1,2&ids[]=3` makes `[1, 2, 3]` with comma-splitting.
 
 use crate::core::{
-    async_trait, Context, Error, Next, Result, State, StatusCode, Variable,
+    async_trait, Context, Error, Middleware, Next, Result, State, StatusCode, Variable,
 };
-use url::form_urlencoded::parse;
+use std::collections::HashMap;
+use std::sync::Arc;
+use typed_builder::TypedBuilder;
+use url::form_urlencoded::{parse, Serializer};
 
 /// A unique symbol to store and load variables in Context::storage.
 struct QuerySymbol;
 
+/// A unique symbol to store and load arrays in Context::storage,
+/// values of each array are urlencoded as context storage only holds strings.
+struct QueryArraySymbol;
+
 /// A context extension.
 /// This extension must be used in downstream of middleware `query_parser`,
 /// otherwise you cannot get expected query variable.
@@ -123,16 +141,141 @@ pub trait Query {
     /// }
     /// ```
     async fn query<'a>(&self, name: &'a str) -> Option<Variable<'a>>;
+
+    /// Query all values of a variable in order, return an empty vector if it not exists.
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::query::{query_parser, Query};
+    /// use roa::core::{App, Result, StatusCode};
+    /// use async_std::task::spawn;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .gate(query_parser)
+    ///         .end( |ctx| async move {
+    ///             let ids = ctx
+    ///                 .query_all("ids")
+    ///                 .await
+    ///                 .iter()
+    ///                 .map(|id| id.parse())
+    ///                 .collect::<Result<Vec<u64>>>()?;
+    ///             assert_eq!(vec![1, 2], ids);
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}?ids[]=1&ids[]=2", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn query_all<'a>(&self, name: &'a str) -> Vec<Variable<'a>>;
+
+    /// Get the raw query string without leading '?', return an empty string if there is no query.
+    ///
+    /// It doesn't require the middleware `query_parser`.
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::query::Query;
+    /// use roa::core::{App, StatusCode};
+    /// use async_std::task::spawn;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (addr, server) = App::new(())
+    ///         .end( |ctx| async move {
+    ///             assert_eq!("name=Hexilee&lang=rust", ctx.query_string().await);
+    ///             Ok(())
+    ///         })
+    ///         .run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}?name=Hexilee&lang=rust", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn query_string(&self) -> String;
 }
 
-/// A middleware to parse query.
+/// A middleware to parse query, values are never comma-split.
+///
+/// Use `QueryParser` to configure it.
 pub async fn query_parser<S: State>(mut ctx: Context<S>, next: Next) -> Result {
+    parse_query(&mut ctx, false).await;
+    next().await
+}
+
+/// A configurable middleware to parse query.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::query::{Query, QueryParser};
+/// use roa::core::{App, StatusCode};
+/// use async_std::task::spawn;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let (addr, server) = App::new(())
+///         .gate(QueryParser::builder().split_comma(true).build())
+///         .end( |ctx| async move {
+///             let tags = ctx.query_all("tags").await;
+///             let tags: Vec<&str> = tags.iter().map(|tag| tag.as_ref()).collect();
+///             assert_eq!(vec!["rust", "web"], tags);
+///             Ok(())
+///         })
+///         .run_local()?;
+///     spawn(server);
+///     let resp = reqwest::get(&format!("http://{}?tags=rust,web", addr)).await?;
+///     assert_eq!(StatusCode::OK, resp.status());
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, TypedBuilder)]
+pub struct QueryParser {
+    /// Split values by comma for `Query::query_all`, empty segments are skipped.
+    /// `Query::query` still gets the whole value.
+    #[builder(default)]
+    split_comma: bool,
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for QueryParser {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, next: Next) -> Result {
+        parse_query(&mut ctx, self.split_comma).await;
+        next().await
+    }
+}
+
+async fn parse_query<S: State>(ctx: &mut Context<S>, split_comma: bool) {
     let uri = ctx.uri().await;
     let query_string = uri.query().unwrap_or("");
+    let mut arrays = HashMap::<String, Vec<String>>::new();
     for (key, value) in parse(query_string.as_bytes()) {
+        let array = arrays
+            .entry(key.trim_end_matches("[]").to_string())
+            .or_default();
+        if split_comma {
+            array.extend(
+                value
+                    .split(',')
+                    .filter(|item| !item.is_empty())
+                    .map(ToString::to_string),
+            );
+        } else {
+            array.push(value.to_string());
+        }
         ctx.store::<QuerySymbol>(&key, value.to_string()).await;
     }
-    next().await
+    for (key, array) in arrays {
+        let encoded = Serializer::new(String::new())
+            .extend_pairs(array.iter().map(|value| ("", value)))
+            .finish();
+        ctx.store::<QueryArraySymbol>(&key, encoded).await;
+    }
 }
 
 #[async_trait]
@@ -149,12 +292,26 @@ impl<S: State> Query for Context<S> {
     async fn query<'a>(&self, name: &'a str) -> Option<Variable<'a>> {
         self.load::<QuerySymbol>(name).await
     }
+
+    async fn query_all<'a>(&self, name: &'a str) -> Vec<Variable<'a>> {
+        match self.load::<QueryArraySymbol>(name).await {
+            Some(array) => parse(array.as_bytes())
+                .map(|(_, value)| Variable::new(name, value.to_string()))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    async fn query_string(&self) -> String {
+        self.uri().await.query().unwrap_or("").to_string()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{query_parser, Query};
+    use super::{query_parser, Query, QueryParser};
     use crate::core::App;
+    use crate::header::FriendlyHeaders;
     use async_std::task::spawn;
     use http::StatusCode;
 
@@ -228,4 +385,97 @@ mod tests {
         assert_eq!(StatusCode::OK, resp.status());
         Ok(())
     }
+
+    #[tokio::test]
+    async fn query_array() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(query_parser)
+            .end(|ctx| async move {
+                let ids = ctx
+                    .query_all("ids")
+                    .await
+                    .iter()
+                    .map(|id| id.parse())
+                    .collect::<crate::core::Result<Vec<u64>>>()?;
+                assert_eq!(vec![1, 2, 3], ids);
+                assert_eq!("3", ctx.must_query("ids[]").await?.as_ref());
+                let names = ctx.query_all("name").await;
+                assert_eq!(1, names.len());
+                assert_eq!("a,b", names[0].as_ref());
+                assert!(ctx.query_all("tags").await.is_empty());
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!(
+            "http://{}?ids=1&ids%5B%5D=2&ids[]=3&name=a,b",
+            addr
+        ))
+        .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        // invalid value
+        let (addr, server) = App::new(())
+            .gate(query_parser)
+            .end(|ctx| async move {
+                ctx.query_all("ids")
+                    .await
+                    .iter()
+                    .map(|id| id.parse())
+                    .collect::<crate::core::Result<Vec<u64>>>()?;
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}?ids=1&ids=x", addr)).await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn query_split_comma() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(QueryParser::builder().split_comma(true).build())
+            .end(|ctx| async move {
+                let tags = ctx.query_all("tags").await;
+                let tags: Vec<&str> = tags.iter().map(|tag| tag.as_ref()).collect();
+                assert_eq!(vec!["a", "b", "c", "d", "e"], tags);
+                assert_eq!("d,e", ctx.must_query("tags").await?.as_ref());
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp =
+            reqwest::get(&format!("http://{}?tags=a,,b&tags[]=c,&tags=d,e", addr))
+                .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn query_string() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(|ctx| async move {
+                let expected = ctx.req().await.must_get("x-expected")?.to_string();
+                assert_eq!(expected, ctx.query_string().await);
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        for (path, expected) in &[
+            ("/", ""),
+            ("/?", ""),
+            ("/?name=Hexilee&name=rust", "name=Hexilee&name=rust"),
+            ("/user?msg=%E4%BD%A0%E5%A5%BD", "msg=%E4%BD%A0%E5%A5%BD"),
+        ] {
+            let resp = client
+                .get(&format!("http://{}{}", addr, path))
+                .header("x-expected", *expected)
+                .send()
+                .await?;
+            assert_eq!(StatusCode::OK, resp.status());
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,147 @@
+//! The request_id module of roa.
+//! This module provides a middleware `request_id` and a context extension `RequestId`.
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::request_id::{request_id, REQUEST_ID};
+//! use roa::logger::logger;
+//! use roa::preload::*;
+//! use roa::core::App;
+//! use async_std::task::spawn;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let (addr, server) = App::new(())
+//!         .gate(request_id)
+//!         .gate(logger)
+//!         .end(|ctx| async move {
+//!             assert!(ctx.request_id().await.is_some());
+//!             Ok(())
+//!         })
+//!         .run_local()?;
+//!     spawn(server);
+//!     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+//!     assert!(resp.headers().contains_key(REQUEST_ID));
+//!     Ok(())
+//! }
+//! ```
+
+use crate::core::{async_trait, Context, Next, Result, State};
+use crate::preload::*;
+use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the header carrying request id.
+pub const REQUEST_ID: &str = "x-request-id";
+
+/// Max length of a request id accepted from client.
+const MAX_LENGTH: usize = 128;
+
+/// A unique symbol to store and load request id in Context::storage.
+struct RequestIdSymbol;
+
+/// Generate a request id unique within this process,
+/// prefixed by the process start time to distinguish restarts.
+fn generate() -> String {
+    lazy_static! {
+        static ref BOOT: u128 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos())
+            .unwrap_or_default();
+    }
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+    format!("{:x}-{:x}", *BOOT, SEQUENCE.fetch_add(1, Ordering::Relaxed))
+}
+
+/// A middleware to assign an id to each request.
+///
+/// The id is taken from request header "X-Request-Id" if it's a visible ASCII string
+/// no longer than 128 bytes, otherwise a new one is generated.
+/// The id is stored in context, and echoed in response header "X-Request-Id".
+///
+/// Place it before `logger` so every log line carries the id.
+pub async fn request_id<S: State>(mut ctx: Context<S>, next: Next) -> Result {
+    let id = match ctx.header(REQUEST_ID).await {
+        Some(Ok(id))
+            if !id.is_empty()
+                && id.len() <= MAX_LENGTH
+                && id.bytes().all(|byte| byte.is_ascii_graphic()) =>
+        {
+            id
+        }
+        _ => generate(),
+    };
+    ctx.store::<RequestIdSymbol>("id", id.clone()).await;
+    ctx.resp_mut().await.insert(REQUEST_ID, id)?;
+    next().await
+}
+
+/// A context extension to get request id.
+#[async_trait]
+pub trait RequestId {
+    /// Get request id, None if middleware `request_id` is not installed.
+    async fn request_id(&self) -> Option<String>;
+}
+
+#[async_trait]
+impl<S: State> RequestId for Context<S> {
+    async fn request_id(&self) -> Option<String> {
+        self.load::<RequestIdSymbol>("id")
+            .await
+            .map(|id| id.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{request_id, RequestId, REQUEST_ID};
+    use crate::core::App;
+    use async_std::task::spawn;
+
+    #[tokio::test]
+    async fn assign() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(request_id)
+            .end(|mut ctx| async move {
+                let id = ctx.request_id().await.unwrap();
+                ctx.resp_mut().await.write_str(id);
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+
+        // take from header
+        let resp = client.get(&url).header(REQUEST_ID, "abc").send().await?;
+        assert_eq!("abc", resp.headers()[REQUEST_ID]);
+        assert_eq!("abc", resp.text().await?);
+
+        // generate
+        let first = client.get(&url).send().await?.text().await?;
+        let second = client.get(&url).header(REQUEST_ID, "a b").send().await?;
+        let header = second.headers()[REQUEST_ID].to_str()?.to_string();
+        let second = second.text().await?;
+        assert_eq!(header, second);
+        assert_ne!("a b", second);
+        assert_ne!(first, second);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn absent() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(|ctx| async move {
+                assert!(ctx.request_id().await.is_none());
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert!(resp.status().is_success());
+        assert!(!resp.headers().contains_key(REQUEST_ID));
+        Ok(())
+    }
+}
@@ -28,17 +28,36 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! ### Path patterns
+//!
+//! - `:name` matches a whole non-empty segment without whitespace, like `/user/:id`.
+//! - `*{name}` matches a non-empty string without whitespace, slashes included,
+//!   and it can be embedded in a segment, like `/static/*{path}` or `/file-*{name}.txt`.
+//! - Variable names consist of word characters, and must be unique in a path.
+//! - Other characters are matched literally, regex syntax is never interpreted.
+//!
+//! Dynamic paths are compiled to regular expressions of crate regex,
+//! which match in linear time without backtracking, so they are not exposed to ReDoS.
+//! The number of variables of each path can be limited by `Router::max_captures`.
 
+mod cache;
 mod err;
 mod path;
+mod strip;
 
+use cache::MatchCache;
 use err::{Conflict, RouterError};
 use path::{join_path, standardize_path, Path, RegexPath};
+pub use strip::{strip_prefix, StripPrefix};
 
 use crate::core::{
     async_trait, join_all, throw, Context, Error, Middleware, Next, Result, State,
     StatusCode, Variable,
 };
+use crate::header::FriendlyHeaders;
+use async_std::future::timeout;
+use http::header::ALLOW;
 use http::Method;
 use percent_encoding::percent_decode_str;
 use radix_trie::Trie;
@@ -47,6 +66,7 @@ use std::convert::AsRef;
 use std::future::Future;
 use std::result::Result as StdResult;
 use std::sync::Arc;
+use std::time::Duration;
 
 const ALL_METHODS: [Method; 9] = [
     Method::GET,
@@ -131,18 +151,32 @@ pub trait RouterParam {
 pub struct Router<S: State> {
     middlewares: Vec<Arc<dyn Middleware<S>>>,
     endpoints: Vec<(Method, String, Arc<dyn Middleware<S>>)>,
+    match_cache: usize,
+    auto_options: bool,
+    max_captures: usize,
 }
 
 struct RouteTable<S: State> {
     static_route: Trie<String, Arc<dyn Middleware<S>>>,
     dynamic_route: Vec<(RegexPath, Arc<dyn Middleware<S>>)>,
+    cache: Option<MatchCache>,
 }
 
 /// A endpoint to handle request by uri path and http method.
 ///
 /// - Throw 404 NOT FOUND when path is not matched.
 /// - Throw 405 METHOD NOT ALLOWED when method is not allowed.
-pub struct RouteEndpoint<S: State>(HashMap<Method, RouteTable<S>>);
+/// - Respond 204 NO CONTENT with "Allow" to OPTIONS requests without an OPTIONS route,
+///   see `Router::auto_options`.
+///
+/// Static paths are matched first. Dynamic paths are matched by specificity
+/// regardless of registration order: paths with less wildcards first,
+/// then paths with more static segments, then paths whose earlier segments are static.
+pub struct RouteEndpoint<S: State> {
+    tables: HashMap<Method, RouteTable<S>>,
+    auto_options: bool,
+    max_captures: usize,
+}
 
 impl<S: State> Router<S> {
     /// Construct a new router.
@@ -150,10 +184,158 @@ impl<S: State> Router<S> {
         Self {
             middlewares: Vec::new(),
             endpoints: Vec::new(),
+            match_cache: 0,
+            auto_options: true,
+            max_captures: 0,
         }
     }
 
+    /// Cache matched dynamic routes of at most `capacity` recently used paths,
+    /// so repeated requests to the same path skip regex evaluation.
+    ///
+    /// A cached entry keeps its router parameters, and the least recently used one
+    /// is evicted when the cache is full. Each method has its own cache.
+    /// Static routes are never cached as they are matched by a trie.
+    ///
+    /// It's disabled by default, and only takes effect on the router calling `Router::routes`.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::router::{Router, RouterParam};
+    /// use roa::core::App;
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut router = Router::<()>::new();
+    ///     router.match_cache(1024).get("/user/:id", |ctx| async move {
+    ///         assert_eq!("0", &*ctx.must_param("id").await?);
+    ///         Ok(())
+    ///     });
+    ///     let (addr, server) = App::new(()).gate(router.routes("/")?).run_local()?;
+    ///     spawn(server);
+    ///     for _ in 0..2 {
+    ///         let resp = reqwest::get(&format!("http://{}/user/0", addr)).await?;
+    ///         assert_eq!(StatusCode::OK, resp.status());
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn match_cache(&mut self, capacity: usize) -> &mut Self {
+        self.match_cache = capacity;
+        self
+    }
+
+    /// Limit the number of variables of each dynamic path to `max`,
+    /// `Router::routes` returns an error once a path exceeds it.
+    ///
+    /// Matching never backtracks catastrophically,
+    /// as paths are compiled to regular expressions guaranteeing linear time,
+    /// see the module document for constraints on paths.
+    /// However, the cost of extracting variables grows with their number,
+    /// so it's a safety net against pathological route definitions, like generated ones.
+    ///
+    /// It's unlimited by default,
+    /// and only takes effect on the router calling `Router::routes`.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::router::Router;
+    ///
+    /// let mut router = Router::<()>::new();
+    /// router
+    ///     .max_captures(2)
+    ///     .get("/:year/:month/:day", |_ctx| async { Ok(()) });
+    /// assert!(router.routes("/").is_err());
+    /// ```
+    pub fn max_captures(&mut self, max: usize) -> &mut Self {
+        self.max_captures = max;
+        self
+    }
+
+    /// Respond OPTIONS requests automatically, enabled by default.
+    ///
+    /// An OPTIONS request to a path without an OPTIONS route gets 204 NO CONTENT,
+    /// with an "Allow" header listing methods registered for the path and OPTIONS itself,
+    /// or 404 NOT FOUND if no method is registered for the path.
+    /// Router gates are skipped as no route is matched.
+    ///
+    /// It works with or without `Cors`: preflight requests are answered by `Cors` used
+    /// before the router, other OPTIONS requests pass through it and are answered here.
+    ///
+    /// It only takes effect on the router calling `Router::routes`.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::router::Router;
+    /// use roa::core::App;
+    /// use roa::core::header::ALLOW;
+    /// use async_std::task::spawn;
+    /// use http::{Method, StatusCode};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut router = Router::<()>::new();
+    ///     router
+    ///         .get("/user", |_ctx| async { Ok(()) })
+    ///         .post("/user", |_ctx| async { Ok(()) });
+    ///     let (addr, server) = App::new(()).gate(router.routes("/")?).run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::Client::new()
+    ///         .request(Method::OPTIONS, &format!("http://{}/user", addr))
+    ///         .send()
+    ///         .await?;
+    ///     assert_eq!(StatusCode::NO_CONTENT, resp.status());
+    ///     assert_eq!("GET, POST, OPTIONS", resp.headers()[ALLOW]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn auto_options(&mut self, enabled: bool) -> &mut Self {
+        self.auto_options = enabled;
+        self
+    }
+
     /// use a middleware.
+    ///
+    /// Gates of a router run after gates of app and before gates of included routers,
+    /// in order of registration downstream and in reverse order upstream.
+    ///
+    /// ### Execution point
+    ///
+    /// Gates are joined with each endpoint, so they run only when a route is matched,
+    /// after route resolution (router parameters are available) and before the endpoint.
+    /// Unmatched requests never reach them,
+    /// which makes router gates suitable for middleware like authentication.
+    ///
+    /// ```rust
+    /// use roa::core::App;
+    /// use roa::router::{Router, RouterParam};
+    /// use async_std::task::spawn;
+    /// use http::StatusCode;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut router = Router::<()>::new();
+    ///     router
+    ///         .gate_fn(|ctx, next| async move {
+    ///             // route is matched here.
+    ///             assert_eq!(0, ctx.must_param("id").await?.parse::<usize>()?);
+    ///             next().await
+    ///         })
+    ///         .get("/user/:id", |_ctx| async { Ok(()) });
+    ///     let (addr, server) = App::new(()).gate(router.routes("/")?).run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}/user/0", addr)).await?;
+    ///     assert_eq!(StatusCode::OK, resp.status());
+    ///     let resp = reqwest::get(&format!("http://{}/post/0", addr)).await?;
+    ///     assert_eq!(StatusCode::NOT_FOUND, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
     pub fn gate(&mut self, middleware: impl Middleware<S>) -> &mut Self {
         self.middlewares.push(Arc::new(middleware));
         self
@@ -265,6 +447,107 @@ impl<S: State> Router<S> {
         self.end(methods, path, endpoint)
     }
 
+    /// Register a new endpoint with a timeout.
+    ///
+    /// The endpoint is cancelled if it's not finished in `timeout`,
+    /// then the status and body are replaced by 504 GATEWAY TIMEOUT,
+    /// headers set by the endpoint are dropped while ones set by upstream are kept.
+    /// The response is written directly instead of returning an error,
+    /// see `roa::core::Error` for server errors.
+    ///
+    /// Gates of the router are not counted in the timeout.
+    pub fn end_with_timeout(
+        &mut self,
+        methods: &[Method],
+        path: &'static str,
+        timeout: Duration,
+        endpoint: impl Middleware<S>,
+    ) -> &mut Self {
+        self.end(
+            methods,
+            path,
+            RouteTimeout {
+                endpoint: Arc::new(endpoint),
+                timeout,
+            },
+        )
+    }
+
+    /// A sugar of `Router::end_with_timeout` to register a GET endpoint.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::router::Router;
+    /// use roa::core::App;
+    /// use async_std::task::{sleep, spawn};
+    /// use http::StatusCode;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut router = Router::<()>::new();
+    ///     router.get_with_timeout("/report", Duration::from_millis(100), |_ctx| async {
+    ///         sleep(Duration::from_secs(1)).await;
+    ///         Ok(())
+    ///     });
+    ///     let (addr, server) = App::new(()).gate(router.routes("/")?).run_local()?;
+    ///     spawn(server);
+    ///     let resp = reqwest::get(&format!("http://{}/report", addr)).await?;
+    ///     assert_eq!(StatusCode::GATEWAY_TIMEOUT, resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn get_with_timeout<F>(
+        &mut self,
+        path: &'static str,
+        timeout: Duration,
+        endpoint: fn(Context<S>) -> F,
+    ) -> &mut Self
+    where
+        F: 'static + Future<Output = Result> + Send,
+    {
+        self.end_with_timeout([Method::GET].as_ref(), path, timeout, endpoint)
+    }
+
+    /// Register endpoints in batch, for table-driven or generated routes.
+    ///
+    /// Conflicts are reported by `Router::routes`, as registering them one by one.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::core::{Middleware, Context, Result};
+    /// use roa::router::Router;
+    /// use http::Method;
+    /// use std::sync::Arc;
+    ///
+    /// async fn list(_ctx: Context<()>) -> Result {
+    ///     Ok(())
+    /// }
+    ///
+    /// async fn create(_ctx: Context<()>) -> Result {
+    ///     Ok(())
+    /// }
+    ///
+    /// let routes: Vec<(Method, &'static str, Arc<dyn Middleware<()>>)> = vec![
+    ///     (Method::GET, "/user", Arc::new(list as fn(_) -> _)),
+    ///     (Method::POST, "/user", Arc::new(create as fn(_) -> _)),
+    /// ];
+    /// let mut router = Router::new();
+    /// router.extend(routes);
+    /// assert!(router.routes("/").is_ok());
+    /// ```
+    pub fn extend(
+        &mut self,
+        routes: impl IntoIterator<Item = (Method, &'static str, Arc<dyn Middleware<S>>)>,
+    ) -> &mut Self {
+        for (method, path, endpoint) in routes {
+            self.endpoints.push((method, path.to_string(), endpoint));
+        }
+        self
+    }
+
     /// Include another router with prefix, allowing all methods.
     pub fn include(&mut self, prefix: &'static str, router: Router<S>) -> &mut Self {
         self.include_methods(prefix, router, ALL_METHODS)
@@ -285,6 +568,37 @@ impl<S: State> Router<S> {
         self
     }
 
+    /// Compose sub-routers into a single router,
+    /// reporting conflicts between them as soon as each one is included.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::router::Router;
+    /// use http::Method;
+    ///
+    /// let mut user = Router::<()>::new();
+    /// user.get("/", |_ctx| async { Ok(()) });
+    /// let mut post = Router::<()>::new();
+    /// post.get("/", |_ctx| async { Ok(()) })
+    ///     .post("/", |_ctx| async { Ok(()) });
+    ///
+    /// let router = Router::compose()
+    ///     .include("/user", user)
+    ///     .unwrap()
+    ///     .include_methods("/post", post, [Method::GET])
+    ///     .unwrap()
+    ///     .build();
+    /// let endpoint = router.routes("/").unwrap();
+    /// assert_eq!(vec![Method::GET], endpoint.allowed_methods("/post"));
+    /// ```
+    pub fn compose() -> Composer<S> {
+        Composer {
+            router: Router::new(),
+            probe: RouteEndpoint::default(),
+        }
+    }
+
     fn on(
         &self,
         prefix: &'static str,
@@ -293,7 +607,7 @@ impl<S: State> Router<S> {
             let mut middlewares = self.middlewares.clone();
             middlewares.push(endpoint.clone());
             let new_endpoint: Arc<dyn Middleware<S>> = Arc::new(join_all(middlewares));
-            let new_path = join_path(&vec![prefix, path.as_str()]);
+            let new_path = join_path([prefix, path.as_str()]);
             (method.clone(), new_path, new_endpoint)
         })
     }
@@ -303,10 +617,19 @@ impl<S: State> Router<S> {
         self,
         prefix: &'static str,
     ) -> StdResult<RouteEndpoint<S>, RouterError> {
-        let mut route_endpoint = RouteEndpoint::default();
+        let mut route_endpoint = RouteEndpoint {
+            auto_options: self.auto_options,
+            max_captures: self.max_captures,
+            ..RouteEndpoint::default()
+        };
         for (method, raw_path, endpoint) in self.on(prefix) {
             route_endpoint.insert(method, raw_path, endpoint)?;
         }
+        if self.match_cache > 0 {
+            for route_table in route_endpoint.tables.values_mut() {
+                route_table.cache = Some(MatchCache::new(self.match_cache));
+            }
+        }
         Ok(route_endpoint)
     }
 }
@@ -317,6 +640,50 @@ impl<S: State> Default for Router<S> {
     }
 }
 
+/// A builder to compose sub-routers into a single `Router`, returned by `Router::compose`.
+///
+/// It's sugar over repeated `Router::include_methods`,
+/// but a conflict is returned by the include introducing it,
+/// instead of by `Router::routes` of the merged router.
+pub struct Composer<S: State> {
+    router: Router<S>,
+    probe: RouteEndpoint<S>,
+}
+
+impl<S: State> Composer<S> {
+    /// Include a sub-router with prefix, allowing all methods.
+    pub fn include(
+        self,
+        prefix: &'static str,
+        router: Router<S>,
+    ) -> StdResult<Self, RouterError> {
+        self.include_methods(prefix, router, ALL_METHODS)
+    }
+
+    /// Include a sub-router with prefix, only allowing method in parameter methods.
+    ///
+    /// Return an error if any route conflicts with routes included before.
+    pub fn include_methods(
+        mut self,
+        prefix: &'static str,
+        router: Router<S>,
+        methods: impl AsRef<[Method]>,
+    ) -> StdResult<Self, RouterError> {
+        for (method, path, endpoint) in router.on(prefix) {
+            if methods.as_ref().contains(&method) {
+                self.probe.insert(method.clone(), &path, endpoint.clone())?;
+                self.router.endpoints.push((method, path, endpoint))
+            }
+        }
+        Ok(self)
+    }
+
+    /// Get the merged router.
+    pub fn build(self) -> Router<S> {
+        self.router
+    }
+}
+
 macro_rules! impl_http_method {
     ($end:ident, $($method:expr),*) => {
         #[allow(missing_docs)]
@@ -359,24 +726,88 @@ impl<S: State> Default for RouteEndpoint<S> {
         for method in ALL_METHODS.as_ref() {
             map.insert(method.clone(), RouteTable::new());
         }
-        Self(map)
+        Self {
+            tables: map,
+            auto_options: true,
+            max_captures: 0,
+        }
     }
 }
 
 impl<S: State> RouteEndpoint<S> {
+    /// List methods registered for a concrete path, checking both static and dynamic routes.
+    ///
+    /// Standard methods are listed in order of `GET, POST, PUT, PATCH, OPTIONS, DELETE, HEAD, TRACE, CONNECT`,
+    /// followed by extension methods in alphabetical order.
+    ///
+    /// ### Example
+    ///
+    /// ```rust
+    /// use roa::router::Router;
+    /// use http::Method;
+    ///
+    /// let mut router = Router::<()>::new();
+    /// router
+    ///     .get("/user/:id", |_ctx| async { Ok(()) })
+    ///     .delete("/user/:id", |_ctx| async { Ok(()) });
+    /// let endpoint = router.routes("/").unwrap();
+    /// assert_eq!(
+    ///     vec![Method::GET, Method::DELETE],
+    ///     endpoint.allowed_methods("/user/0")
+    /// );
+    /// assert!(endpoint.allowed_methods("/post/0").is_empty());
+    /// ```
+    pub fn allowed_methods(&self, path: &str) -> Vec<Method> {
+        let path = standardize_path(path);
+        let mut methods: Vec<Method> = self
+            .tables
+            .iter()
+            .filter(|(_, route_table)| route_table.contains(&path))
+            .map(|(method, _)| method.clone())
+            .collect();
+        methods.sort_by_key(|method| {
+            let index = ALL_METHODS.iter().position(|standard| standard == method);
+            (index.unwrap_or(ALL_METHODS.len()), method.to_string())
+        });
+        methods
+    }
+
+    /// Resolve the endpoint which would handle a request, without executing it,
+    /// for unit testing of route resolution.
+    ///
+    /// The endpoint is composed with gates of the router it's registered on.
+    /// Return `None` if no route matches the method and the concrete path,
+    /// even if the request would be handled by `Router::auto_options`.
+    ///
+    /// Only available in tests or with feature "testing".
+    #[cfg(any(test, feature = "testing"))]
+    pub fn resolve(
+        &self,
+        method: &Method,
+        path: &str,
+    ) -> Option<Arc<dyn Middleware<S>>> {
+        self.tables.get(method)?.resolve(&standardize_path(path))
+    }
+
     fn insert(
         &mut self,
         method: Method,
         raw_path: impl AsRef<str>,
         endpoint: Arc<dyn Middleware<S>>,
     ) -> StdResult<(), RouterError> {
-        match self.0.get_mut(&method) {
-            Some(route_table) => route_table.insert(raw_path, endpoint),
-            None => {
-                self.0.insert(method.clone(), RouteTable::new());
-                self.insert(method, raw_path, endpoint)
+        let path: Path = raw_path.as_ref().parse()?;
+        if let Path::Dynamic(ref regex_path) = path {
+            if self.max_captures > 0 && regex_path.vars.len() > self.max_captures {
+                return Err(RouterError::TooManyCaptures(
+                    regex_path.raw.clone(),
+                    self.max_captures,
+                ));
             }
         }
+        self.tables
+            .entry(method)
+            .or_insert_with(RouteTable::new)
+            .insert(path, endpoint)
     }
 }
 
@@ -385,15 +816,16 @@ impl<S: State> RouteTable<S> {
         Self {
             static_route: Trie::new(),
             dynamic_route: Vec::new(),
+            cache: None,
         }
     }
 
     fn insert(
         &mut self,
-        raw_path: impl AsRef<str>,
+        path: Path,
         endpoint: Arc<dyn Middleware<S>>,
     ) -> StdResult<(), RouterError> {
-        match raw_path.as_ref().parse()? {
+        match path {
             Path::Static(path) => {
                 if self
                     .static_route
@@ -403,57 +835,166 @@ impl<S: State> RouteTable<S> {
                     return Err(Conflict::Path(path).into());
                 }
             }
-            Path::Dynamic(regex_path) => self.dynamic_route.push((regex_path, endpoint)),
+            Path::Dynamic(regex_path) => {
+                if let Some((existing, _)) = self
+                    .dynamic_route
+                    .iter()
+                    .find(|(existing, _)| existing.shape == regex_path.shape)
+                {
+                    return Err(
+                        Conflict::Pattern(regex_path.raw, existing.raw.clone()).into()
+                    );
+                }
+                // keep dynamic routes sorted by specificity, stable for equal ones.
+                let index = self.dynamic_route.partition_point(|(existing, _)| {
+                    existing.specificity <= regex_path.specificity
+                });
+                self.dynamic_route.insert(index, (regex_path, endpoint))
+            }
         }
         Ok(())
     }
 
+    /// Resolve the endpoint of a standardized path,
+    /// in the same order as `RouteTable::end`.
+    #[cfg(any(test, feature = "testing"))]
+    fn resolve(&self, path: &str) -> Option<Arc<dyn Middleware<S>>> {
+        if let Some(handler) = self.static_route.get(path) {
+            return Some(handler.clone());
+        }
+        self.dynamic_route
+            .iter()
+            .find(|(regexp_path, _)| regexp_path.re.is_match(path))
+            .map(|(_, handler)| handler.clone())
+    }
+
+    /// Check if a standardized path is matched by any route.
+    fn contains(&self, path: &str) -> bool {
+        self.static_route.get(path).is_some()
+            || self
+                .dynamic_route
+                .iter()
+                .any(|(regexp_path, _)| regexp_path.re.is_match(path))
+    }
+
     async fn end(&self, mut ctx: Context<S>) -> Result {
-        let uri = ctx.uri().await;
-        let path =
-            standardize_path(&percent_decode_str(uri.path()).decode_utf8().map_err(
-                |err| {
-                    Error::new(
-                        StatusCode::BAD_REQUEST,
-                        format!(
-                            "{}\npath `{}` is not a valid utf-8 string",
-                            err,
-                            uri.path()
-                        ),
-                        true,
-                    )
-                },
-            )?);
+        let path = route_path(&ctx).await?;
         if let Some(handler) = self.static_route.get(&path) {
             return handler.clone().end(ctx).await;
         }
 
-        for (regexp_path, handler) in self.dynamic_route.iter() {
-            if let Some(cap) = regexp_path.re.captures(&path) {
-                for var in regexp_path.vars.iter() {
-                    ctx.store::<RouterSymbol>(var, cap[var.as_str()].to_string())
-                        .await;
-                }
-                return handler.clone().end(ctx).await;
+        let cached = self.cache.as_ref().and_then(|cache| cache.get(&path));
+        let matched = cached.or_else(|| self.match_dynamic(&path));
+        if let Some((index, vars)) = matched {
+            for (var, value) in vars {
+                ctx.store::<RouterSymbol>(&var, value).await;
             }
+            return self.dynamic_route[index].1.clone().end(ctx).await;
         }
         throw!(StatusCode::NOT_FOUND)
     }
+
+    /// Match path by regex of dynamic routes, cache the result if cache is enabled.
+    fn match_dynamic(&self, path: &str) -> Option<cache::Matched> {
+        for (index, (regexp_path, _)) in self.dynamic_route.iter().enumerate() {
+            if let Some(cap) = regexp_path.re.captures(path) {
+                let vars: Vec<(String, String)> = regexp_path
+                    .vars
+                    .iter()
+                    .map(|var| (var.clone(), cap[var.as_str()].to_string()))
+                    .collect();
+                if let Some(cache) = self.cache.as_ref() {
+                    cache.insert(path.to_string(), (index, vars.clone()));
+                }
+                return Some((index, vars));
+            }
+        }
+        None
+    }
+}
+
+/// Get the percent-decoded and standardized request path.
+async fn route_path<S: State>(ctx: &Context<S>) -> Result<String> {
+    let uri = ctx.uri().await;
+    let path = percent_decode_str(uri.path())
+        .decode_utf8()
+        .map_err(|err| {
+            Error::new(
+                StatusCode::BAD_REQUEST,
+                format!("{}\npath `{}` is not a valid utf-8 string", err, uri.path()),
+                true,
+            )
+        })?;
+    Ok(standardize_path(&path))
+}
+
+impl<S: State> RouteEndpoint<S> {
+    /// Respond 204 NO CONTENT with "Allow" to an OPTIONS request without OPTIONS route.
+    async fn options(&self, mut ctx: Context<S>) -> Result {
+        let mut methods = self.allowed_methods(&route_path(&ctx).await?);
+        if methods.is_empty() {
+            throw!(StatusCode::NOT_FOUND)
+        }
+        methods.push(Method::OPTIONS);
+        let allow = methods
+            .iter()
+            .map(Method::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut response = ctx.resp_mut().await;
+        response.insert(ALLOW, allow)?;
+        response.status = StatusCode::NO_CONTENT;
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl<S: State> Middleware<S> for RouteEndpoint<S> {
     async fn handle(self: Arc<Self>, ctx: Context<S>, _next: Next) -> Result {
-        match self.0.get(&ctx.method().await) {
+        let method = ctx.method().await;
+        if method == Method::OPTIONS && self.auto_options {
+            let options = &self.tables[&Method::OPTIONS];
+            if !options.contains(&route_path(&ctx).await?) {
+                return self.options(ctx).await;
+            }
+        }
+        match self.tables.get(&method) {
             None => throw!(
                 StatusCode::METHOD_NOT_ALLOWED,
-                format!("method {} is not allowed", &ctx.method().await)
+                format!("method {} is not allowed", method)
             ),
             Some(handler) => handler.end(ctx).await,
         }
     }
 }
 
+/// An endpoint cancelled after a timeout, registered by `Router::end_with_timeout`.
+struct RouteTimeout<M> {
+    endpoint: Arc<M>,
+    timeout: Duration,
+}
+
+#[async_trait]
+impl<S: State, M: Middleware<S>> Middleware<S> for RouteTimeout<M> {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, next: Next) -> Result {
+        // headers set by upstream gates (like cors or request id) survive a timeout
+        let headers = ctx.resp().await.headers.clone();
+        let endpoint = self.endpoint.clone().handle(ctx.clone(), next);
+        match timeout(self.timeout, endpoint).await {
+            Ok(result) => result,
+            Err(_) => {
+                let mut response = ctx.resp_mut().await;
+                response.headers = headers;
+                response.status = StatusCode::GATEWAY_TIMEOUT;
+                // drop the partial body written by the endpoint
+                std::mem::take(&mut **response);
+                response.write_str("endpoint timed out");
+                Ok(())
+            }
+        }
+    }
+}
+
 #[async_trait]
 impl<S: State> RouterParam for Context<S> {
     async fn must_param<'a>(&self, name: &'a str) -> Result<Variable<'a>> {
@@ -472,12 +1013,14 @@ impl<S: State> RouterParam for Context<S> {
 
 #[cfg(test)]
 mod tests {
-    use super::Router;
-    use crate::core::App;
-    use async_std::task::spawn;
+    use super::{Router, RouterParam};
+    use crate::core::{App, Context, Middleware, Next};
+    use async_std::task::{sleep, spawn};
     use encoding::EncoderTrap;
-    use http::StatusCode;
+    use http::{HeaderValue, Method, StatusCode};
     use percent_encoding::NON_ALPHANUMERIC;
+    use std::sync::Arc;
+    use std::time::Duration;
 
     #[tokio::test]
     async fn gate() -> Result<(), Box<dyn std::error::Error>> {
@@ -523,6 +1066,313 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn allowed_methods() -> Result<(), Box<dyn std::error::Error>> {
+        let mut router = Router::<()>::new();
+        let custom = Method::from_bytes(b"PURGE")?;
+        router
+            .delete("/user", |_ctx| async { Ok(()) })
+            .get("/user", |_ctx| async { Ok(()) })
+            .put("/user/:id", |_ctx| async { Ok(()) })
+            .end(
+                std::slice::from_ref(&custom),
+                "/user/:id",
+                |_ctx, _next: Next| async { Ok(()) },
+            )
+            .all("/file/*{path}", |_ctx| async { Ok(()) });
+        let endpoint = router.routes("/api")?;
+        assert_eq!(
+            vec![Method::GET, Method::DELETE],
+            endpoint.allowed_methods("/api/user/")
+        );
+        assert_eq!(
+            vec![Method::PUT, custom],
+            endpoint.allowed_methods("/api/user/0")
+        );
+        assert_eq!(9, endpoint.allowed_methods("/api/file/a/b.txt").len());
+        assert!(endpoint.allowed_methods("/user").is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn auto_options() -> Result<(), Box<dyn std::error::Error>> {
+        use http::header::ALLOW;
+        let mut user = Router::<()>::new();
+        user.gate_fn(|_ctx, _next| async { panic!("router gates are skipped") })
+            .get("/user/:id", |_ctx| async { Ok(()) })
+            .delete("/user/:id", |_ctx| async { Ok(()) });
+        let mut router = Router::<()>::new();
+        router
+            .include("/", user)
+            .options("/post", |mut ctx| async move {
+                ctx.resp_mut().await.write_str("custom options");
+                Ok(())
+            });
+        let (addr, server) = App::new(()).gate(router.routes("/")?).run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+
+        let resp = client
+            .request(Method::OPTIONS, &format!("http://{}/user/0", addr))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NO_CONTENT, resp.status());
+        assert_eq!("GET, DELETE, OPTIONS", resp.headers()[ALLOW]);
+
+        // registered OPTIONS route takes precedence
+        let resp = client
+            .request(Method::OPTIONS, &format!("http://{}/post", addr))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("custom options", resp.text().await?);
+
+        let resp = client
+            .request(Method::OPTIONS, &format!("http://{}/comment", addr))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+
+        // disabled
+        let mut router = Router::<()>::new();
+        router
+            .auto_options(false)
+            .get("/user/:id", |_ctx| async { Ok(()) });
+        let (addr, server) = App::new(()).gate(router.routes("/")?).run_local()?;
+        spawn(server);
+        let resp = client
+            .request(Method::OPTIONS, &format!("http://{}/user/0", addr))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn match_cache() -> Result<(), Box<dyn std::error::Error>> {
+        let mut router = Router::<()>::new();
+        router.match_cache(2);
+        for i in 0..128 {
+            let path: &'static str = Box::leak(format!("/r{}/:id", i).into_boxed_str());
+            router.end(
+                &[Method::GET],
+                path,
+                move |mut ctx: Context<()>, _next: Next| async move {
+                    let id = ctx.must_param("id").await?.to_string();
+                    ctx.resp_mut().await.write_str(format!("{}:{}", i, id));
+                    Ok(())
+                },
+            );
+        }
+        let (addr, server) = App::new(()).gate(router.routes("/")?).run_local()?;
+        spawn(server);
+        for (path, expected) in [
+            ("/r127/1", "127:1"),
+            ("/r127/1", "127:1"),
+            ("/r127/2", "127:2"),
+            ("/r0/3", "0:3"),
+            ("/r127/1", "127:1"),
+            ("/r0/3", "0:3"),
+        ]
+        .iter()
+        {
+            let resp = reqwest::get(&format!("http://{}{}", addr, path)).await?;
+            assert_eq!(StatusCode::OK, resp.status());
+            assert_eq!(*expected, resp.text().await?);
+        }
+        let resp = reqwest::get(&format!("http://{}/r128/0", addr)).await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn gate_order() -> Result<(), Box<dyn std::error::Error>> {
+        use futures::lock::Mutex;
+        let vector = Arc::new(Mutex::new(Vec::new()));
+        let recorder = |name: &'static str| {
+            let vec = vector.clone();
+            move |_ctx, next: Next| {
+                let vec = vec.clone();
+                async move {
+                    vec.lock().await.push(format!("{} down", name));
+                    next().await?;
+                    vec.lock().await.push(format!("{} up", name));
+                    Ok(())
+                }
+            }
+        };
+        let mut router = Router::<()>::new();
+        let mut user_router = Router::<()>::new();
+        router.gate(recorder("router1")).gate(recorder("router2"));
+        user_router.gate(recorder("user_router"));
+        let vec = vector.clone();
+        user_router.end(&[Method::GET], "/", move |_ctx, _next: Next| {
+            let vec = vec.clone();
+            async move {
+                vec.lock().await.push("endpoint".to_string());
+                Ok(())
+            }
+        });
+        router.include("/user", user_router);
+        let (addr, server) = App::new(())
+            .gate(recorder("app1"))
+            .gate(recorder("app2"))
+            .gate(router.routes("/")?)
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/user", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(
+            vec![
+                "app1 down",
+                "app2 down",
+                "router1 down",
+                "router2 down",
+                "user_router down",
+                "endpoint",
+                "user_router up",
+                "router2 up",
+                "router1 up",
+                "app2 up",
+                "app1 up",
+            ],
+            *vector.lock().await
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn gate_only_on_matched() -> Result<(), Box<dyn std::error::Error>> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let counter = Arc::new(AtomicUsize::new(0));
+        let count = counter.clone();
+        let mut router = Router::<()>::new();
+        router
+            .gate(move |_ctx, next: Next| {
+                count.fetch_add(1, Ordering::SeqCst);
+                next()
+            })
+            .get("/user", |_ctx| async { Ok(()) });
+        let (addr, server) = App::new(()).gate(router.routes("/")?).run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let resp = client.get(&format!("http://{}/post", addr)).send().await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        let resp = client.post(&format!("http://{}/user", addr)).send().await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        assert_eq!(0, counter.load(Ordering::SeqCst));
+        let resp = client.get(&format!("http://{}/user", addr)).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(1, counter.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn route_timeout() -> Result<(), Box<dyn std::error::Error>> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let sent = Arc::new(AtomicUsize::new(0));
+        let sent_counter = sent.clone();
+        let mut router = Router::<()>::new();
+        router
+            .gate_fn(move |mut ctx, next| {
+                let sent = sent_counter.clone();
+                async move {
+                    ctx.resp_mut()
+                        .await
+                        .headers
+                        .insert("x-gate", HeaderValue::from_static("passed"));
+                    ctx.on_response_sent(move |_body| {
+                        sent.fetch_add(1, Ordering::SeqCst);
+                    })
+                    .await;
+                    next().await
+                }
+            })
+            .get_with_timeout(
+                "/slow",
+                Duration::from_millis(100),
+                |mut ctx| async move {
+                    ctx.resp_mut()
+                        .await
+                        .headers
+                        .insert("x-endpoint", HeaderValue::from_static("partial"));
+                    ctx.resp_mut().await.write_str("partial");
+                    sleep(Duration::from_secs(1)).await;
+                    Ok(())
+                },
+            )
+            .get_with_timeout("/fast", Duration::from_secs(1), |mut ctx| async move {
+                ctx.resp_mut().await.write_str("fast");
+                Ok(())
+            });
+        let (addr, server) = App::new(()).gate(router.routes("/")?).run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/slow", addr)).await?;
+        assert_eq!(StatusCode::GATEWAY_TIMEOUT, resp.status());
+        assert_eq!("passed", resp.headers()["x-gate"]);
+        assert!(resp.headers().get("x-endpoint").is_none());
+        assert_eq!("endpoint timed out", resp.text().await?);
+        // callbacks registered by upstream survive a timeout
+        assert_eq!(1, sent.load(Ordering::SeqCst));
+
+        let resp = reqwest::get(&format!("http://{}/fast", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("passed", resp.headers()["x-gate"]);
+        assert_eq!("fast", resp.text().await?);
+        Ok(())
+    }
+
+    #[test]
+    fn resolve() -> Result<(), Box<dyn std::error::Error>> {
+        let mut router = Router::<()>::new();
+        let mut user_router = Router::<()>::new();
+        user_router
+            .gate_fn(|_ctx, next| next())
+            .get("/:id", |_ctx| async { Ok(()) })
+            .get("/me", |_ctx| async { Ok(()) });
+        router
+            .include("/user", user_router)
+            .post("/post", |_ctx| async { Ok(()) });
+        let endpoint = router.routes("/api")?;
+
+        let user = endpoint.resolve(&Method::GET, "/api/user/0").unwrap();
+        let another = endpoint.resolve(&Method::GET, "/api/user/1/").unwrap();
+        let me = endpoint.resolve(&Method::GET, "api/user/me").unwrap();
+        assert!(Arc::ptr_eq(&user, &another));
+        assert!(!Arc::ptr_eq(&user, &me));
+        assert!(endpoint.resolve(&Method::POST, "/api/post").is_some());
+
+        assert!(endpoint.resolve(&Method::POST, "/api/user/0").is_none());
+        assert!(endpoint.resolve(&Method::GET, "/api/post").is_none());
+        assert!(endpoint.resolve(&Method::GET, "/user/0").is_none());
+        assert!(endpoint.resolve(&Method::OPTIONS, "/api/post").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn max_captures() -> Result<(), Box<dyn std::error::Error>> {
+        let mut router = Router::<()>::new();
+        let mut archive = Router::<()>::new();
+        archive.get("/:year/:month/:day", |_ctx| async { Ok(()) });
+        router
+            .max_captures(3)
+            .get("/static/*{path}", |_ctx| async { Ok(()) })
+            .include("/:lang", archive);
+        let err = router.routes("/").err().unwrap();
+        assert_eq!(
+            "too many variables on path /:lang/:year/:month/:day/, the limit is 3",
+            err.to_string()
+        );
+
+        let mut router = Router::<()>::new();
+        router
+            .max_captures(3)
+            .get("/:year/:month/:day", |_ctx| async { Ok(()) })
+            .get("/about", |_ctx| async { Ok(()) });
+        router.routes("/")?;
+        Ok(())
+    }
+
     #[test]
     fn conflict_path() -> Result<(), Box<dyn std::error::Error>> {
         let mut router = Router::<()>::new();
@@ -535,6 +1385,174 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn compose() -> Result<(), Box<dyn std::error::Error>> {
+        let mut user = Router::<()>::new();
+        user.gate_fn(|mut ctx, next| async move {
+            ctx.resp_mut().await.write_str("user ");
+            next().await
+        })
+        .get("/:id", |mut ctx| async move {
+            ctx.resp_mut().await.write_str("get");
+            Ok(())
+        })
+        .delete("/:id", |_ctx| async { Ok(()) });
+        let mut post = Router::<()>::new();
+        post.get("/", |mut ctx| async move {
+            ctx.resp_mut().await.write_str("posts");
+            Ok(())
+        });
+
+        let router = Router::compose()
+            .include_methods("/user", user, [Method::GET])?
+            .include("/post", post)?
+            .build();
+        let (addr, server) = App::new(()).gate(router.routes("/api")?).run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let resp = client
+            .get(&format!("http://{}/api/user/0", addr))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("user get", resp.text().await?);
+        let resp = client
+            .delete(&format!("http://{}/api/user/0", addr))
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        let resp = client
+            .get(&format!("http://{}/api/post", addr))
+            .send()
+            .await?;
+        assert_eq!("posts", resp.text().await?);
+        Ok(())
+    }
+
+    #[test]
+    fn compose_conflict() -> Result<(), Box<dyn std::error::Error>> {
+        let mut user = Router::<()>::new();
+        user.get("/:id", |_ctx| async { Ok(()) })
+            .post("/:id", |_ctx| async { Ok(()) });
+        let mut evil = Router::<()>::new();
+        evil.get("/user/:name", |_ctx| async { Ok(()) });
+        let composer = Router::compose().include("/user", user)?;
+        let err = match composer.include_methods("/", evil, [Method::GET]) {
+            Ok(_) => panic!("conflict is not reported"),
+            Err(err) => err,
+        };
+        assert_eq!(
+            "Conflict! conflict pattern: `/user/:name/` is equivalent to `/user/:id/`",
+            err.to_string()
+        );
+
+        // routes filtered out never conflict
+        let mut user = Router::<()>::new();
+        user.post("/:id", |_ctx| async { Ok(()) });
+        let mut evil = Router::<()>::new();
+        evil.post("/user/:name", |_ctx| async { Ok(()) });
+        let router = Router::compose()
+            .include("/user", user)?
+            .include_methods("/", evil, [Method::GET])?
+            .build();
+        assert!(router.routes("/").is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn specificity_order() -> Result<(), Box<dyn std::error::Error>> {
+        let mut router = Router::<()>::new();
+        for path in ["/user/*{path}", "/:kind/new", "/user/:id", "/user/new"].iter() {
+            router.end(
+                &[Method::GET],
+                path,
+                move |mut ctx: Context<()>, _next: Next| async move {
+                    ctx.resp_mut().await.write_str(*path);
+                    Ok(())
+                },
+            );
+        }
+        let (addr, server) = App::new(()).gate(router.routes("/")?).run_local()?;
+        spawn(server);
+        for (path, expected) in [
+            ("/user/new", "/user/new"),
+            ("/user/0", "/user/:id"),
+            ("/post/new", "/:kind/new"),
+            ("/user/0/posts", "/user/*{path}"),
+        ]
+        .iter()
+        {
+            let resp = reqwest::get(&format!("http://{}{}", addr, path)).await?;
+            assert_eq!(StatusCode::OK, resp.status());
+            assert_eq!(*expected, resp.text().await?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn conflict_pattern() {
+        let mut router = Router::<()>::new();
+        let mut evil_router = Router::<()>::new();
+        router.get("/user/:a", |_ctx| async { Ok(()) });
+        evil_router.get("/:b", |_ctx| async { Ok(()) });
+        router.include("/user", evil_router);
+        match router.routes("/") {
+            Ok(_) => panic!("`/user/:b` should conflict with `/user/:a`"),
+            Err(err) => assert_eq!(
+                "Conflict! conflict pattern: `/user/:b/` is equivalent to `/user/:a/`",
+                err.to_string()
+            ),
+        }
+
+        // different methods never conflict
+        let mut router = Router::<()>::new();
+        router
+            .get("/:a", |_ctx| async { Ok(()) })
+            .post("/:b", |_ctx| async { Ok(()) });
+        assert!(router.routes("/").is_ok());
+    }
+
+    #[tokio::test]
+    async fn extend() -> Result<(), Box<dyn std::error::Error>> {
+        let mut router = Router::<()>::new();
+        let routes: Vec<(Method, &'static str, Arc<dyn Middleware<()>>)> = vec![
+            (
+                Method::GET,
+                "/user",
+                Arc::new(|_ctx, _next: Next| async { Ok(()) }),
+            ),
+            (
+                Method::POST,
+                "/user",
+                Arc::new(|_ctx, _next: Next| async { Ok(()) }),
+            ),
+        ];
+        router.extend(routes);
+        let (addr, server) = App::new(()).gate(router.routes("/")?).run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let resp = client.get(&format!("http://{}/user", addr)).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let resp = client.post(&format!("http://{}/user", addr)).send().await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let resp = client.put(&format!("http://{}/user", addr)).send().await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+        Ok(())
+    }
+
+    #[test]
+    fn extend_conflict() {
+        let mut router = Router::<()>::new();
+        router.get("/user", |_ctx| async { Ok(()) });
+        let routes: Vec<(Method, &'static str, Arc<dyn Middleware<()>>)> = vec![(
+            Method::GET,
+            "/user",
+            Arc::new(|_ctx, _next: Next| async { Ok(()) }),
+        )];
+        router.extend(routes);
+        assert!(router.routes("/").is_err());
+    }
+
     #[tokio::test]
     async fn route_not_found() -> Result<(), Box<dyn std::error::Error>> {
         let (addr, server) = App::new(())
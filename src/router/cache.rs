@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// Index of a dynamic route and its captured variables.
+pub type Matched = (usize, Vec<(String, String)>);
+
+/// A LRU cache mapping standardized path to matched dynamic route.
+pub struct MatchCache {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    tick: u64,
+    entries: HashMap<String, (u64, Matched)>,
+
+    /// Paths ordered by the last time they were used.
+    order: BTreeMap<u64, String>,
+}
+
+impl MatchCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<Matched> {
+        let mut guard = self.inner.lock().ok()?;
+        let inner = &mut *guard;
+        inner.tick += 1;
+        let tick = inner.tick;
+        let (last_used, matched) = inner.entries.get_mut(path)?;
+        let last_used = std::mem::replace(last_used, tick);
+        let matched = matched.clone();
+        if let Some(path) = inner.order.remove(&last_used) {
+            inner.order.insert(tick, path);
+        }
+        Some(matched)
+    }
+
+    pub fn insert(&self, path: String, matched: Matched) {
+        let mut guard = match self.inner.lock() {
+            Ok(guard) => guard,
+            Err(_) => return,
+        };
+        let inner = &mut *guard;
+        inner.tick += 1;
+        let tick = inner.tick;
+        if let Some((last_used, _)) = inner.entries.insert(path.clone(), (tick, matched))
+        {
+            inner.order.remove(&last_used);
+        } else if inner.entries.len() > self.capacity {
+            let least_used = inner.order.keys().next().copied();
+            if let Some(path) = least_used.and_then(|tick| inner.order.remove(&tick)) {
+                inner.entries.remove(&path);
+            }
+        }
+        inner.order.insert(tick, path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MatchCache;
+
+    #[test]
+    fn lru() {
+        let cache = MatchCache::new(2);
+        cache.insert("/a/".to_string(), (0, vec![]));
+        cache.insert("/b/".to_string(), (1, vec![]));
+        assert_eq!(0, cache.get("/a/").unwrap().0);
+        // "/b/" is the least recently used
+        cache.insert("/c/".to_string(), (2, vec![]));
+        assert!(cache.get("/b/").is_none());
+        assert_eq!(0, cache.get("/a/").unwrap().0);
+        assert_eq!(2, cache.get("/c/").unwrap().0);
+
+        // update an existing entry without eviction
+        cache.insert("/a/".to_string(), (3, vec![]));
+        assert_eq!(3, cache.get("/a/").unwrap().0);
+        assert_eq!(2, cache.get("/c/").unwrap().0);
+    }
+}
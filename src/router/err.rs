@@ -4,11 +4,13 @@ use std::fmt::{self, Display, Formatter};
 pub enum RouterError {
     MissingVariable(String),
     Conflict(Conflict),
+    TooManyCaptures(String, usize),
 }
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum Conflict {
     Path(String),
+    Pattern(String, String),
     Method(String, http::Method),
     Variable {
         paths: (String, String),
@@ -20,6 +22,10 @@ impl Display for Conflict {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
         match self {
             Conflict::Path(path) => f.write_str(&format!("conflict path: `{}`", path)),
+            Conflict::Pattern(path, existing) => f.write_str(&format!(
+                "conflict pattern: `{}` is equivalent to `{}`",
+                path, existing
+            )),
             Conflict::Method(path, method) => f.write_str(&format!(
                 "conflict method: `{}` on `{}` is already set",
                 method, path
@@ -41,6 +47,10 @@ impl Display for RouterError {
             RouterError::MissingVariable(path) => {
                 f.write_str(&format!("missing variable on path {}", path))
             }
+            RouterError::TooManyCaptures(path, max) => f.write_str(&format!(
+                "too many variables on path {}, the limit is {}",
+                path, max
+            )),
         }
     }
 }
@@ -64,6 +74,10 @@ mod tests {
             "conflict path: `/`",
             Conflict::Path("/".to_string()).to_string()
         );
+        assert_eq!(
+            "conflict pattern: `/:b/` is equivalent to `/:a/`",
+            Conflict::Pattern("/:b/".to_string(), "/:a/".to_string()).to_string()
+        );
         assert_eq!(
             "conflict method: `GET` on `/` is already set",
             Conflict::Method("/".to_string(), http::Method::GET).to_string()
@@ -88,5 +102,9 @@ mod tests {
             "missing variable on path /:",
             RouterError::MissingVariable("/:".to_string()).to_string()
         );
+        assert_eq!(
+            "too many variables on path /:a/:b/, the limit is 1",
+            RouterError::TooManyCaptures("/:a/:b/".to_string(), 1).to_string()
+        );
     }
 }
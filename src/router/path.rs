@@ -1,11 +1,13 @@
 use super::{Conflict, RouterError};
 use regex::{escape, Captures, Regex};
+use std::cmp::Reverse;
 use std::collections::HashSet;
 use std::convert::AsRef;
 use std::str::FromStr;
 
 const WILDCARD: &str = r"\*\{(?P<var>\w*)\}";
 const VARIABLE: &str = r"/:(?P<var>\w*)/";
+const NAMED_GROUP: &str = r"\(\?P<\w+>";
 
 pub fn standardize_path(raw_path: &str) -> String {
     format!("/{}/", raw_path.trim_matches('/'))
@@ -22,12 +24,12 @@ pub fn join_path<'a>(paths: impl 'a + AsRef<[&'a str]>) -> String {
 
 fn must_build(pattern: &str) -> Regex {
     Regex::new(pattern).unwrap_or_else(|err| {
-        panic!(format!(
+        panic!(
             r#"{}
                 regex pattern {} is invalid, this is a bug of roa-router::path.
                 please report it to https://github.com/Hexilee/roa"#,
             err, pattern
-        ))
+        )
     })
 }
 
@@ -42,6 +44,38 @@ pub struct RegexPath {
     pub raw: String,
     pub vars: HashSet<String>,
     pub re: Regex,
+
+    /// The pattern without variable names,
+    /// paths of the same shape like `/:a` and `/:b` match exactly the same requests.
+    pub shape: String,
+
+    /// Smaller is more specific.
+    pub specificity: Specificity,
+}
+
+/// Ordering key of dynamic paths:
+/// paths with less wildcards go first, then paths with more static segments,
+/// then paths whose earlier segments are static.
+pub type Specificity = (usize, Reverse<usize>, Vec<u8>);
+
+/// Compute specificity of a standardized path.
+fn specificity(path: &str) -> Specificity {
+    let ranks: Vec<u8> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            if segment.contains("*{") {
+                2
+            } else if segment.starts_with(':') {
+                1
+            } else {
+                0
+            }
+        })
+        .collect();
+    let wildcards = ranks.iter().filter(|rank| **rank == 2).count();
+    let statics = ranks.iter().filter(|rank| **rank == 0).count();
+    (wildcards, Reverse(statics), ranks)
 }
 
 impl FromStr for Path {
@@ -51,8 +85,12 @@ impl FromStr for Path {
         Ok(match path_to_regexp(&path)? {
             None => Path::Static(path),
             Some((pattern, vars)) => Path::Dynamic(RegexPath {
+                specificity: specificity(&path),
                 raw: path,
                 vars,
+                shape: must_build(NAMED_GROUP)
+                    .replace_all(&pattern, "(")
+                    .to_string(),
                 re: must_build(&format!(r"^{}$", pattern)),
             }),
         })
@@ -83,7 +121,7 @@ fn path_to_regexp(path: &str) -> Result<Option<(String, HashSet<String>)>, Route
         };
         for cap in wildcards {
             let variable = &cap["var"];
-            if variable == r"" {
+            if variable.is_empty() {
                 return Err(RouterError::MissingVariable(path.to_string()));
             }
             let var = escape(variable);
@@ -95,7 +133,7 @@ fn path_to_regexp(path: &str) -> Result<Option<(String, HashSet<String>)>, Route
         }
         for cap in variables {
             let variable = &cap["var"];
-            if variable == "" {
+            if variable.is_empty() {
                 return Err(RouterError::MissingVariable(path.to_string()));
             }
             let var = escape(variable);
@@ -112,7 +150,7 @@ fn path_to_regexp(path: &str) -> Result<Option<(String, HashSet<String>)>, Route
 #[cfg(test)]
 mod tests {
     use super::Path;
-    use super::{must_build, path_to_regexp, VARIABLE, WILDCARD};
+    use super::{must_build, path_to_regexp, specificity, VARIABLE, WILDCARD};
     use test_case::test_case;
 
     #[test_case("/:id/"; "pure dynamic")]
@@ -175,10 +213,39 @@ mod tests {
         assert!(path_to_regexp(path).is_err())
     }
 
+    #[test_case(r"/:a/", r"/:b/")]
+    #[test_case(r"/user/:id/", r"/user/:name/")]
+    #[test_case(r"/*{path}", r"/*{file}")]
+    fn same_shape(a: &str, b: &str) {
+        let shape = |path: &str| match path.parse().unwrap() {
+            Path::Static(path) => panic!("`{}` should be dynamic", path),
+            Path::Dynamic(re) => re.shape,
+        };
+        assert_eq!(shape(a), shape(b));
+    }
+
+    #[test_case(r"/:a/", r"/*{a}")]
+    #[test_case(r"/user/:id/", r"/post/:id/")]
+    fn different_shape(a: &str, b: &str) {
+        let shape = |path: &str| match path.parse().unwrap() {
+            Path::Static(path) => panic!("`{}` should be dynamic", path),
+            Path::Dynamic(re) => re.shape,
+        };
+        assert_ne!(shape(a), shape(b));
+    }
+
+    #[test_case(r"/user/new/:x/", r"/user/:id/:x/"; "more static segments")]
+    #[test_case(r"/a/:b/", r"/:a/b/"; "earlier static segment")]
+    #[test_case(r"/user/:id/:x/:y/", r"/user/*{path}"; "wildcard last")]
+    #[test_case(r"/*{path}/a/b/", r"/a/*{path}"; "less wildcards first")]
+    fn more_specific(a: &str, b: &str) {
+        assert!(specificity(a) < specificity(b));
+    }
+
     fn path_match(pattern: &str, path: &str) {
         let pattern: Path = pattern.parse().unwrap();
         match pattern {
-            Path::Static(pattern) => panic!(format!("`{}` should be dynamic", pattern)),
+            Path::Static(pattern) => panic!("`{}` should be dynamic", pattern),
             Path::Dynamic(re) => assert!(re.re.is_match(path)),
         }
     }
@@ -186,9 +253,9 @@ mod tests {
     fn path_not_match(pattern: &str, path: &str) {
         let pattern: Path = pattern.parse().unwrap();
         match pattern {
-            Path::Static(pattern) => panic!(format!("`{}` should be dynamic", pattern)),
+            Path::Static(pattern) => panic!("`{}` should be dynamic", pattern),
             Path::Dynamic(re) => {
-                println!("regex: {}", re.re.to_string());
+                println!("regex: {}", re.re);
                 assert!(!re.re.is_match(path))
             }
         }
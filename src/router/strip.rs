@@ -0,0 +1,153 @@
+use crate::core::{
+    async_trait, throw, Context, Error, Middleware, Next, Result, State, StatusCode,
+};
+use http::uri::{PathAndQuery, Uri};
+use std::sync::Arc;
+
+/// A middleware to strip a path prefix, returned by `strip_prefix`.
+pub struct StripPrefix {
+    prefix: String,
+    required: bool,
+}
+
+/// Strip prefix from the path of `ctx.uri()` before downstream middleware (like a router) run,
+/// which is useful when roa is mounted behind a reverse proxy adding a prefix.
+///
+/// The prefix only matches whole segments, "/api" matches "/api" and "/api/user",
+/// but not "/apis". The stripped path is "/" if nothing remains, and the query is kept.
+///
+/// Requests whose path doesn't start with prefix are passed through by default,
+/// call `StripPrefix::required` to reject them with 400 BAD REQUEST.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::router::{strip_prefix, Router};
+/// use roa::core::App;
+/// use async_std::task::spawn;
+/// use http::StatusCode;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut router = Router::<()>::new();
+///     router.get("/user", |_ctx| async move { Ok(()) });
+///     let (addr, server) = App::new(())
+///         .gate(strip_prefix("/api"))
+///         .gate(router.routes("/")?)
+///         .run_local()?;
+///     spawn(server);
+///     let resp = reqwest::get(&format!("http://{}/api/user", addr)).await?;
+///     assert_eq!(StatusCode::OK, resp.status());
+///     Ok(())
+/// }
+/// ```
+pub fn strip_prefix(prefix: impl ToString) -> StripPrefix {
+    let prefix = prefix.to_string();
+    StripPrefix {
+        prefix: format!("/{}", prefix.trim_matches('/')),
+        required: false,
+    }
+}
+
+impl StripPrefix {
+    /// Reject requests whose path doesn't start with prefix with 400 BAD REQUEST.
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// Strip prefix from path, return None if it doesn't match.
+    fn strip<'a>(&self, path: &'a str) -> Option<&'a str> {
+        if self.prefix == "/" {
+            return Some(path);
+        }
+        match path.strip_prefix(self.prefix.as_str()) {
+            Some("") => Some("/"),
+            Some(rest) if rest.starts_with('/') => Some(rest),
+            _ => None,
+        }
+    }
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for StripPrefix {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, next: Next) -> Result {
+        let uri = ctx.uri().await;
+        match self.strip(uri.path()) {
+            Some(path) => {
+                let path_and_query = match uri.query() {
+                    Some(query) => format!("{}?{}", path, query),
+                    None => path.to_string(),
+                };
+                let mut parts = uri.clone().into_parts();
+                parts.path_and_query =
+                    Some(path_and_query.parse::<PathAndQuery>().map_err(|err| {
+                        Error::new(StatusCode::BAD_REQUEST, err, true)
+                    })?);
+                ctx.req_mut().await.uri = Uri::from_parts(parts)
+                    .map_err(|err| Error::new(StatusCode::BAD_REQUEST, err, true))?;
+            }
+            None if self.required => throw!(
+                StatusCode::BAD_REQUEST,
+                format!("path {} doesn't start with {}", uri.path(), self.prefix)
+            ),
+            None => (),
+        }
+        next().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::strip_prefix;
+    use crate::core::App;
+    use async_std::task::spawn;
+    use http::StatusCode;
+
+    #[test]
+    fn strip() {
+        let strip = strip_prefix("/api/");
+        assert_eq!(Some("/"), strip.strip("/api"));
+        assert_eq!(Some("/"), strip.strip("/api/"));
+        assert_eq!(Some("/user"), strip.strip("/api/user"));
+        assert_eq!(None, strip.strip("/apis"));
+        assert_eq!(None, strip.strip("/user"));
+        assert_eq!(Some("/user"), strip_prefix("/").strip("/user"));
+    }
+
+    #[tokio::test]
+    async fn strip_prefix_pass() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(strip_prefix("/api"))
+            .end(|mut ctx| async move {
+                let uri = ctx.uri().await;
+                ctx.resp_mut().await.write_str(uri.to_string());
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/api/user?id=1", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("/user?id=1", resp.text().await?);
+
+        let resp = reqwest::get(&format!("http://{}/apis", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("/apis", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn strip_prefix_required() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(strip_prefix("/api").required())
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::get(&format!("http://{}/api", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+
+        let resp = reqwest::get(&format!("http://{}/user", addr)).await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        assert_eq!("path /user doesn't start with /api", resp.text().await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+//! The shedding module of roa.
+//! This module provides a middleware `MaxInFlight` to shed load
+//! when too many requests are in flight.
+
+use crate::core::header::{HeaderValue, RETRY_AFTER};
+use crate::core::{async_trait, Context, Middleware, Next, Result, State, StatusCode};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A middleware to shed load, returned by `max_in_flight`.
+pub struct MaxInFlight {
+    limit: usize,
+    retry_after: Duration,
+    in_flight: AtomicUsize,
+}
+
+/// Increase the in-flight counter and decrease it on drop.
+struct InFlight<'a> {
+    counter: &'a AtomicUsize,
+    count: usize,
+}
+
+impl<'a> InFlight<'a> {
+    fn enter(counter: &'a AtomicUsize) -> Self {
+        let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+        Self { counter, count }
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Reject requests with 503 SERVICE UNAVAILABLE and a "Retry-After" header
+/// when more than `limit` requests are passing through this middleware.
+///
+/// A request is in flight while downstream middleware run,
+/// the time to send response body is not counted.
+/// Rejected responses are written directly instead of returning an error,
+/// see `roa::core::Error` for server errors.
+///
+/// "Retry-After" is 1 second by default, call `MaxInFlight::retry_after` to change it.
+///
+/// ### Example
+///
+/// ```rust
+/// use roa::shedding::max_in_flight;
+/// use roa::core::App;
+/// use async_std::task::spawn;
+/// use http::StatusCode;
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let (addr, server) = App::new(())
+///         .gate(max_in_flight(1024).retry_after(Duration::from_secs(5)))
+///         .run_local()?;
+///     spawn(server);
+///     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+///     assert_eq!(StatusCode::OK, resp.status());
+///     Ok(())
+/// }
+/// ```
+pub fn max_in_flight(limit: usize) -> MaxInFlight {
+    MaxInFlight {
+        limit,
+        retry_after: Duration::from_secs(1),
+        in_flight: AtomicUsize::new(0),
+    }
+}
+
+impl MaxInFlight {
+    /// Set the "Retry-After" of rejected responses, it's rounded down to seconds.
+    pub fn retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// Number of requests in flight.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for MaxInFlight {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, next: Next) -> Result {
+        let in_flight = InFlight::enter(&self.in_flight);
+        if in_flight.count > self.limit {
+            let mut response = ctx.resp_mut().await;
+            response.status = StatusCode::SERVICE_UNAVAILABLE;
+            response
+                .headers
+                .insert(RETRY_AFTER, HeaderValue::from(self.retry_after.as_secs()));
+            response.write_str("server is over capacity");
+            return Ok(());
+        }
+        next().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::max_in_flight;
+    use crate::core::App;
+    use async_std::task::{sleep, spawn};
+    use futures::future::join_all;
+    use http::header::RETRY_AFTER;
+    use http::StatusCode;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn shed_load() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(max_in_flight(2).retry_after(Duration::from_secs(3)))
+            .gate_fn(|_ctx, _next| async move {
+                sleep(Duration::from_millis(200)).await;
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let url = format!("http://{}", addr);
+        let tasks = (0..10).map(|_| {
+            let url = url.clone();
+            tokio::spawn(async move { reqwest::get(&url).await })
+        });
+        let (mut ok, mut rejected) = (0, 0);
+        for resp in join_all(tasks).await {
+            let resp = resp??;
+            match resp.status() {
+                StatusCode::OK => ok += 1,
+                StatusCode::SERVICE_UNAVAILABLE => {
+                    assert_eq!("3", resp.headers()[RETRY_AFTER]);
+                    assert_eq!("server is over capacity", resp.text().await?);
+                    rejected += 1;
+                }
+                status => panic!("unexpected status {}", status),
+            }
+        }
+        assert!(ok >= 2);
+        assert!(rejected > 0);
+
+        // capacity is released
+        let resp = reqwest::get(&url).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+//! The signed_url module of roa.
+//! This module provides a function `sign` to make time-limited urls,
+//! and a middleware `VerifySignedUrl` to verify them.
+//!
+//! A signed url carries its expiry in query "expires" as unix timestamp,
+//! and a HMAC-SHA256 signature of the path and the expiry in query "signature".
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::signed_url::{sign, verify_signed_url};
+//! use roa::query::query_parser;
+//! use roa::core::App;
+//! use async_std::task::spawn;
+//! use http::StatusCode;
+//! use std::time::{Duration, SystemTime};
+//!
+//! const SECRET: &[u8] = b"123456";
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let (addr, server) = App::new(())
+//!         .gate(query_parser)
+//!         .gate(verify_signed_url(SECRET))
+//!         .end(|mut ctx| async move {
+//!             ctx.resp_mut().await.write_str("file content");
+//!             Ok(())
+//!         })
+//!         .run_local()?;
+//!     spawn(server);
+//!     let expiry = SystemTime::now() + Duration::from_secs(60);
+//!     let url = sign("/download/report.pdf", expiry, SECRET);
+//!     let resp = reqwest::get(&format!("http://{}{}", addr, url)).await?;
+//!     assert_eq!(StatusCode::OK, resp.status());
+//!
+//!     let resp = reqwest::get(&format!("http://{}/download/report.pdf", addr)).await?;
+//!     assert_eq!(StatusCode::FORBIDDEN, resp.status());
+//!     Ok(())
+//! }
+//! ```
+
+use crate::core::{
+    async_trait, throw, Context, Middleware, Next, Result, State, StatusCode,
+};
+use crate::query::Query;
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Name of the query carrying expiry.
+pub const EXPIRES: &str = "expires";
+
+/// Name of the query carrying signature.
+pub const SIGNATURE: &str = "signature";
+
+/// A middleware to verify signed urls, returned by `verify_signed_url`.
+pub struct VerifySignedUrl {
+    key: Vec<u8>,
+}
+
+/// Sign a `path` until `expiry` with a shared secret `key`,
+/// return the path with queries "expires" and "signature".
+///
+/// The `path` should not contain query,
+/// other queries appended to the signed url are not covered by the signature.
+pub fn sign(path: &str, expiry: SystemTime, key: &[u8]) -> String {
+    let expires = expiry
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let signature = base64::encode_config(
+        mac(key, path, expires).finalize().into_bytes(),
+        base64::URL_SAFE_NO_PAD,
+    );
+    format!(
+        "{}?{}={}&{}={}",
+        path, EXPIRES, expires, SIGNATURE, signature
+    )
+}
+
+/// Verify signed urls made by `sign` with the same `key`.
+///
+/// - Throw 403 FORBIDDEN if "expires" or "signature" is missing,
+///   or the signature mismatches.
+/// - Throw 410 GONE if the signature matches but the url is expired.
+///
+/// The original path of request is verified, regardless of upstream rewriting.
+/// This middleware must be used in downstream of middleware `query_parser`.
+pub fn verify_signed_url(key: impl Into<Vec<u8>>) -> VerifySignedUrl {
+    VerifySignedUrl { key: key.into() }
+}
+
+/// HMAC-SHA256 of a path and its expiry.
+fn mac(key: &[u8], path: &str, expires: u64) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_varkey(key).expect("HMAC can take key of any size");
+    mac.update(path.as_bytes());
+    mac.update(b"\n");
+    mac.update(expires.to_string().as_bytes());
+    mac
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for VerifySignedUrl {
+    async fn handle(self: Arc<Self>, ctx: Context<S>, next: Next) -> Result {
+        let expires = ctx.query(EXPIRES).await.and_then(|v| v.parse::<u64>().ok());
+        let signature = ctx.query(SIGNATURE).await.and_then(|v| {
+            base64::decode_config(v.as_ref(), base64::URL_SAFE_NO_PAD).ok()
+        });
+        let (expires, signature) = match (expires, signature) {
+            (Some(expires), Some(signature)) => (expires, signature),
+            _ => throw!(StatusCode::FORBIDDEN, "url is not signed"),
+        };
+        let path = ctx.original_uri().path();
+        if mac(&self.key, path, expires).verify(&signature).is_err() {
+            throw!(StatusCode::FORBIDDEN, "invalid signature");
+        }
+        if UNIX_EPOCH + Duration::from_secs(expires) <= SystemTime::now() {
+            throw!(StatusCode::GONE, "url is expired");
+        }
+        next().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign, verify_signed_url};
+    use crate::core::App;
+    use crate::query::query_parser;
+    use async_std::task::spawn;
+    use http::StatusCode;
+    use std::time::{Duration, SystemTime};
+
+    const SECRET: &[u8] = b"123456";
+
+    #[tokio::test]
+    async fn round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(query_parser)
+            .gate(verify_signed_url(SECRET))
+            .end(|mut ctx| async move {
+                ctx.resp_mut().await.write_str("content");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let url = sign("/file", SystemTime::now() + Duration::from_secs(60), SECRET);
+        let resp = reqwest::get(&format!("http://{}{}", addr, url)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("content", resp.text().await?);
+
+        let url = sign("/file", SystemTime::now() - Duration::from_secs(1), SECRET);
+        let resp = reqwest::get(&format!("http://{}{}", addr, url)).await?;
+        assert_eq!(StatusCode::GONE, resp.status());
+        assert_eq!("url is expired", resp.text().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tamper() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(query_parser)
+            .gate(verify_signed_url(SECRET))
+            .end(|_ctx| async move { Ok(()) })
+            .run_local()?;
+        spawn(server);
+        let expiry = SystemTime::now() + Duration::from_secs(60);
+        let url = sign("/file", expiry, SECRET);
+
+        // unsigned
+        let resp = reqwest::get(&format!("http://{}/file", addr)).await?;
+        assert_eq!(StatusCode::FORBIDDEN, resp.status());
+        assert_eq!("url is not signed", resp.text().await?);
+
+        // another path
+        let tampered = url.replacen("/file", "/secret", 1);
+        let resp = reqwest::get(&format!("http://{}{}", addr, tampered)).await?;
+        assert_eq!(StatusCode::FORBIDDEN, resp.status());
+        assert_eq!("invalid signature", resp.text().await?);
+
+        // extended expiry
+        let expires = url
+            .split("expires=")
+            .nth(1)
+            .unwrap()
+            .split('&')
+            .next()
+            .unwrap();
+        let extended = (expires.parse::<u64>()? + 3600).to_string();
+        let tampered = url.replacen(expires, &extended, 1);
+        let resp = reqwest::get(&format!("http://{}{}", addr, tampered)).await?;
+        assert_eq!(StatusCode::FORBIDDEN, resp.status());
+
+        // another key
+        let forged = sign("/file", expiry, b"654321");
+        let resp = reqwest::get(&format!("http://{}{}", addr, forged)).await?;
+        assert_eq!(StatusCode::FORBIDDEN, resp.status());
+
+        // expired url with forged signature is still forbidden
+        let forged = sign(
+            "/file",
+            SystemTime::now() - Duration::from_secs(1),
+            b"654321",
+        );
+        let resp = reqwest::get(&format!("http://{}{}", addr, forged)).await?;
+        assert_eq!(StatusCode::FORBIDDEN, resp.status());
+        Ok(())
+    }
+}
@@ -0,0 +1,265 @@
+//! The status module of roa.
+//! This module provides a context extension `ResponseStatus`
+//! with readable helpers for common status codes.
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::core::{Context, Result};
+//! use roa::preload::*;
+//!
+//! async fn create(mut ctx: Context<()>) -> Result {
+//!     let name = match ctx.query("name").await {
+//!         Some(name) => name.into_value(),
+//!         None => return ctx.bad_request("query `name` is required").await,
+//!     };
+//!     ctx.resp_mut().await.write_str(name);
+//!     ctx.created().await
+//! }
+//! ```
+//!
+//! For uncommon status codes, set `ctx.resp_mut().await.status` directly.
+
+use crate::core::header::LOCATION;
+use crate::core::{async_trait, Context, Error, Result, State, StatusCode};
+use crate::preload::*;
+
+/// A context extension to set common status codes.
+///
+/// - 2xx helpers set status and return `Ok`, they can end a handler.
+/// - 3xx helpers set status and "Location" (except `not_modified`), then return `Ok`.
+/// - 4xx helpers return an exposed `Err` with the message, so the upstream can handle it.
+/// - 5xx helpers set status and write the message directly, then return `Ok`,
+///   see `roa::core::Error` for server errors.
+#[async_trait]
+pub trait ResponseStatus {
+    /// Set status 200 OK.
+    async fn ok(&mut self) -> Result;
+
+    /// Set status 201 CREATED.
+    async fn created(&mut self) -> Result;
+
+    /// Set status 202 ACCEPTED.
+    async fn accepted(&mut self) -> Result;
+
+    /// Set status 204 NO CONTENT.
+    async fn no_content(&mut self) -> Result;
+
+    /// Set status 301 MOVED PERMANENTLY and "Location".
+    async fn moved_permanently(&mut self, location: &str) -> Result;
+
+    /// Set status 302 FOUND and "Location".
+    async fn found(&mut self, location: &str) -> Result;
+
+    /// Set status 303 SEE OTHER and "Location".
+    async fn see_other(&mut self, location: &str) -> Result;
+
+    /// Set status 307 TEMPORARY REDIRECT and "Location".
+    async fn temporary_redirect(&mut self, location: &str) -> Result;
+
+    /// Set status 308 PERMANENT REDIRECT and "Location".
+    async fn permanent_redirect(&mut self, location: &str) -> Result;
+
+    /// Set status 304 NOT MODIFIED.
+    async fn not_modified(&mut self) -> Result;
+
+    /// Throw 400 BAD REQUEST.
+    async fn bad_request<M: ToString + Send>(&mut self, message: M) -> Result;
+
+    /// Throw 401 UNAUTHORIZED.
+    async fn unauthorized<M: ToString + Send>(&mut self, message: M) -> Result;
+
+    /// Throw 403 FORBIDDEN.
+    async fn forbidden<M: ToString + Send>(&mut self, message: M) -> Result;
+
+    /// Throw 404 NOT FOUND.
+    async fn not_found<M: ToString + Send>(&mut self, message: M) -> Result;
+
+    /// Throw 409 CONFLICT.
+    async fn conflict<M: ToString + Send>(&mut self, message: M) -> Result;
+
+    /// Throw 410 GONE.
+    async fn gone<M: ToString + Send>(&mut self, message: M) -> Result;
+
+    /// Throw 422 UNPROCESSABLE ENTITY.
+    async fn unprocessable_entity<M: ToString + Send>(&mut self, message: M) -> Result;
+
+    /// Throw 429 TOO MANY REQUESTS.
+    async fn too_many_requests<M: ToString + Send>(&mut self, message: M) -> Result;
+
+    /// Respond 500 INTERNAL SERVER ERROR with the message.
+    async fn internal_server_error<M: ToString + Send>(&mut self, message: M) -> Result;
+
+    /// Respond 503 SERVICE UNAVAILABLE with the message.
+    async fn service_unavailable<M: ToString + Send>(&mut self, message: M) -> Result;
+}
+
+async fn set_status<S: State>(ctx: &mut Context<S>, status: StatusCode) -> Result {
+    ctx.resp_mut().await.status = status;
+    Ok(())
+}
+
+async fn redirect<S: State>(
+    ctx: &mut Context<S>,
+    status: StatusCode,
+    location: &str,
+) -> Result {
+    let mut response = ctx.resp_mut().await;
+    response.insert(LOCATION, location)?;
+    response.status = status;
+    Ok(())
+}
+
+async fn server_error<S: State>(
+    ctx: &mut Context<S>,
+    status: StatusCode,
+    message: String,
+) -> Result {
+    let mut response = ctx.resp_mut().await;
+    response.status = status;
+    response.write_str(message);
+    Ok(())
+}
+
+fn client_error(status: StatusCode, message: String) -> Result {
+    Err(Error::new(status, message, true))
+}
+
+#[async_trait]
+impl<S: State> ResponseStatus for Context<S> {
+    async fn ok(&mut self) -> Result {
+        set_status(self, StatusCode::OK).await
+    }
+
+    async fn created(&mut self) -> Result {
+        set_status(self, StatusCode::CREATED).await
+    }
+
+    async fn accepted(&mut self) -> Result {
+        set_status(self, StatusCode::ACCEPTED).await
+    }
+
+    async fn no_content(&mut self) -> Result {
+        set_status(self, StatusCode::NO_CONTENT).await
+    }
+
+    async fn moved_permanently(&mut self, location: &str) -> Result {
+        redirect(self, StatusCode::MOVED_PERMANENTLY, location).await
+    }
+
+    async fn found(&mut self, location: &str) -> Result {
+        redirect(self, StatusCode::FOUND, location).await
+    }
+
+    async fn see_other(&mut self, location: &str) -> Result {
+        redirect(self, StatusCode::SEE_OTHER, location).await
+    }
+
+    async fn temporary_redirect(&mut self, location: &str) -> Result {
+        redirect(self, StatusCode::TEMPORARY_REDIRECT, location).await
+    }
+
+    async fn permanent_redirect(&mut self, location: &str) -> Result {
+        redirect(self, StatusCode::PERMANENT_REDIRECT, location).await
+    }
+
+    async fn not_modified(&mut self) -> Result {
+        set_status(self, StatusCode::NOT_MODIFIED).await
+    }
+
+    async fn bad_request<M: ToString + Send>(&mut self, message: M) -> Result {
+        client_error(StatusCode::BAD_REQUEST, message.to_string())
+    }
+
+    async fn unauthorized<M: ToString + Send>(&mut self, message: M) -> Result {
+        client_error(StatusCode::UNAUTHORIZED, message.to_string())
+    }
+
+    async fn forbidden<M: ToString + Send>(&mut self, message: M) -> Result {
+        client_error(StatusCode::FORBIDDEN, message.to_string())
+    }
+
+    async fn not_found<M: ToString + Send>(&mut self, message: M) -> Result {
+        client_error(StatusCode::NOT_FOUND, message.to_string())
+    }
+
+    async fn conflict<M: ToString + Send>(&mut self, message: M) -> Result {
+        client_error(StatusCode::CONFLICT, message.to_string())
+    }
+
+    async fn gone<M: ToString + Send>(&mut self, message: M) -> Result {
+        client_error(StatusCode::GONE, message.to_string())
+    }
+
+    async fn unprocessable_entity<M: ToString + Send>(&mut self, message: M) -> Result {
+        client_error(StatusCode::UNPROCESSABLE_ENTITY, message.to_string())
+    }
+
+    async fn too_many_requests<M: ToString + Send>(&mut self, message: M) -> Result {
+        client_error(StatusCode::TOO_MANY_REQUESTS, message.to_string())
+    }
+
+    async fn internal_server_error<M: ToString + Send>(&mut self, message: M) -> Result {
+        let message = message.to_string();
+        server_error(self, StatusCode::INTERNAL_SERVER_ERROR, message).await
+    }
+
+    async fn service_unavailable<M: ToString + Send>(&mut self, message: M) -> Result {
+        let message = message.to_string();
+        server_error(self, StatusCode::SERVICE_UNAVAILABLE, message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResponseStatus;
+    use crate::core::App;
+    use async_std::task::spawn;
+    use http::header::LOCATION;
+    use http::StatusCode;
+
+    #[tokio::test]
+    async fn status_helpers() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .end(|mut ctx| async move {
+                match ctx.uri().await.path() {
+                    "/created" => ctx.created().await,
+                    "/no-content" => ctx.no_content().await,
+                    "/see-other" => ctx.see_other("/user/1").await,
+                    "/not-modified" => ctx.not_modified().await,
+                    "/bad-request" => ctx.bad_request("invalid name").await,
+                    "/not-found" => ctx.not_found("user 1 not found").await,
+                    "/unavailable" => ctx.service_unavailable("maintaining").await,
+                    _ => ctx.ok().await,
+                }
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        let url = |path: &str| format!("http://{}{}", addr, path);
+        for (path, status, body) in &[
+            ("/", StatusCode::OK, ""),
+            ("/created", StatusCode::CREATED, ""),
+            ("/no-content", StatusCode::NO_CONTENT, ""),
+            ("/see-other", StatusCode::SEE_OTHER, ""),
+            ("/not-modified", StatusCode::NOT_MODIFIED, ""),
+            ("/bad-request", StatusCode::BAD_REQUEST, "invalid name"),
+            ("/not-found", StatusCode::NOT_FOUND, "user 1 not found"),
+            (
+                "/unavailable",
+                StatusCode::SERVICE_UNAVAILABLE,
+                "maintaining",
+            ),
+        ] {
+            let resp = client.get(&url(path)).send().await?;
+            assert_eq!(*status, resp.status());
+            if *status == StatusCode::SEE_OTHER {
+                assert_eq!("/user/1", resp.headers()[LOCATION]);
+            }
+            assert_eq!(*body, resp.text().await?);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+//! The tap module of roa, enabled by feature "tap".
+//! This module provides a middleware `Tap` to inspect request and response bodies,
+//! which is intended for debugging in development.
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::tap::tap;
+//! use roa::core::App;
+//! use async_std::task::spawn;
+//! use futures::AsyncReadExt;
+//! use log::debug;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let (addr, server) = App::new(())
+//!         .gate(
+//!             tap()
+//!                 .limit(1024)
+//!                 .request(|body| debug!("request: {:?}", body))
+//!                 .response(|body| debug!("response: {:?}", body)),
+//!         )
+//!         .end(|mut ctx| async move {
+//!             // the request body is still readable.
+//!             let mut data = String::new();
+//!             ctx.req_mut().await.read_to_string(&mut data).await?;
+//!             ctx.resp_mut().await.write_str(data);
+//!             Ok(())
+//!         })
+//!         .run_local()?;
+//!     spawn(server);
+//!     let resp = reqwest::Client::new()
+//!         .post(&format!("http://{}", addr))
+//!         .body("Hello, World!")
+//!         .send()
+//!         .await?;
+//!     assert_eq!("Hello, World!", resp.text().await?);
+//!     Ok(())
+//! }
+//! ```
+
+use crate::core::{async_trait, Body, Context, Middleware, Next, Result, State};
+use futures::AsyncReadExt;
+use std::sync::Arc;
+
+/// A copy of the leading bytes of a body, passed to callbacks of `Tap`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TapBody {
+    /// The leading bytes, no more than the limit.
+    pub data: Vec<u8>,
+
+    /// Whether the body is longer than the limit.
+    pub truncated: bool,
+}
+
+type Callback = Box<dyn 'static + Sync + Send + Fn(&TapBody)>;
+
+/// A middleware to invoke callbacks with a copy of request and response bodies,
+/// returned by `tap`.
+pub struct Tap {
+    limit: usize,
+    request: Option<Callback>,
+    response: Option<Callback>,
+}
+
+/// Tap request and response bodies, buffering up to 4 KiB of each body by default.
+///
+/// The bodies are not consumed: the buffered bytes are replayed before the rest of body,
+/// so downstream and the client read the same bytes as without `Tap`.
+///
+/// ### Performance
+///
+/// Don't use it in production.
+///
+/// - Up to `limit` bytes of each body are buffered in memory and copied for callbacks.
+/// - The response is tapped after downstream returns, and the first byte
+///   is not sent until `limit` bytes of response body are produced or it ends,
+///   which breaks streaming responses like server-sent events.
+pub fn tap() -> Tap {
+    Tap {
+        limit: 4096,
+        request: None,
+        response: None,
+    }
+}
+
+impl Tap {
+    /// Set the max number of bytes buffered of each body.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Invoke `callback` with the request body before calling downstream.
+    pub fn request(
+        mut self,
+        callback: impl 'static + Sync + Send + Fn(&TapBody),
+    ) -> Self {
+        self.request = Some(Box::new(callback));
+        self
+    }
+
+    /// Invoke `callback` with the response body after downstream returns `Ok`.
+    pub fn response(
+        mut self,
+        callback: impl 'static + Sync + Send + Fn(&TapBody),
+    ) -> Self {
+        self.response = Some(Box::new(callback));
+        self
+    }
+
+    /// Buffer leading bytes of `body` and replace it with a replayable one.
+    async fn tap(&self, body: &mut Body, callback: &Callback) -> Result {
+        let mut origin = std::mem::take(body);
+        let mut data = Vec::new();
+        (&mut origin)
+            .take(self.limit as u64 + 1)
+            .read_to_end(&mut data)
+            .await?;
+        let truncated = data.len() > self.limit;
+        let mut tapped = TapBody { data, truncated };
+        if truncated {
+            body.write_bytes(tapped.data.clone()).write_buf(origin);
+            tapped.data.truncate(self.limit);
+        } else {
+            body.write_bytes(tapped.data.clone());
+        }
+        callback(&tapped);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for Tap {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, next: Next) -> Result {
+        if let Some(ref callback) = self.request {
+            self.tap(&mut **ctx.req_mut().await, callback).await?;
+        }
+        next().await?;
+        if let Some(ref callback) = self.response {
+            self.tap(&mut **ctx.resp_mut().await, callback).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tap, TapBody};
+    use crate::core::App;
+    use async_std::task::spawn;
+    use futures::AsyncReadExt;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn tap_bodies() -> Result<(), Box<dyn std::error::Error>> {
+        let tapped = Arc::new(Mutex::new(Vec::new()));
+        let requests = tapped.clone();
+        let responses = tapped.clone();
+        let (addr, server) = App::new(())
+            .gate(
+                tap()
+                    .limit(5)
+                    .request(move |body| requests.lock().unwrap().push(body.clone()))
+                    .response(move |body| responses.lock().unwrap().push(body.clone())),
+            )
+            .end(|mut ctx| async move {
+                let mut data = String::new();
+                ctx.req_mut().await.read_to_string(&mut data).await?;
+                ctx.resp_mut().await.write_str(data.to_uppercase());
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}", addr);
+
+        let resp = client.post(&url).body("Hello, World!").send().await?;
+        assert_eq!("HELLO, WORLD!", resp.text().await?);
+        let resp = client.post(&url).body("Hi").send().await?;
+        assert_eq!("HI", resp.text().await?);
+
+        let body = |data: &[u8], truncated| TapBody {
+            data: data.to_vec(),
+            truncated,
+        };
+        assert_eq!(
+            vec![
+                body(b"Hello", true),
+                body(b"HELLO", true),
+                body(b"Hi", false),
+                body(b"HI", false),
+            ],
+            *tapped.lock().unwrap()
+        );
+        Ok(())
+    }
+}
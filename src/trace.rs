@@ -0,0 +1,205 @@
+//! The trace module of roa.
+//! This module provides a middleware `tracer`,
+//! an alternative of `logger` for users of crate `tracing`.
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::trace::tracer;
+//! use roa::body::PowerBody;
+//! use roa::core::{App, StatusCode};
+//! use async_std::task::spawn;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let (addr, server) = App::new(())
+//!         .gate(tracer)
+//!         .end(|mut ctx| async move {
+//!             tracing::info!("correlated with the request");
+//!             ctx.write_text("Hello, World!").await
+//!         })
+//!         .run_local()?;
+//!     spawn(server);
+//!     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+//!     assert_eq!(StatusCode::OK, resp.status());
+//!     Ok(())
+//! }
+//! ```
+
+use crate::core::{Context, Model, Next, Result};
+use tracing::field::Empty;
+use tracing::{error, info, info_span, Instrument};
+
+pub use crate::request_id::REQUEST_ID;
+
+/// A middleware to open a span named "request" for each request,
+/// with fields `method`, `path`, `request_id` (from "X-Request-Id", if any) and `status`.
+///
+/// The span is entered whenever downstream middleware are polled,
+/// so their `tracing` events are correlated with the request.
+pub async fn tracer<M: Model>(ctx: Context<M>, next: Next) -> Result {
+    let method = ctx.method().await;
+    let uri = ctx.uri().await;
+    let span = info_span!(
+        "request",
+        method = %method,
+        path = %uri.path(),
+        request_id = Empty,
+        status = Empty,
+    );
+    if let Some(Ok(request_id)) = ctx.header(REQUEST_ID).await {
+        span.record("request_id", request_id.as_str());
+    }
+    let result = next().instrument(span.clone()).await;
+    let status = match result {
+        Ok(()) => ctx.status().await,
+        Err(ref err) => err.status_code,
+    };
+    span.record("status", status.as_u16());
+    span.in_scope(|| match result {
+        Ok(()) => info!("response {}", status),
+        Err(ref err) => error!("response {}: {}", status, err.message),
+    });
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tracer;
+    use crate::core::{throw, App, StatusCode};
+    use async_std::task::spawn;
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    type Fields = HashMap<String, String>;
+
+    /// Messages of events and ids of spans they are in.
+    type Events = Vec<(Option<u64>, String)>;
+
+    /// A subscriber recording fields of spans and their events.
+    #[derive(Default)]
+    struct Recorder {
+        next_id: AtomicU64,
+        spans: Arc<Mutex<HashMap<u64, Fields>>>,
+        events: Arc<Mutex<Events>>,
+        current: Mutex<Vec<u64>>,
+    }
+
+    struct FieldsVisitor<'a>(&'a mut Fields);
+
+    impl Visit for FieldsVisitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+            let mut fields = Fields::new();
+            span.record(&mut FieldsVisitor(&mut fields));
+            self.spans.lock().unwrap().insert(id, fields);
+            Id::from_u64(id)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            if let Some(fields) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+                values.record(&mut FieldsVisitor(fields));
+            }
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields::new();
+            event.record(&mut FieldsVisitor(&mut fields));
+            let current = self.current.lock().unwrap().last().copied();
+            let message = fields.remove("message").unwrap_or_default();
+            self.events.lock().unwrap().push((current, message));
+        }
+
+        fn enter(&self, span: &Id) {
+            self.current.lock().unwrap().push(span.into_u64());
+        }
+
+        fn exit(&self, span: &Id) {
+            let mut current = self.current.lock().unwrap();
+            if let Some(index) = current.iter().rposition(|id| *id == span.into_u64()) {
+                current.remove(index);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn request_span() -> Result<(), Box<dyn std::error::Error>> {
+        let recorder = Recorder::default();
+        let spans = recorder.spans.clone();
+        let events = recorder.events.clone();
+        tracing::subscriber::set_global_default(recorder)?;
+
+        let (addr, server) = App::new(())
+            .gate(tracer)
+            .end(|ctx| async move {
+                tracing::info!("handling");
+                if ctx.uri().await.path() == "/fail" {
+                    throw!(StatusCode::BAD_REQUEST, "bad request")
+                }
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let resp = reqwest::Client::new()
+            .get(&format!("http://{}/user", addr))
+            .header("x-request-id", "abc")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        let resp = reqwest::get(&format!("http://{}/fail", addr)).await?;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+
+        let spans = spans.lock().unwrap();
+        let find = |path: &str| {
+            spans
+                .iter()
+                .find(|(_, fields)| fields.get("path").map(String::as_str) == Some(path))
+                .map(|(id, fields)| (*id, fields.clone()))
+                .unwrap()
+        };
+        let (user_id, user) = find("/user");
+        assert_eq!("GET", user["method"]);
+        assert_eq!("abc", user["request_id"]);
+        assert_eq!("200", user["status"]);
+        let (fail_id, fail) = find("/fail");
+        assert!(!fail.contains_key("request_id"));
+        assert_eq!("400", fail["status"]);
+
+        let events = events.lock().unwrap();
+        let messages = |id| {
+            events
+                .iter()
+                .filter(|(span, _)| *span == Some(id))
+                .map(|(_, message)| message.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["handling", "response 200 OK"], messages(user_id));
+        assert_eq!(
+            vec!["handling", "response 400 Bad Request: bad request"],
+            messages(fail_id)
+        );
+        Ok(())
+    }
+}
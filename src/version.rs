@@ -0,0 +1,103 @@
+//! The version module of roa.
+//! This module provides a middleware `MinHttpVersion` to reject old http versions.
+//!
+//! The version of incoming request can be got by `Context::version`.
+//!
+//! ### Example
+//!
+//! ```rust
+//! use roa::version::min_http_version;
+//! use roa::core::App;
+//! use async_std::task::spawn;
+//! use http::{StatusCode, Version};
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let (addr, server) = App::new(())
+//!         .gate(min_http_version(Version::HTTP_11))
+//!         .end(|ctx| async move {
+//!             assert_eq!(Version::HTTP_11, ctx.version().await);
+//!             Ok(())
+//!         })
+//!         .run_local()?;
+//!     spawn(server);
+//!     let resp = reqwest::get(&format!("http://{}", addr)).await?;
+//!     assert_eq!(StatusCode::OK, resp.status());
+//!     Ok(())
+//! }
+//! ```
+
+use crate::core::{async_trait, Context, Middleware, Next, Result, State, StatusCode};
+use http::Version;
+use std::sync::Arc;
+
+/// A middleware to reject requests older than a minimum http version,
+/// returned by `min_http_version`.
+pub struct MinHttpVersion {
+    version: Version,
+}
+
+/// Respond 505 HTTP VERSION NOT SUPPORTED to requests older than `version`,
+/// others are passed to downstream.
+///
+/// The response is written directly instead of thrown,
+/// see `roa::core::Error` for server errors.
+pub fn min_http_version(version: Version) -> MinHttpVersion {
+    MinHttpVersion { version }
+}
+
+#[async_trait]
+impl<S: State> Middleware<S> for MinHttpVersion {
+    async fn handle(self: Arc<Self>, mut ctx: Context<S>, next: Next) -> Result {
+        let version = ctx.version().await;
+        if version < self.version {
+            let mut response = ctx.resp_mut().await;
+            response.status = StatusCode::HTTP_VERSION_NOT_SUPPORTED;
+            response.write_str(format!(
+                "{:?} is not supported, use {:?} or later",
+                version, self.version
+            ));
+            return Ok(());
+        }
+        next().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::min_http_version;
+    use crate::core::App;
+    use async_std::net::TcpStream;
+    use async_std::task::spawn;
+    use futures::{AsyncReadExt, AsyncWriteExt};
+    use http::{StatusCode, Version};
+
+    #[tokio::test]
+    async fn reject_http10() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, server) = App::new(())
+            .gate(min_http_version(Version::HTTP_11))
+            .end(|mut ctx| async move {
+                ctx.resp_mut().await.write_str("Hello, World");
+                Ok(())
+            })
+            .run_local()?;
+        spawn(server);
+        let mut stream = TcpStream::connect(addr).await?;
+        stream
+            .write_all(b"GET / HTTP/1.0\r\nHost: localhost\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        assert!(
+            response.starts_with("HTTP/1.0 505 HTTP Version Not Supported\r\n"),
+            "unexpected response: {}",
+            response
+        );
+        assert!(response.ends_with("HTTP/1.0 is not supported, use HTTP/1.1 or later"));
+
+        let resp = reqwest::get(&format!("http://{}", addr)).await?;
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("Hello, World", resp.text().await?);
+        Ok(())
+    }
+}